@@ -1,20 +1,44 @@
+mod energy;
+
+use std::f32::consts::PI;
+
 use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
 use bevy_inspector_egui::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use bevy_prototype_debug_lines::{DebugLines, DebugLinesPlugin};
+use energy::{Energy, EnergyDiagnosticsPlugin, StepEnergy};
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugin(WorldInspectorPlugin::default())
+        .add_plugin(DebugLinesPlugin::default())
+        .add_plugin(EnergyDiagnosticsPlugin)
         .insert_resource(ClearColor(Color::WHITE))
         .init_resource::<Config>()
+        .init_resource::<BallAssets>()
+        .init_resource::<Launcher>()
         .add_startup_system(setup)
         .add_system(simulate)
+        .add_system(launch)
+        .add_system(draw_trajectory.after(launch))
         .register_type::<Config>()
         .register_type::<Velocity>()
+        .register_type::<Mass>()
         .run();
 }
 
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum DragModel {
+    // the plain projectile, gravity only
+    #[default]
+    None,
+    // slow and small, like a ball in honey
+    Linear,
+    // fast and large, like a ball through air
+    Quadratic,
+}
+
 #[derive(Reflect, Resource, InspectorOptions)]
 #[reflect(Resource, InspectorOptions)]
 struct Config {
@@ -22,6 +46,21 @@ struct Config {
     #[inspector(min = 0, max = 100)]
     sub_steps: u32,
     gravity: Vec2,
+    drag: DragModel,
+    // N s/m
+    linear_drag: f32,
+    // 0.47 for a sphere
+    drag_coefficient: f32,
+    // kg/m^3
+    air_density: f32,
+    // m/s, the air moves and the drag pulls balls along with it
+    wind: Vec2,
+    // kg
+    ball_mass: f32,
+    // launch speed per meter the mouse is pulled back
+    launch_strength: f32,
+    // seconds of the predicted path to draw
+    prediction_time: f32,
 }
 
 impl Default for Config {
@@ -30,10 +69,47 @@ impl Default for Config {
             scale: 5.,
             sub_steps: 5,
             gravity: Vec2::new(0., -9.81),
+            drag: DragModel::None,
+            linear_drag: 100.,
+            drag_coefficient: 0.47,
+            air_density: 1.225,
+            wind: Vec2::ZERO,
+            ball_mass: 1000.,
+            launch_strength: 2.,
+            prediction_time: 3.,
         }
     }
 }
 
+// where a mouse drag started and where it is now, pulling back sets the launch velocity
+#[derive(Resource, Default)]
+struct Launcher {
+    start: Option<Vec2>,
+    end: Vec2,
+    velocity: Vec2,
+}
+
+const BALL_SIZE: f32 = 5.0;
+
+#[derive(Resource)]
+struct BallAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<ColorMaterial>,
+}
+
+impl FromWorld for BallAssets {
+    fn from_world(world: &mut World) -> Self {
+        let scale = world.resource::<Config>().scale;
+        let mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(shape::Circle::new(BALL_SIZE * scale).into());
+        let material = world
+            .resource_mut::<Assets<ColorMaterial>>()
+            .add(ColorMaterial::from(Color::RED));
+        Self { mesh, material }
+    }
+}
+
 #[derive(Component)]
 struct Ball(pub f32);
 
@@ -41,48 +117,129 @@ struct Ball(pub f32);
 #[reflect(Component)]
 struct Velocity(pub Vec2);
 
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    config: Res<Config>,
-) {
+#[derive(Reflect, Component, Default, Deref, DerefMut)]
+#[reflect(Component)]
+struct Mass(pub f32);
+
+fn setup(mut commands: Commands, ball_assets: Res<BallAssets>, config: Res<Config>) {
     commands.spawn(Camera2dBundle {
         transform: Transform::from_xyz(0., 0., 100.),
         ..Default::default()
     });
 
     // Ball
-    let size = 5.0;
+    spawn_ball(
+        &mut commands,
+        &ball_assets,
+        &config,
+        Vec2::ZERO,
+        Vec2::new(-20., 10.) * config.scale,
+    );
+
+    info!("Drag with the left mouse button and let go to launch a ball");
+}
+
+fn spawn_ball(
+    commands: &mut Commands,
+    ball_assets: &BallAssets,
+    config: &Config,
+    pos: Vec2,
+    velocity: Vec2,
+) {
     commands.spawn((
         MaterialMesh2dBundle {
-            mesh: meshes
-                .add(shape::Circle::new(size * config.scale).into())
-                .into(),
-            material: materials.add(ColorMaterial::from(Color::RED)),
-            transform: Transform::from_xyz(0., 0., 0.),
+            mesh: ball_assets.mesh.clone().into(),
+            material: ball_assets.material.clone(),
+            transform: Transform::from_translation(pos.extend(0.)),
             ..default()
         },
-        Velocity(Vec2::new(-20., 10.) * config.scale),
-        Ball(size),
+        Velocity(velocity),
+        Mass(config.ball_mass),
+        Ball(BALL_SIZE),
         Name::new("Ball"),
     ));
 }
 
+// the air pushes against the velocity relative to the wind, spread over the ball's cross-section
+fn drag_acceleration(velocity: Vec2, radius: f32, mass: f32, config: &Config) -> Vec2 {
+    if mass <= 0.0 {
+        return Vec2::ZERO;
+    }
+    let relative = velocity - config.wind;
+    let force = match config.drag {
+        DragModel::None => Vec2::ZERO,
+        DragModel::Linear => -config.linear_drag * relative,
+        DragModel::Quadratic => {
+            let area = PI * radius * radius;
+            -0.5 * config.air_density
+                * config.drag_coefficient
+                * area
+                * relative.length()
+                * relative
+        }
+    };
+    force / mass
+}
+
+// one sub step, shared with the trajectory prediction so the drawn path is the one flown
+fn step(pos: &mut Vec3, velocity: &mut Vec2, radius: f32, mass: f32, sdt: f32, config: &Config) {
+    let acc = config.gravity + drag_acceleration(*velocity, radius, mass, config);
+    *velocity += acc * sdt * config.scale;
+    *pos += (*velocity * sdt * config.scale).extend(0.);
+}
+
+fn keep_in_bounds(pos: &mut Vec3, velocity: &mut Vec2, limit: Vec2) {
+    if pos.x < -limit.x {
+        pos.x = -limit.x;
+        velocity.x = -velocity.x;
+    }
+
+    if pos.x > limit.x {
+        pos.x = limit.x;
+        velocity.x = -velocity.x;
+    }
+
+    if pos.y < -limit.y {
+        pos.y = -limit.y;
+        velocity.y = -velocity.y;
+    }
+
+    if pos.y > limit.y {
+        pos.y = limit.y;
+        velocity.y = -velocity.y;
+    }
+}
+
 fn simulate(
-    mut query: Query<(&mut Transform, &mut Velocity, &Ball)>,
+    mut query: Query<(&mut Transform, &mut Velocity, &Mass, &Ball)>,
     window_query: Query<&Window>,
     time: Res<Time>,
     config: Res<Config>,
+    mut step_energy: ResMut<StepEnergy>,
 ) {
     let window = window_query.single();
     let sdt = time.delta_seconds() / config.sub_steps as f32;
 
-    for (mut trans, mut velocity, ball) in query.iter_mut() {
+    for (mut trans, mut velocity, mass, ball) in query.iter_mut() {
         // sub steps
-        for _ in 0..config.sub_steps {
-            velocity.0 += config.gravity * sdt * config.scale;
-            trans.translation += (velocity.0 * sdt * config.scale).extend(0.);
+        for i in 0..config.sub_steps as usize {
+            step(
+                &mut trans.translation,
+                &mut velocity.0,
+                ball.0,
+                mass.0,
+                sdt,
+                &config,
+            );
+            step_energy.add(
+                i,
+                Energy::particle(
+                    mass.0,
+                    trans.translation.truncate() / config.scale,
+                    velocity.0,
+                    config.gravity,
+                ),
+            );
         }
 
         // keep ball in bounds
@@ -90,24 +247,92 @@ fn simulate(
             window.width() * 0.5 - (ball.0 * config.scale),
             window.height() * 0.5 - (ball.0 * config.scale),
         );
-        if trans.translation.x < -limit.x {
-            trans.translation.x = -limit.x;
-            velocity.0.x = -velocity.0.x;
-        }
+        keep_in_bounds(&mut trans.translation, &mut velocity.0, limit);
+    }
+}
 
-        if trans.translation.x > limit.x {
-            trans.translation.x = limit.x;
-            velocity.0.x = -velocity.0.x;
-        }
+fn cursor_world_pos(
+    window: &Window,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let (camera, camera_trans) = camera_query.single();
+    let cursor_pos = window.cursor_position()?;
+    let ray = camera.viewport_to_world(camera_trans, cursor_pos)?;
+    Some(ray.origin.truncate())
+}
 
-        if trans.translation.y < -limit.y {
-            trans.translation.y = -limit.y;
-            velocity.0.y = -velocity.0.y;
-        }
+fn launch(
+    mut commands: Commands,
+    ball_assets: Res<BallAssets>,
+    mut launcher: ResMut<Launcher>,
+    mouse: Res<Input<MouseButton>>,
+    window_query: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    config: Res<Config>,
+) {
+    let window = window_query.single();
+    let cursor = cursor_world_pos(window, &camera_query);
+
+    if mouse.just_pressed(MouseButton::Left) {
+        launcher.start = cursor;
+    }
+
+    let Some(start) = launcher.start else {
+        return;
+    };
+    // pulled back like a slingshot, the last position is kept when the cursor leaves the window
+    if let Some(cursor) = cursor {
+        launcher.end = cursor;
+        launcher.velocity = (start - cursor) / config.scale * config.launch_strength;
+    }
+
+    if mouse.just_released(MouseButton::Left) {
+        spawn_ball(
+            &mut commands,
+            &ball_assets,
+            &config,
+            start,
+            launcher.velocity,
+        );
+        launcher.start = None;
+    }
+}
+
+fn draw_trajectory(
+    mut lines: ResMut<DebugLines>,
+    launcher: Res<Launcher>,
+    window_query: Query<&Window>,
+    config: Res<Config>,
+) {
+    let Some(start) = launcher.start else {
+        return;
+    };
+    let window = window_query.single();
+    let limit = Vec2::new(
+        window.width() * 0.5 - (BALL_SIZE * config.scale),
+        window.height() * 0.5 - (BALL_SIZE * config.scale),
+    );
 
-        if trans.translation.y > limit.y {
-            trans.translation.y = limit.y;
-            velocity.0.y = -velocity.0.y;
+    lines.line_colored(start.extend(0.), launcher.end.extend(0.), 0., Color::GRAY);
+
+    // frames as long as the default 60 fps, with the same sub steps as the simulation
+    let dt = 1. / 60.;
+    let sdt = dt / config.sub_steps.max(1) as f32;
+    let mut pos = start.extend(0.);
+    let mut velocity = launcher.velocity;
+    for _ in 0..(config.prediction_time / dt) as u32 {
+        let prev = pos;
+        for _ in 0..config.sub_steps {
+            step(
+                &mut pos,
+                &mut velocity,
+                BALL_SIZE,
+                config.ball_mass,
+                sdt,
+                &config,
+            );
         }
+        keep_in_bounds(&mut pos, &mut velocity, limit);
+        lines.line_colored(prev, pos, 0., Color::BLUE);
     }
 }
@@ -13,3 +13,8 @@ impl Default for Ball {
 #[derive(Reflect, Component, Default, Deref, DerefMut)]
 #[reflect(Component)]
 pub struct Velocity(pub Vec3);
+
+// world space, rad / s
+#[derive(Reflect, Component, Default, Deref, DerefMut)]
+#[reflect(Component)]
+pub struct AngularVelocity(pub Vec3);
@@ -0,0 +1,135 @@
+use std::{iter::Sum, ops::Add};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics, LogDiagnosticsPlugin},
+    prelude::*,
+};
+
+use crate::simulate;
+
+// summed over the balls after each substep, spin included
+pub const KINETIC_ENERGY: DiagnosticId =
+    DiagnosticId::from_u128(287349205193735460150288716924375128337);
+pub const POTENTIAL_ENERGY: DiagnosticId =
+    DiagnosticId::from_u128(75628130970124336610943318709841521994);
+pub const TOTAL_ENERGY: DiagnosticId =
+    DiagnosticId::from_u128(159874633285117640072569326617004235911);
+pub const LINEAR_MOMENTUM: DiagnosticId =
+    DiagnosticId::from_u128(331542086719304577198046623550987260142);
+pub const ANGULAR_MOMENTUM: DiagnosticId =
+    DiagnosticId::from_u128(48231695026615902237412804536793357470);
+
+pub struct EnergyDiagnosticsPlugin;
+
+impl Plugin for EnergyDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StepEnergy>()
+            .add_startup_system(setup_diagnostics)
+            .add_system(record_energy.after(simulate))
+            // there's no text overlay here, the averages are logged every second instead
+            .add_plugin(LogDiagnosticsPlugin::filtered(vec![
+                KINETIC_ENERGY,
+                POTENTIAL_ENERGY,
+                TOTAL_ENERGY,
+                LINEAR_MOMENTUM,
+                ANGULAR_MOMENTUM,
+            ]));
+    }
+}
+
+// potential energy is zero on the ground, angular momentum is about the origin
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Energy {
+    pub kinetic: f32,
+    pub potential: f32,
+    pub linear_momentum: Vec3,
+    pub angular_momentum: Vec3,
+}
+
+impl Energy {
+    pub fn particle(mass: f32, position: Vec3, velocity: Vec3, gravity: Vec3) -> Self {
+        let momentum = velocity * mass;
+        Self {
+            kinetic: 0.5 * mass * velocity.length_squared(),
+            potential: -mass * gravity.dot(position),
+            linear_momentum: momentum,
+            angular_momentum: position.cross(momentum),
+        }
+    }
+
+    // inertia is the diagonal of the inertia tensor in the body's local frame
+    pub fn rigid_body(
+        mass: f32,
+        inertia: Vec3,
+        position: Vec3,
+        rotation: Quat,
+        velocity: Vec3,
+        omega: Vec3,
+        gravity: Vec3,
+    ) -> Self {
+        let local = rotation.inverse() * omega;
+        let mut energy = Self::particle(mass, position, velocity, gravity);
+        energy.kinetic += 0.5 * local.dot(inertia * local);
+        energy.angular_momentum += rotation * (inertia * local);
+        energy
+    }
+
+    pub fn total(&self) -> f32 {
+        self.kinetic + self.potential
+    }
+}
+
+impl Add for Energy {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            kinetic: self.kinetic + other.kinetic,
+            potential: self.potential + other.potential,
+            linear_momentum: self.linear_momentum + other.linear_momentum,
+            angular_momentum: self.angular_momentum + other.angular_momentum,
+        }
+    }
+}
+
+impl Sum for Energy {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+// the energy after every substep of this frame, both simulate systems fill it in
+#[derive(Resource, Default)]
+pub struct StepEnergy(Vec<Energy>);
+
+impl StepEnergy {
+    pub fn add(&mut self, step: usize, energy: Energy) {
+        if self.0.len() <= step {
+            self.0.resize(step + 1, Energy::default());
+        }
+        self.0[step] = self.0[step] + energy;
+    }
+}
+
+fn setup_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(KINETIC_ENERGY, "kinetic_energy", 20).with_suffix("J"));
+    diagnostics.add(Diagnostic::new(POTENTIAL_ENERGY, "potential_energy", 20).with_suffix("J"));
+    diagnostics.add(Diagnostic::new(TOTAL_ENERGY, "total_energy", 20).with_suffix("J"));
+    diagnostics.add(Diagnostic::new(LINEAR_MOMENTUM, "linear_momentum", 20).with_suffix("kg m/s"));
+    diagnostics.add(
+        Diagnostic::new(ANGULAR_MOMENTUM, "angular_momentum", 20).with_suffix("kg m^2/s"),
+    );
+}
+
+// one measurement per substep
+fn record_energy(mut steps: ResMut<StepEnergy>, mut diagnostics: ResMut<Diagnostics>) {
+    for energy in steps.0.drain(..) {
+        diagnostics.add_measurement(KINETIC_ENERGY, || energy.kinetic as f64);
+        diagnostics.add_measurement(POTENTIAL_ENERGY, || energy.potential as f64);
+        diagnostics.add_measurement(TOTAL_ENERGY, || energy.total() as f64);
+        diagnostics.add_measurement(LINEAR_MOMENTUM, || energy.linear_momentum.length() as f64);
+        diagnostics.add_measurement(ANGULAR_MOMENTUM, || {
+            energy.angular_momentum.length() as f64
+        });
+    }
+}
@@ -1,4 +1,6 @@
 mod components;
+mod contact;
+mod energy;
 mod reset;
 mod resources;
 
@@ -6,6 +8,8 @@ use bevy::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
 use components::*;
+use contact::*;
+use energy::*;
 use reset::*;
 use resources::*;
 use std::f32::consts::*;
@@ -15,6 +19,7 @@ fn main() {
         .add_plugins(DefaultPlugins)
         .add_plugin(WorldInspectorPlugin::default())
         .add_plugin(ResetPlugin)
+        .add_plugin(EnergyDiagnosticsPlugin)
         .init_resource::<Config>()
         .add_startup_system(setup)
         .add_system(simulate)
@@ -22,6 +27,8 @@ fn main() {
         .register_type::<Config>()
         .register_type::<Ball>()
         .register_type::<Velocity>()
+        .register_type::<AngularVelocity>()
+        .register_type::<ContactMaterial>()
         .run();
 }
 
@@ -100,60 +107,73 @@ fn spawn_ball(
             ..default()
         },
         Velocity(Vec3::new(-3., 8., -6.) * config.scale),
+        AngularVelocity::default(),
+        ContactMaterial::default(),
         Ball(size),
         Name::new("Ball"),
     ));
 }
 
 fn simulate(
-    mut query: Query<(&mut Transform, &mut Velocity, &Ball)>,
+    mut query: Query<(
+        &mut Transform,
+        &mut Velocity,
+        &mut AngularVelocity,
+        &Ball,
+        &ContactMaterial,
+    )>,
     time: Res<Time>,
     config: Res<Config>,
+    mut step_energy: ResMut<StepEnergy>,
 ) {
     let sdt = time.delta_seconds() / config.sub_steps as f32;
 
-    for (mut trans, mut velocity, ball) in query.iter_mut() {
+    // ground and walls, normals point into the box
+    let walls = [
+        (Vec3::Y, 0.0),
+        (Vec3::NEG_Y, -config.half_size),
+        (Vec3::X, -config.half_size),
+        (Vec3::NEG_X, -config.half_size),
+        (Vec3::Z, -config.half_size),
+        (Vec3::NEG_Z, -config.half_size),
+    ];
+    // slower than gravity adds in a couple of steps is resting, not bouncing
+    let rest_speed = 2.0 * config.gravity.length() * sdt * config.scale;
+
+    for (mut trans, mut velocity, mut omega, ball, material) in query.iter_mut() {
+        // the ball has no mass of its own, it counts as a solid 1 kg sphere
+        let inertia = Vec3::splat(0.4 * ball.0 * ball.0);
+
         // sub steps
-        for _ in 0..config.sub_steps {
+        for step in 0..config.sub_steps as usize {
             velocity.0 += config.gravity * sdt * config.scale;
-            trans.translation += velocity.0 * sdt * config.scale
-        }
-
-        // keep ball in bounds
-        let limit = Vec3::new(
-            config.half_size - ball.0,
-            config.half_size - ball.0,
-            config.half_size - ball.0,
-        );
-        if trans.translation.x < -limit.x {
-            trans.translation.x = -limit.x;
-            velocity.0.x = -velocity.0.x;
-        }
-
-        if trans.translation.x > limit.x {
-            trans.translation.x = limit.x;
-            velocity.0.x = -velocity.0.x;
-        }
-
-        if trans.translation.y < ball.0 {
-            // limit to ground + ball radius
-            trans.translation.y = ball.0;
-            velocity.0.y = -velocity.0.y;
-        }
-
-        if trans.translation.y > limit.y {
-            trans.translation.y = limit.y;
-            velocity.0.y = -velocity.0.y;
-        }
-
-        if trans.translation.z < -limit.z {
-            trans.translation.z = -limit.z;
-            velocity.0.z = -velocity.0.z;
-        }
-
-        if trans.translation.z > limit.z {
-            trans.translation.z = limit.z;
-            velocity.0.z = -velocity.0.z;
+            trans.translation += velocity.0 * sdt * config.scale;
+            trans.rotation = Quat::from_scaled_axis(omega.0 * sdt * config.scale) * trans.rotation;
+
+            for wall in walls {
+                ball_plane_contact(
+                    &mut trans.translation,
+                    &mut velocity.0,
+                    &mut omega.0,
+                    ball.0,
+                    wall,
+                    material,
+                    rest_speed,
+                );
+            }
+
+            step_energy.add(
+                step,
+                Energy::rigid_body(
+                    1.0,
+                    inertia,
+                    trans.translation,
+                    trans.rotation,
+                    velocity.0,
+                    omega.0,
+                    config.gravity,
+                ),
+            );
         }
     }
 }
@@ -0,0 +1,257 @@
+use std::f32::consts::{FRAC_PI_4, TAU};
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    sprite::MaterialMesh2dBundle,
+};
+
+use crate::{reset::ResetState, simulate, Ball, Config, Velocity};
+
+// static shapes balls bounce off, their points are relative to the entity's transform so they
+// can be moved and turned like anything else
+pub struct CollidersPlugin;
+
+impl Plugin for CollidersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_colliders.in_schedule(OnEnter(ResetState::Playing)))
+            .add_system(collide_static.after(simulate))
+            .register_type::<SegmentCollider>()
+            .register_type::<PolygonCollider>();
+    }
+}
+
+// a line with rounded ends, radius is half its thickness
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct SegmentCollider {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub radius: f32,
+}
+
+// convex, the points go counter clockwise
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct PolygonCollider {
+    pub points: Vec<Vec2>,
+}
+
+fn closest_point_on_segment(p: Vec3, a: Vec3, b: Vec3) -> Vec3 {
+    let ab = b - a;
+
+    let t = ab.dot(ab);
+    if t == 0.0 {
+        return a;
+    }
+
+    let t = 0.0f32.max(1.0f32.min((p.dot(ab) - a.dot(ab)) / t));
+    a + ab * t
+}
+
+// normal pointing from the segment to the ball and how deep the ball is in it
+fn segment_contact(pos: Vec2, radius: f32, a: Vec2, b: Vec2) -> Option<(Vec2, f32)> {
+    let closest = closest_point_on_segment(pos.extend(0.), a.extend(0.), b.extend(0.)).truncate();
+    let d = pos - closest;
+    let dist = d.length();
+    if dist >= radius {
+        return None;
+    }
+    let normal = if dist > 0.0 {
+        d / dist
+    } else {
+        (b - a).perp().normalize_or_zero()
+    };
+    Some((normal, radius - dist))
+}
+
+// normal pointing out of the polygon to the ball and how deep the ball is in it, a ball whose
+// center got inside is pushed out through the nearest edge
+fn polygon_contact(pos: Vec2, radius: f32, points: &[Vec2]) -> Option<(Vec2, f32)> {
+    if points.len() < 3 {
+        return None;
+    }
+
+    let mut inside = true;
+    let mut nearest_edge = (Vec2::ZERO, f32::MAX);
+    let mut contact = None;
+    let mut deepest = 0.0;
+    for (i, &a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        let outward = -(b - a).perp().normalize_or_zero();
+        let side = (pos - a).dot(outward);
+        if side > 0.0 {
+            inside = false;
+        }
+        if -side < nearest_edge.1 {
+            nearest_edge = (outward, -side);
+        }
+
+        if let Some((normal, depth)) = segment_contact(pos, radius, a, b) {
+            if depth > deepest {
+                deepest = depth;
+                contact = Some((normal, depth));
+            }
+        }
+    }
+
+    if inside {
+        let (normal, dist) = nearest_edge;
+        return Some((normal, dist + radius));
+    }
+    contact
+}
+
+fn world_points(trans: &Transform, points: &[Vec2]) -> Vec<Vec2> {
+    points
+        .iter()
+        .map(|p| trans.transform_point(p.extend(0.)).truncate())
+        .collect()
+}
+
+fn collide_static(
+    mut balls: Query<(&mut Transform, &mut Velocity, &Ball)>,
+    segments: Query<(&Transform, &SegmentCollider), Without<Ball>>,
+    polygons: Query<(&Transform, &PolygonCollider), Without<Ball>>,
+    config: Res<Config>,
+) {
+    let segments = segments
+        .iter()
+        .map(|(trans, segment)| {
+            let points = world_points(trans, &[segment.start, segment.end]);
+            (points[0], points[1], segment.radius)
+        })
+        .collect::<Vec<_>>();
+    let polygons = polygons
+        .iter()
+        .map(|(trans, polygon)| world_points(trans, &polygon.points))
+        .collect::<Vec<_>>();
+
+    for (mut trans, mut velocity, ball) in balls.iter_mut() {
+        for &(a, b, radius) in segments.iter() {
+            let contact = segment_contact(trans.translation.truncate(), ball.0 + radius, a, b);
+            resolve_contact(&mut trans, &mut velocity, contact, config.restitution);
+        }
+
+        for points in polygons.iter() {
+            let contact = polygon_contact(trans.translation.truncate(), ball.0, points);
+            resolve_contact(&mut trans, &mut velocity, contact, config.restitution);
+        }
+    }
+}
+
+// the colliders don't move, so the ball takes all of the correction
+fn resolve_contact(
+    trans: &mut Transform,
+    velocity: &mut Velocity,
+    contact: Option<(Vec2, f32)>,
+    restitution: f32,
+) {
+    let Some((normal, depth)) = contact else {
+        return;
+    };
+    trans.translation += (normal * depth).extend(0.);
+    let v = velocity.0.dot(normal);
+    if v < 0.0 {
+        velocity.0 -= normal * (1.0 + restitution) * v;
+    }
+}
+
+// triangle fan, fine for convex outlines
+fn polygon_mesh(points: &[Vec2]) -> Mesh {
+    let positions = points.iter().map(|p| [p.x, p.y, 0.]).collect::<Vec<_>>();
+    let indices = (1..points.len().saturating_sub(1) as u32)
+        .flat_map(|i| [0, i, i + 1])
+        .collect::<Vec<_>>();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; points.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; points.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+fn segment_mesh(segment: &SegmentCollider) -> Mesh {
+    let side = (segment.end - segment.start).perp().normalize_or_zero() * segment.radius;
+    polygon_mesh(&[
+        segment.start - side,
+        segment.end - side,
+        segment.end + side,
+        segment.start + side,
+    ])
+}
+
+fn regular_polygon(sides: u32, radius: f32) -> Vec<Vec2> {
+    (0..sides)
+        .map(|i| {
+            let angle = TAU * i as f32 / sides as f32;
+            Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+// a funnel over two ramps, a few obstacles and bins along the bottom, turn on gravity to see
+// the balls roll through it
+fn spawn_colliders(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    window_query: Query<&Window>,
+) {
+    let window = window_query.single();
+    let half = Vec2::new(window.width(), window.height()) * 0.5;
+    let material = materials.add(ColorMaterial::from(Color::DARK_GRAY));
+
+    let mut segments = vec![
+        // funnel
+        (Vec2::new(-300., 300.), Vec2::new(-80., 180.)),
+        (Vec2::new(300., 300.), Vec2::new(80., 180.)),
+        // ramps
+        (Vec2::new(-half.x, 120.), Vec2::new(-250., 20.)),
+        (Vec2::new(half.x, 20.), Vec2::new(250., -80.)),
+    ];
+    // bins
+    for i in -2..=2 {
+        let x = i as f32 * half.x * 0.4;
+        segments.push((Vec2::new(x, -half.y), Vec2::new(x, -half.y + 140.)));
+    }
+
+    for (i, (start, end)) in segments.into_iter().enumerate() {
+        let segment = SegmentCollider {
+            start,
+            end,
+            radius: 4.,
+        };
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: meshes.add(segment_mesh(&segment)).into(),
+                material: material.clone(),
+                // behind the balls
+                transform: Transform::from_xyz(0., 0., -1.),
+                ..default()
+            },
+            segment,
+            Name::new(format!("Segment {}", i)),
+        ));
+    }
+
+    let polygons = [
+        (Vec2::new(-350., -120.), 0.0, regular_polygon(6, 50.)),
+        (Vec2::new(350., 150.), 0.3, regular_polygon(3, 60.)),
+        (Vec2::new(0., -40.), FRAC_PI_4, regular_polygon(4, 40.)),
+    ];
+    for (i, (pos, angle, points)) in polygons.into_iter().enumerate() {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: meshes.add(polygon_mesh(&points)).into(),
+                material: material.clone(),
+                transform: Transform::from_translation(pos.extend(-1.))
+                    .with_rotation(Quat::from_rotation_z(angle)),
+                ..default()
+            },
+            PolygonCollider { points },
+            Name::new(format!("Polygon {}", i)),
+        ));
+    }
+}
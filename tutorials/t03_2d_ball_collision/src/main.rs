@@ -1,3 +1,5 @@
+mod colliders;
+mod energy;
 mod reset;
 use std::f32::consts::PI;
 
@@ -5,6 +7,8 @@ use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
 use bevy_inspector_egui::prelude::*;
 use bevy_inspector_egui::quick::ResourceInspectorPlugin;
 
+use colliders::CollidersPlugin;
+use energy::{Energy, EnergyDiagnosticsPlugin, StepEnergy};
 use reset::*;
 
 fn main() {
@@ -12,6 +16,8 @@ fn main() {
         .add_plugins(DefaultPlugins)
         .add_plugin(ResourceInspectorPlugin::<Config>::default())
         .add_plugin(ResetPlugin)
+        .add_plugin(CollidersPlugin)
+        .add_plugin(EnergyDiagnosticsPlugin)
         .init_resource::<Config>()
         .insert_resource(ClearColor(Color::WHITE))
         .add_startup_system(setup)
@@ -109,6 +115,7 @@ fn simulate(
     window_query: Query<&Window>,
     time: Res<Time>,
     config: Res<Config>,
+    mut step_energy: ResMut<StepEnergy>,
 ) {
     let window = window_query.single();
     let bounds = Vec2::new(window.width(), window.height());
@@ -116,11 +123,15 @@ fn simulate(
 
     let sdt = time.delta_seconds() / config.sub_steps as f32;
 
-    for (mut trans, mut velocity, _mass, _ball) in query.iter_mut() {
+    for (mut trans, mut velocity, mass, _ball) in query.iter_mut() {
         // sub steps
-        for _ in 0..config.sub_steps {
+        for step in 0..config.sub_steps as usize {
             velocity.0 += config.gravity * sdt;
             trans.translation += (velocity.0 * sdt).extend(0.);
+            step_energy.add(
+                step,
+                Energy::particle(mass.0, trans.translation.truncate(), velocity.0, config.gravity),
+            );
         }
     }
 
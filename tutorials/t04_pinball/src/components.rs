@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use serde::Deserialize;
 
 #[derive(Component)]
 pub struct Ball {
@@ -29,8 +30,8 @@ pub struct Flipper {
     pub current_angular_velocity: f32,
     pub sign: f32,
     pub key: KeyCode,
-    // pressed
-    //pub pressed: bool,
+    // read once a frame, the flipper moves inside the simulation sub steps
+    pub pressed: bool,
 }
 
 impl Default for Flipper {
@@ -45,6 +46,7 @@ impl Default for Flipper {
             angular_velocity: 0.0,
             current_angular_velocity: 0.0,
             key: KeyCode::Space,
+            pressed: false,
         }
     }
 }
@@ -80,6 +82,11 @@ impl Flipper {
     pub fn get_tip(&self, trans: &Transform) -> Vec3 {
         return trans.transform_point(Vec3::new(0., self.length, 0.));
     }
+
+    // where the tip was before the last step
+    pub fn get_prev_tip(&self, trans: &Transform) -> Vec3 {
+        trans.translation + Quat::from_rotation_z(self.prev_rotation) * Vec3::Y * self.length
+    }
 }
 
 #[derive(Reflect, Component, Default)]
@@ -87,6 +94,7 @@ impl Flipper {
 pub struct Obstacle {
     pub radius: f32,
     pub push_velocity: f32,
+    pub points: u32,
 }
 
 #[derive(Reflect, Resource, Default)]
@@ -100,3 +108,80 @@ impl Border {
         self.points.push(Vec3::new(point.x, point.y, 0.));
     }
 }
+
+// open polyline balls bounce off from both sides, lane guides and the like
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct Wall {
+    pub points: Vec<Vec3>,
+}
+
+// segment that kicks balls away from itself when they touch it
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct Slingshot {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub kick_velocity: f32,
+    pub points: u32,
+}
+
+// spring loaded, pulled back while the key is held and shoots up when it is let go, the
+// transform is the top center of the plunger
+#[derive(Reflect, Component)]
+#[reflect(Component)]
+pub struct Plunger {
+    pub rest: Vec3,
+    pub width: f32,
+    pub max_pull: f32,
+    pub pull_speed: f32,
+    // speed at full pull
+    pub max_speed: f32,
+    pub pull: f32,
+    // of the top surface, up is positive
+    pub velocity: f32,
+    pub key: KeyCode,
+}
+
+impl Default for Plunger {
+    fn default() -> Self {
+        Self {
+            rest: Vec3::ZERO,
+            width: 20.0,
+            max_pull: 30.0,
+            pull_speed: 30.0,
+            max_speed: 700.0,
+            pull: 0.0,
+            velocity: 0.0,
+            key: KeyCode::Down,
+        }
+    }
+}
+
+#[derive(Reflect, FromReflect, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SensorKind {
+    #[default]
+    Rollover,
+    Drain,
+}
+
+// trigger only region around its transform, balls pass through
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct Sensor {
+    pub kind: SensorKind,
+    pub half_size: Vec2,
+    pub points: u32,
+    // rollovers light up when a ball passes
+    pub lit: bool,
+    // balls inside since the last frame, so entering fires once
+    pub inside: Vec<Entity>,
+}
+
+// a ball entered a sensor
+pub struct SensorEvent {
+    pub sensor: Entity,
+    pub ball: Entity,
+    pub kind: SensorKind,
+    pub points: u32,
+}
@@ -0,0 +1,107 @@
+use std::{iter::Sum, ops::Add};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+};
+
+use crate::simulate;
+
+// summed over the balls after each substep, in table units (pixels and ball area as mass), the
+// flippers are moved by hand and don't count
+pub const KINETIC_ENERGY: DiagnosticId =
+    DiagnosticId::from_u128(272591775475118928193609827699193381714);
+pub const POTENTIAL_ENERGY: DiagnosticId =
+    DiagnosticId::from_u128(318317020254816057185425997281364315702);
+pub const TOTAL_ENERGY: DiagnosticId =
+    DiagnosticId::from_u128(140673540442930417039215408028663676411);
+pub const LINEAR_MOMENTUM: DiagnosticId =
+    DiagnosticId::from_u128(288210892467034387706659014548936242155);
+pub const ANGULAR_MOMENTUM: DiagnosticId =
+    DiagnosticId::from_u128(27457686551377237331966974283189313415);
+
+pub struct EnergyDiagnosticsPlugin;
+
+impl Plugin for EnergyDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StepEnergy>()
+            .add_startup_system(setup_diagnostics)
+            .add_system(record_energy.after(simulate));
+    }
+}
+
+// potential energy is zero at the origin, angular momentum is about the origin
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Energy {
+    pub kinetic: f32,
+    pub potential: f32,
+    pub linear_momentum: Vec2,
+    pub angular_momentum: f32,
+}
+
+impl Energy {
+    pub fn particle(mass: f32, position: Vec2, velocity: Vec2, gravity: Vec2) -> Self {
+        let momentum = velocity * mass;
+        Self {
+            kinetic: 0.5 * mass * velocity.length_squared(),
+            potential: -mass * gravity.dot(position),
+            linear_momentum: momentum,
+            angular_momentum: position.perp_dot(momentum),
+        }
+    }
+
+    pub fn total(&self) -> f32 {
+        self.kinetic + self.potential
+    }
+}
+
+impl Add for Energy {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            kinetic: self.kinetic + other.kinetic,
+            potential: self.potential + other.potential,
+            linear_momentum: self.linear_momentum + other.linear_momentum,
+            angular_momentum: self.angular_momentum + other.angular_momentum,
+        }
+    }
+}
+
+impl Sum for Energy {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+// the energy after every substep of this frame, simulate fills it in
+#[derive(Resource, Default)]
+pub struct StepEnergy(Vec<Energy>);
+
+impl StepEnergy {
+    pub fn add(&mut self, step: usize, energy: Energy) {
+        if self.0.len() <= step {
+            self.0.resize(step + 1, Energy::default());
+        }
+        self.0[step] = self.0[step] + energy;
+    }
+}
+
+fn setup_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(KINETIC_ENERGY, "kinetic_energy", 20));
+    diagnostics.add(Diagnostic::new(POTENTIAL_ENERGY, "potential_energy", 20));
+    diagnostics.add(Diagnostic::new(TOTAL_ENERGY, "total_energy", 20));
+    diagnostics.add(Diagnostic::new(LINEAR_MOMENTUM, "linear_momentum", 20));
+    diagnostics.add(Diagnostic::new(ANGULAR_MOMENTUM, "angular_momentum", 20));
+}
+
+// one measurement per substep
+fn record_energy(mut steps: ResMut<StepEnergy>, mut diagnostics: ResMut<Diagnostics>) {
+    for energy in steps.0.drain(..) {
+        diagnostics.add_measurement(KINETIC_ENERGY, || energy.kinetic as f64);
+        diagnostics.add_measurement(POTENTIAL_ENERGY, || energy.potential as f64);
+        diagnostics.add_measurement(TOTAL_ENERGY, || energy.total() as f64);
+        diagnostics.add_measurement(LINEAR_MOMENTUM, || energy.linear_momentum.length() as f64);
+        diagnostics.add_measurement(ANGULAR_MOMENTUM, || energy.angular_momentum as f64);
+    }
+}
@@ -1,37 +1,55 @@
 mod components;
+mod energy;
+mod mechanics;
 mod reset;
+mod table;
 mod ui;
 
 use std::f32::consts::PI;
 
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy::{ecs::system::SystemParam, prelude::*, sprite::MaterialMesh2dBundle};
 use bevy_inspector_egui::prelude::*;
 //use bevy_inspector_egui::quick::{ResourceInspectorPlugin, WorldInspectorPlugin};
 use bevy_prototype_debug_lines::{DebugLines, DebugLinesPlugin};
 use components::*;
+use energy::{Energy, EnergyDiagnosticsPlugin, StepEnergy};
+use mechanics::{
+    handle_ball_plunger_collision, handle_ball_slingshot_collision, handle_ball_wall_collision,
+    MechanicsPlugin,
+};
 use reset::*;
+use table::*;
 use ui::UiPlugin;
 
 fn main() {
     App::new()
-        .add_plugins(DefaultPlugins)
+        // watching for changes hot reloads the table file
+        .add_plugins(DefaultPlugins.set(AssetPlugin {
+            watch_for_changes: true,
+            ..default()
+        }))
         //.add_plugin(WorldInspectorPlugin)
         .add_plugin(DebugLinesPlugin::default())
         .add_plugin(ResetPlugin)
         .add_plugin(UiPlugin)
+        .add_plugin(EnergyDiagnosticsPlugin)
+        .add_plugin(TablePlugin)
+        .add_plugin(MechanicsPlugin)
         .init_resource::<Config>()
         .init_resource::<Border>()
         .init_resource::<Score>()
+        .init_resource::<ScoreRules>()
         //.add_plugin(ResourceInspectorPlugin::<Config>::default())
         .insert_resource(ClearColor(Color::WHITE))
         .add_startup_system(setup)
         .add_system(spawn_balls.in_schedule(OnEnter(ResetState::Playing)))
-        .add_system(flipper_simulate.before(simulate))
+        .add_system(flipper_input.before(simulate))
         .add_system(simulate)
         .add_system(draw_boarder)
         .add_system(spawn_flipper)
         .register_type::<Config>()
         .register_type::<Score>()
+        .register_type::<ScoreRules>()
         .register_type::<Mass>()
         .register_type::<Velocity>()
         .register_type::<Border>()
@@ -44,6 +62,7 @@ struct Config {
     scale: f32,
     #[inspector(min = 0, max = 100)]
     sub_steps: u32,
+    // pixels / s², the table is a few hundred pixels tall so real gravity would look floaty
     gravity: Vec2,
     restitution: f32,
     number_balls: u32,
@@ -54,7 +73,7 @@ impl Default for Config {
         Self {
             scale: 50.,
             sub_steps: 5,
-            gravity: Vec2::new(0., -9.8),
+            gravity: Vec2::new(0., -196.),
             restitution: 1.0,
             number_balls: 20,
         }
@@ -65,17 +84,19 @@ impl Default for Config {
 #[reflect(Resource)]
 pub struct Score(pub u32);
 
+// points on top of what each part gives, set by the table
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct ScoreRules {
+    // for lighting all rollovers, they go dark again after
+    pub rollover_bonus: u32,
+}
+
 fn scale_vec2(pos: Vec2, scale: f32) -> Vec2 {
     (pos + Vec2::splat(-0.5)) * scale
 }
 
-fn setup(
-    mut commands: Commands,
-    window_query: Query<&Window>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut border: ResMut<Border>,
-) {
+fn setup(mut commands: Commands) {
     // Setup Camera
     commands.spawn((
         Camera2dBundle {
@@ -85,111 +106,10 @@ fn setup(
         Keep,
     ));
 
-    // Setup Board based on window size
-    let window = window_query.single();
-    let scale = window.width().min(window.height());
-
-    let offset = Vec2::new(0.25, 0.10);
-    let top_height = 1.0 - 0.68;
-    let pit_height = 0.15 + offset.y;
-    let pit_width_inset = offset.x + 0.12;
-
-    // setup border
-    border.push(scale_vec2(
-        Vec2::new(1.0 - pit_width_inset, pit_height),
-        scale,
-    )); // pit top right
-    border.push(scale_vec2(Vec2::new(1.0 - offset.x, top_height), scale));
-    border.push(scale_vec2(Vec2::new(1.0 - offset.x, 1.0 - offset.y), scale)); // top right
-    border.push(scale_vec2(Vec2::new(offset.x, 1.0 - offset.y), scale)); // top left
-    border.push(scale_vec2(Vec2::new(offset.x, top_height), scale));
-    border.push(scale_vec2(Vec2::new(pit_width_inset, pit_height), scale)); // pit top left
-    border.push(scale_vec2(Vec2::new(pit_width_inset, offset.y), scale)); // pit bottom left
-    border.push(scale_vec2(
-        Vec2::new(1.0 - pit_width_inset, offset.y),
-        scale,
-    )); // pit bottom right
-
-    // setup obstacles
-    for (size, position) in [
-        (0.055, Vec2::new(0.35, 0.72)),
-        (0.07, Vec2::new(0.6, 0.62)),
-        (0.055, Vec2::new(0.37, 0.42)),
-        (0.055, Vec2::new(0.65, 0.39)),
-    ]
-    .iter()
-    {
-        let radius = *size * scale;
-        let pos = scale_vec2(*position, scale).extend(0.);
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::new(radius).into()).into(),
-                material: materials.add(ColorMaterial::from(Color::ORANGE)),
-                transform: Transform::from_translation(pos),
-                ..Default::default()
-            },
-            Obstacle {
-                radius,
-                push_velocity: 200.0,
-            },
-            Keep,
-            Name::new("Bumper"),
-        ));
-    }
-
-    // setup flippers
-    let flipper_radius = 0.015;
-    let flipper_length = 0.10;
-    let flipper_max_rotaiton = 1.0;
-    let flipper_rest_angle = PI * 1.33;
-    let angular_velocity = 10.0;
-
-    for (position, rest_angle, max_rotation, sign, key) in [
-        (
-            Vec2::new(pit_width_inset, pit_height - flipper_radius),
-            flipper_rest_angle,
-            flipper_max_rotaiton,
-            1.0,
-            KeyCode::LShift,
-        ),
-        (
-            Vec2::new(1.0 - pit_width_inset, pit_height - flipper_radius),
-            -flipper_rest_angle,
-            -flipper_max_rotaiton,
-            -1.0,
-            KeyCode::RShift,
-        ),
-    ]
-    .iter()
-    {
-        let pos = scale_vec2(*position, scale).extend(0.);
-        commands.spawn((
-            TransformBundle {
-                local: Transform {
-                    translation: pos,
-                    rotation: Quat::from_rotation_z(*rest_angle),
-                    ..Default::default()
-                },
-                ..Default::default()
-            },
-            VisibilityBundle::default(),
-            Flipper::new(
-                flipper_radius * scale,
-                flipper_length * scale,
-                *max_rotation,
-                *rest_angle,
-                angular_velocity,
-                *sign,
-                *key,
-            ),
-            Restitution(0.0),
-            Keep,
-            Name::new("Flipper"),
-        ));
-    }
-
     info!("Press 'R' to reset");
     info!("Press 'LShift' and `RShift` to control the flippers");
+    info!("Hold 'Down' to pull the plunger and let go to launch");
+    info!("Edit assets/tables/default.table.json to change the table while playing");
 }
 
 fn spawn_flipper(
@@ -236,29 +156,28 @@ fn spawn_flipper(
     }
 }
 
-fn flipper_simulate(
-    mut query: Query<(&mut Flipper, &mut Transform)>,
-    keyboard_input: Res<Input<KeyCode>>,
-    time: Res<Time>,
-) {
-    let dt = time.delta_seconds();
-    for (mut flipper, mut trans) in query.iter_mut() {
-        let mut rotation = trans.rotation.to_euler(EulerRot::YXZ).2;
+fn flipper_input(mut query: Query<&mut Flipper>, keyboard_input: Res<Input<KeyCode>>) {
+    for mut flipper in query.iter_mut() {
+        flipper.pressed = keyboard_input.pressed(flipper.key);
+    }
+}
 
-        flipper.prev_rotation = rotation;
+fn flipper_simulate(flipper: &mut Flipper, trans: &mut Transform, dt: f32) {
+    let mut rotation = trans.rotation.to_euler(EulerRot::YXZ).2;
 
-        if keyboard_input.pressed(flipper.key) {
-            rotation += dt * flipper.angular_velocity * flipper.sign;
-        } else {
-            rotation -= dt * flipper.angular_velocity * flipper.sign;
-        }
-        rotation = rotation.clamp(
-            flipper.rest_angle.min(flipper.max_rotation),
-            flipper.rest_angle.max(flipper.max_rotation),
-        );
-        flipper.current_angular_velocity = (rotation - flipper.prev_rotation) / dt;
-        trans.rotation = Quat::from_axis_angle(Vec3::Z, rotation);
+    flipper.prev_rotation = rotation;
+
+    if flipper.pressed {
+        rotation += dt * flipper.angular_velocity * flipper.sign;
+    } else {
+        rotation -= dt * flipper.angular_velocity * flipper.sign;
     }
+    rotation = rotation.clamp(
+        flipper.rest_angle.min(flipper.max_rotation),
+        flipper.rest_angle.max(flipper.max_rotation),
+    );
+    flipper.current_angular_velocity = (rotation - flipper.prev_rotation) / dt;
+    trans.rotation = Quat::from_axis_angle(Vec3::Z, rotation);
 }
 
 fn draw_boarder(mut lines: ResMut<DebugLines>, border: ResMut<Border>) {
@@ -276,103 +195,201 @@ fn spawn_balls(
     mut materials: ResMut<Assets<ColorMaterial>>,
     config: Res<Config>,
     window_query: Query<&Window>,
+    table_assets: Res<TableAssets>,
+    tables: Res<Assets<Table>>,
 ) {
+    // the table resets once more when it's loaded
+    let Some(table) = tables.get(&table_assets.table) else {
+        return;
+    };
     let window = window_query.single();
     let scale = window.width().min(window.height());
-    // Ball
-
-    for _ in 0..2 {
-        let pos = scale_vec2(Vec2::new(0.3 + (fastrand::f32() * 0.5), 0.7), scale);
-        let radius = 0.02 * scale;
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::new(radius).into()).into(),
-                material: materials.add(ColorMaterial::from(Color::BLACK)),
-                transform: Transform::from_xyz(pos.x, pos.y, 0.),
-                ..default()
-            },
-            Mass(PI * radius * radius),
-            Velocity(Vec2::new(
-                -1. + 2.0 * fastrand::f32() * config.scale * 3.,
-                -1. + 2.0 * fastrand::f32() * config.scale * 3.,
-            )),
-            Restitution(0.9),
-            Ball { radius },
-            Name::new("Ball"),
-        ));
+
+    for spawn in table.balls.iter() {
+        for _ in 0..spawn.count {
+            let pos = scale_vec2(
+                Vec2::from(spawn.position)
+                    + Vec2::new(fastrand::f32(), fastrand::f32()) * Vec2::from(spawn.spread),
+                scale,
+            );
+            let velocity = match spawn.velocity {
+                Some(velocity) => Vec2::from(velocity) * scale,
+                None => Vec2::new(
+                    -1. + 2.0 * fastrand::f32() * config.scale * 3.,
+                    -1. + 2.0 * fastrand::f32() * config.scale * 3.,
+                ),
+            };
+            spawn_ball(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                pos,
+                spawn.radius * scale,
+                velocity,
+            );
+        }
     }
 }
 
+pub fn spawn_ball(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    pos: Vec2,
+    radius: f32,
+    velocity: Vec2,
+) {
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(shape::Circle::new(radius).into()).into(),
+            material: materials.add(ColorMaterial::from(Color::BLACK)),
+            transform: Transform::from_xyz(pos.x, pos.y, 0.),
+            ..default()
+        },
+        Mass(PI * radius * radius),
+        Velocity(velocity),
+        Restitution(0.9),
+        Ball { radius },
+        Name::new("Ball"),
+    ));
+}
+
+// balls and flippers both move in the substep loop, the filters keep their transforms apart
+type BallData = (
+    &'static mut Transform,
+    &'static mut Velocity,
+    &'static Mass,
+    &'static Restitution,
+    &'static Ball,
+);
+type BallItem<'a> = (
+    Mut<'a, Transform>,
+    Mut<'a, Velocity>,
+    &'a Mass,
+    &'a Restitution,
+    &'a Ball,
+);
+type FlipperData = (
+    &'static mut Transform,
+    &'static mut Flipper,
+    &'static Restitution,
+);
+type StaticFilter = (Without<Ball>, Without<Flipper>);
+
+// everything the balls hit that doesn't move during the substeps
+#[derive(SystemParam)]
+struct Colliders<'w, 's> {
+    obstacles: Query<'w, 's, (&'static Transform, &'static Obstacle), StaticFilter>,
+    walls: Query<'w, 's, &'static Wall>,
+    slingshots: Query<'w, 's, &'static Slingshot>,
+    plungers: Query<'w, 's, (&'static Transform, &'static Plunger), StaticFilter>,
+    border: Res<'w, Border>,
+}
+
 fn simulate(
-    mut balls: Query<
-        (&mut Transform, &mut Velocity, &Mass, &Restitution, &Ball),
-        (Without<Flipper>, Without<Obstacle>),
-    >,
-    obstacles: Query<(&Transform, &Obstacle), (Without<Ball>, Without<Flipper>)>,
-    mut flipper: Query<(&mut Transform, &Flipper), (Without<Ball>, Without<Obstacle>)>,
+    mut balls: Query<BallData, (Without<Flipper>, Without<Obstacle>)>,
+    mut flippers: Query<FlipperData, (Without<Ball>, Without<Obstacle>)>,
+    colliders: Colliders,
     time: Res<Time>,
     config: Res<Config>,
     mut score: ResMut<Score>,
-    border: Res<Border>,
+    mut step_energy: ResMut<StepEnergy>,
 ) {
     let dt = time.delta_seconds();
-    // simulate balls
-    for (mut trans, mut velocity, _mass, _res, _ball) in balls.iter_mut() {
-        velocity.0 += config.gravity * dt * 20.;
-        trans.translation += (velocity.0 * dt).extend(0.);
+    if dt == 0.0 || config.sub_steps == 0 {
+        return;
     }
+    // flippers and balls move together in small steps, so a fast flip can't skip over a ball
+    let sdt = dt / config.sub_steps as f32;
 
-    // Look for ball ball collisions
-    let mut combinations = balls.iter_combinations_mut();
-    while let Some(
-        [(mut trans_a, mut vel_a, mass_a, rest_a, ball_a), (mut trans_b, mut vel_b, mass_b, rest_b, ball_b)],
-    ) = combinations.fetch_next()
-    {
-        handle_ball_ball_collision(
-            &mut trans_a,
-            &mut vel_a,
-            mass_a,
-            rest_a,
-            ball_a,
-            &mut trans_b,
-            &mut vel_b,
-            mass_b,
-            rest_b,
-            ball_b,
-        )
-    }
+    for step in 0..config.sub_steps as usize {
+        for (mut trans, mut flipper, _res) in flippers.iter_mut() {
+            flipper_simulate(&mut flipper, &mut trans, sdt);
+        }
+
+        // simulate balls
+        for (mut trans, mut velocity, _mass, _res, _ball) in balls.iter_mut() {
+            velocity.0 += config.gravity * sdt;
+            trans.translation += (velocity.0 * sdt).extend(0.);
+        }
+
+        // Look for ball ball collisions
+        let mut combinations = balls.iter_combinations_mut();
+        while let Some([mut a, mut b]) = combinations.fetch_next() {
+            handle_ball_ball_collision(&mut a, &mut b);
+        }
 
-    for (mut trans_a, mut vel_a, _mass_a, res_a, ball_a) in balls.iter_mut() {
-        for (trans_b, obstacle) in obstacles.iter() {
-            handle_ball_obstacle_collision(
+        for (mut trans_a, mut vel_a, _mass_a, res_a, ball_a) in balls.iter_mut() {
+            for (trans_b, obstacle) in colliders.obstacles.iter() {
+                handle_ball_obstacle_collision(
+                    &mut trans_a,
+                    &mut vel_a,
+                    ball_a,
+                    trans_b,
+                    obstacle,
+                    &mut score,
+                );
+            }
+
+            for (trans_b, flipper, res_b) in flippers.iter() {
+                handle_ball_flipper_collision(
+                    &mut trans_a,
+                    &mut vel_a,
+                    ball_a,
+                    trans_b,
+                    flipper,
+                    res_b,
+                );
+            }
+            for wall in colliders.walls.iter() {
+                handle_ball_wall_collision(&mut trans_a, &mut vel_a, res_a, ball_a, wall);
+            }
+
+            for slingshot in colliders.slingshots.iter() {
+                handle_ball_slingshot_collision(
+                    &mut trans_a,
+                    &mut vel_a,
+                    ball_a,
+                    slingshot,
+                    &mut score,
+                );
+            }
+
+            for (plunger_trans, plunger) in colliders.plungers.iter() {
+                handle_ball_plunger_collision(
+                    &mut trans_a,
+                    &mut vel_a,
+                    ball_a,
+                    plunger_trans,
+                    plunger,
+                );
+            }
+
+            handle_ball_border_collision(
                 &mut trans_a,
                 &mut vel_a,
+                res_a,
                 ball_a,
-                trans_b,
-                obstacle,
-                &mut score,
+                &colliders.border,
             );
         }
 
-        for (mut trans_b, flipper) in flipper.iter_mut() {
-            handle_ball_flipper_collision(&mut trans_a, &mut vel_a, ball_a, &mut trans_b, flipper);
-        }
-        handle_ball_border_collision(&mut trans_a, &mut vel_a, res_a, ball_a, &border);
+        step_energy.add(
+            step,
+            balls
+                .iter()
+                .map(|(trans, velocity, mass, _res, _ball)| {
+                    let position = trans.translation.truncate();
+                    Energy::particle(mass.0, position, velocity.0, config.gravity)
+                })
+                .sum(),
+        );
     }
 }
 
-fn handle_ball_ball_collision(
-    trans_a: &mut Transform,
-    vel_a: &mut Velocity,
-    mass_a: &Mass,
-    rest_a: &Restitution,
-    ball_a: &Ball,
-    trans_b: &mut Transform,
-    vel_b: &mut Velocity,
-    mass_b: &Mass,
-    rest_b: &Restitution,
-    ball_b: &Ball,
-) {
+fn handle_ball_ball_collision(a: &mut BallItem, b: &mut BallItem) {
+    let (trans_a, vel_a, mass_a, rest_a, ball_a) = a;
+    let (trans_b, vel_b, mass_b, rest_b, ball_b) = b;
     let restitution = rest_a.min(rest_b.0);
     let mut dir = (trans_b.translation - trans_a.translation).truncate();
     let d = dir.length();
@@ -421,46 +438,52 @@ fn handle_ball_obstacle_collision(
 
     let v = vel_a.0.dot(dir);
     vel_a.0 += dir * (obstacle.push_velocity - v);
-    score.0 += 1;
+    score.0 += obstacle.points;
 }
 
 fn handle_ball_flipper_collision(
     trans_a: &mut Transform,
     vel_a: &mut Velocity,
     ball_a: &Ball,
-
-    trans_b: &mut Transform,
+    trans_b: &Transform,
     flipper_b: &Flipper,
+    rest_b: &Restitution,
 ) {
-    let closest = closest_point_on_segment(
-        trans_a.translation,
-        trans_b.translation,
-        flipper_b.get_tip(&trans_b),
-    );
+    let pivot = trans_b.translation;
+    let tip = flipper_b.get_tip(trans_b);
+    let closest = closest_point_on_segment(trans_a.translation, pivot, tip);
     let mut dir = trans_a.translation - closest;
     let d = dir.length();
-    if d == 0.0 || d > ball_a.radius + flipper_b.radius {
+    let min_dist = ball_a.radius + flipper_b.radius;
+    if d == 0.0 || d > min_dist {
         return;
     }
 
     dir = dir.normalize();
 
-    let corr = ball_a.radius + flipper_b.radius - d;
-    trans_a.translation += dir * corr;
-
-    // update velocitiy
+    // the flipper swept past the ball's center during this step, push it back out on the side
+    // it came from instead of letting it through
+    let side = |tip: Vec3| (tip - pivot).cross(trans_a.translation - pivot).z;
+    let prev_side = side(flipper_b.get_prev_tip(trans_b));
+    let along = (closest - pivot).length() / flipper_b.length;
+    if prev_side * side(tip) < 0.0 && along > 0.0 && along < 1.0 {
+        dir = -dir;
+        trans_a.translation += dir * (d + min_dist);
+    } else {
+        trans_a.translation += dir * (min_dist - d);
+    }
 
-    let mut radius = closest.clone();
-    radius += dir * flipper_b.radius;
-    radius -= trans_b.translation;
-    let mut surface_vel = Vec3::new(-radius.y, radius.x, 0.); // perp
-    surface_vel *= flipper_b.current_angular_velocity;
+    // velocity of the flipper surface where the ball touches it
+    let radius = closest + dir * flipper_b.radius - pivot;
+    let surface_vel = Vec3::new(-radius.y, radius.x, 0.) * flipper_b.current_angular_velocity;
 
-    // TODO: this feels wrong, balls stick to the flipper,
-    // but it's better than having no control
+    // only when the ball moves into the flipper, so it can roll off a raised flipper
     let v = vel_a.extend(0.).dot(dir);
-    let vnew = surface_vel.dot(dir);
-    vel_a.0 += (dir * (vnew - v)).truncate();
+    let surface_v = surface_vel.dot(dir);
+    if v < surface_v {
+        let vnew = surface_v + (surface_v - v) * rest_b.0;
+        vel_a.0 += (dir * (vnew - v)).truncate();
+    }
 }
 
 fn handle_ball_border_collision(
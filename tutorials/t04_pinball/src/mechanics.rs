@@ -0,0 +1,268 @@
+use bevy::prelude::*;
+use bevy_prototype_debug_lines::DebugLines;
+
+use crate::{closest_point_on_segment, components::*, simulate, spawn_ball, Score, ScoreRules};
+
+// plunger, slingshots, lane walls and sensors, everything a table has besides bumpers and
+// flippers
+pub struct MechanicsPlugin;
+
+impl Plugin for MechanicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SensorEvent>()
+            .add_system(plunger_simulate.before(simulate))
+            .add_systems(
+                (detect_sensors, score_sensors, drain_balls)
+                    .chain()
+                    .after(simulate),
+            )
+            .add_system(draw_mechanics)
+            .register_type::<Wall>()
+            .register_type::<Slingshot>()
+            .register_type::<Plunger>()
+            .register_type::<Sensor>();
+    }
+}
+
+fn plunger_simulate(
+    mut query: Query<(&mut Plunger, &mut Transform)>,
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    if dt == 0.0 {
+        return;
+    }
+
+    for (mut plunger, mut trans) in query.iter_mut() {
+        let prev_pull = plunger.pull;
+        if keyboard_input.pressed(plunger.key) {
+            plunger.pull = (plunger.pull + plunger.pull_speed * dt).min(plunger.max_pull);
+            plunger.velocity = (prev_pull - plunger.pull) / dt;
+        } else {
+            // the spring snaps back within a frame, the further it was pulled the faster
+            plunger.pull = 0.0;
+            plunger.velocity = if plunger.max_pull > 0.0 {
+                plunger.max_speed * prev_pull / plunger.max_pull
+            } else {
+                0.0
+            };
+        }
+        trans.translation = plunger.rest - Vec3::Y * plunger.pull;
+    }
+}
+
+// normal pointing from the segment to the ball and how deep the ball is in it
+fn segment_contact(pos: Vec3, radius: f32, a: Vec3, b: Vec3) -> Option<(Vec3, f32)> {
+    let closest = closest_point_on_segment(pos, a, b);
+    let d = pos - closest;
+    let dist = d.length();
+    if dist >= radius {
+        return None;
+    }
+    let normal = if dist > 0.0 {
+        d / dist
+    } else {
+        let ab = b - a;
+        Vec3::new(-ab.y, ab.x, 0.0).normalize_or_zero()
+    };
+    Some((normal, radius - dist))
+}
+
+// the collisions below run inside simulate's substep loop, so a launched ball can't skip
+// past a lane wall between two checks
+
+pub fn handle_ball_wall_collision(
+    trans: &mut Transform,
+    vel: &mut Velocity,
+    restitution: &Restitution,
+    ball: &Ball,
+    wall: &Wall,
+) {
+    for segment in wall.points.windows(2) {
+        let Some((normal, depth)) =
+            segment_contact(trans.translation, ball.radius, segment[0], segment[1])
+        else {
+            continue;
+        };
+        trans.translation += normal * depth;
+        let v = vel.0.dot(normal.truncate());
+        if v < 0.0 {
+            vel.0 -= normal.truncate() * (1.0 + restitution.0) * v;
+        }
+    }
+}
+
+pub fn handle_ball_slingshot_collision(
+    trans: &mut Transform,
+    vel: &mut Velocity,
+    ball: &Ball,
+    slingshot: &Slingshot,
+    score: &mut Score,
+) {
+    let Some((normal, depth)) = segment_contact(
+        trans.translation,
+        ball.radius,
+        slingshot.start,
+        slingshot.end,
+    ) else {
+        return;
+    };
+    trans.translation += normal * depth;
+    let v = vel.0.dot(normal.truncate());
+    vel.0 += normal.truncate() * (slingshot.kick_velocity - v);
+    score.0 += slingshot.points;
+}
+
+pub fn handle_ball_plunger_collision(
+    trans: &mut Transform,
+    vel: &mut Velocity,
+    ball: &Ball,
+    plunger_trans: &Transform,
+    plunger: &Plunger,
+) {
+    // one sided, the top can jump past the ball's center when it snaps back
+    let top = plunger_trans.translation;
+    let offset = trans.translation - top;
+    let depth = ball.radius - offset.y;
+    if offset.x.abs() > plunger.width * 0.5 || depth <= 0.0 || depth > ball.radius + plunger.max_pull
+    {
+        return;
+    }
+    trans.translation.y += depth;
+    // no bounce, the ball moves with the top of the plunger
+    if vel.0.y < plunger.velocity {
+        vel.0.y = plunger.velocity;
+    }
+}
+
+fn detect_sensors(
+    balls: Query<(Entity, &Transform), With<Ball>>,
+    mut sensors: Query<(Entity, &Transform, &mut Sensor), Without<Ball>>,
+    mut events: EventWriter<SensorEvent>,
+) {
+    for (e, trans, mut sensor) in sensors.iter_mut() {
+        let inside = balls
+            .iter()
+            .filter(|(_, ball_trans)| {
+                let d = (ball_trans.translation - trans.translation)
+                    .truncate()
+                    .abs();
+                d.x <= sensor.half_size.x && d.y <= sensor.half_size.y
+            })
+            .map(|(ball, _)| ball)
+            .collect::<Vec<_>>();
+
+        for ball in inside.iter() {
+            if !sensor.inside.contains(ball) {
+                events.send(SensorEvent {
+                    sensor: e,
+                    ball: *ball,
+                    kind: sensor.kind,
+                    points: sensor.points,
+                });
+            }
+        }
+        sensor.inside = inside;
+    }
+}
+
+fn score_sensors(
+    mut events: EventReader<SensorEvent>,
+    mut sensors: Query<&mut Sensor>,
+    mut score: ResMut<Score>,
+    rules: Res<ScoreRules>,
+) {
+    for event in events.iter() {
+        score.0 += event.points;
+
+        if event.kind != SensorKind::Rollover {
+            continue;
+        }
+        if let Ok(mut sensor) = sensors.get_mut(event.sensor) {
+            sensor.lit = true;
+        }
+        let mut rollovers = sensors
+            .iter_mut()
+            .filter(|sensor| sensor.kind == SensorKind::Rollover);
+        if rollovers.all(|sensor| sensor.lit) {
+            score.0 += rules.rollover_bonus;
+            info!("All rollovers lit, bonus {}", rules.rollover_bonus);
+            for mut sensor in sensors.iter_mut() {
+                sensor.lit = false;
+            }
+        }
+    }
+}
+
+// drained balls come back on the plunger
+fn drain_balls(
+    mut commands: Commands,
+    mut events: EventReader<SensorEvent>,
+    balls: Query<&Ball>,
+    plungers: Query<&Plunger>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for event in events.iter() {
+        if event.kind != SensorKind::Drain {
+            continue;
+        }
+        let Ok(ball) = balls.get(event.ball) else {
+            continue;
+        };
+        commands.entity(event.ball).despawn();
+        info!("Ball drained");
+
+        if let Some(plunger) = plungers.iter().next() {
+            spawn_ball(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                (plunger.rest + Vec3::Y * ball.radius).truncate(),
+                ball.radius,
+                Vec2::ZERO,
+            );
+        }
+    }
+}
+
+fn draw_mechanics(
+    mut lines: ResMut<DebugLines>,
+    walls: Query<&Wall>,
+    slingshots: Query<&Slingshot>,
+    sensors: Query<(&Transform, &Sensor)>,
+) {
+    for wall in walls.iter() {
+        for segment in wall.points.windows(2) {
+            lines.line_colored(segment[0], segment[1], 0.0, Color::BLACK);
+        }
+    }
+
+    for slingshot in slingshots.iter() {
+        lines.line_colored(slingshot.start, slingshot.end, 0.0, Color::RED);
+    }
+
+    for (trans, sensor) in sensors.iter() {
+        let color = match sensor.kind {
+            SensorKind::Rollover if sensor.lit => Color::GOLD,
+            SensorKind::Rollover => Color::GREEN,
+            SensorKind::Drain => Color::GRAY,
+        };
+        let h = sensor.half_size;
+        let corners = [
+            Vec3::new(-h.x, -h.y, 0.),
+            Vec3::new(h.x, -h.y, 0.),
+            Vec3::new(h.x, h.y, 0.),
+            Vec3::new(-h.x, h.y, 0.),
+        ];
+        for (i, corner) in corners.iter().enumerate() {
+            lines.line_colored(
+                trans.translation + *corner,
+                trans.translation + corners[(i + 1) % 4],
+                0.0,
+                color,
+            );
+        }
+    }
+}
@@ -0,0 +1,419 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::TypeUuid,
+    sprite::MaterialMesh2dBundle,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use serde_json::from_slice;
+
+use crate::{
+    components::{
+        Border, Flipper, Obstacle, Plunger, Restitution, Sensor, SensorKind, Slingshot, Wall,
+    },
+    reset::{Keep, ResetState},
+    scale_vec2, ScoreRules,
+};
+
+// loads the table layout and rebuilds the table whenever the file changes on disk
+pub struct TablePlugin;
+
+impl Plugin for TablePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Table>()
+            .init_asset_loader::<TableLoader>()
+            .init_resource::<TableAssets>()
+            .add_system(build_table);
+    }
+}
+
+// positions and sizes are fractions of the window's shorter side, (0, 0) is the bottom left
+#[derive(Debug, Deserialize, TypeUuid, Clone, Default)]
+#[uuid = "b710b0fd-0bb6-4502-8381-7abb58cfa05e"]
+#[serde(default)]
+pub struct Table {
+    // closed polyline, counter clockwise so the normals point inside
+    pub border: Vec<[f32; 2]>,
+    pub bumpers: Vec<BumperDesc>,
+    pub flippers: Vec<FlipperDesc>,
+    pub balls: Vec<BallSpawn>,
+    // open polylines inside the border
+    pub walls: Vec<Vec<[f32; 2]>>,
+    pub slingshots: Vec<SlingshotDesc>,
+    pub plunger: Option<PlungerDesc>,
+    pub sensors: Vec<SensorDesc>,
+    // for lighting all rollover sensors
+    pub rollover_bonus: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct BumperDesc {
+    pub position: [f32; 2],
+    pub radius: f32,
+    // pixels / s the ball leaves with
+    pub push_velocity: f32,
+    pub points: u32,
+}
+
+impl Default for BumperDesc {
+    fn default() -> Self {
+        Self {
+            position: [0.5, 0.5],
+            radius: 0.055,
+            push_velocity: 200.0,
+            points: 1,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SlingshotDesc {
+    pub start: [f32; 2],
+    pub end: [f32; 2],
+    // pixels / s the ball leaves with
+    pub kick_velocity: f32,
+    pub points: u32,
+}
+
+impl Default for SlingshotDesc {
+    fn default() -> Self {
+        Self {
+            start: [0.4, 0.4],
+            end: [0.4, 0.3],
+            kick_velocity: 250.0,
+            points: 10,
+        }
+    }
+}
+
+// position is the top center at rest, speeds are per second
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PlungerDesc {
+    pub position: [f32; 2],
+    pub width: f32,
+    pub max_pull: f32,
+    pub pull_speed: f32,
+    pub max_speed: f32,
+    // bevy KeyCode name, e.g. "Down" or "Return"
+    pub key: KeyCode,
+}
+
+impl Default for PlungerDesc {
+    fn default() -> Self {
+        Self {
+            position: [0.785, 0.2],
+            width: 0.05,
+            max_pull: 0.05,
+            pull_speed: 0.05,
+            max_speed: 1.2,
+            key: KeyCode::Down,
+        }
+    }
+}
+
+// position is the center of the region
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SensorDesc {
+    pub kind: SensorKind,
+    pub position: [f32; 2],
+    pub half_size: [f32; 2],
+    pub points: u32,
+}
+
+impl Default for SensorDesc {
+    fn default() -> Self {
+        Self {
+            kind: SensorKind::Rollover,
+            position: [0.5, 0.85],
+            half_size: [0.015, 0.02],
+            points: 100,
+        }
+    }
+}
+
+// angles in radians, the flipper swings from rest_angle by max_rotation and the sign of
+// max_rotation is the direction it swings in
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct FlipperDesc {
+    pub position: [f32; 2],
+    pub radius: f32,
+    pub length: f32,
+    pub rest_angle: f32,
+    pub max_rotation: f32,
+    pub angular_velocity: f32,
+    // bevy KeyCode name, e.g. "LShift" or "Z"
+    pub key: KeyCode,
+}
+
+impl Default for FlipperDesc {
+    fn default() -> Self {
+        Self {
+            position: [0.5, 0.25],
+            radius: 0.015,
+            length: 0.1,
+            rest_angle: 0.0,
+            max_rotation: 1.0,
+            angular_velocity: 10.0,
+            key: KeyCode::Space,
+        }
+    }
+}
+
+// count balls are dropped at random inside position + [0, spread], with a random velocity
+// unless one is given
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct BallSpawn {
+    pub position: [f32; 2],
+    pub spread: [f32; 2],
+    pub radius: f32,
+    pub count: u32,
+    pub velocity: Option<[f32; 2]>,
+}
+
+impl Default for BallSpawn {
+    fn default() -> Self {
+        Self {
+            position: [0.5, 0.7],
+            spread: [0.0, 0.0],
+            radius: 0.02,
+            count: 1,
+            velocity: None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TableLoader;
+
+impl AssetLoader for TableLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let custom_asset = from_slice::<Table>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(custom_asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["table.json"]
+    }
+}
+
+#[derive(Resource)]
+pub struct TableAssets {
+    pub table: Handle<Table>,
+}
+
+impl FromWorld for TableAssets {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let table = asset_server.load("tables/default.table.json");
+        Self { table }
+    }
+}
+
+// everything spawned from the table file, replaced as a whole on reload
+#[derive(Component)]
+pub struct TableItem;
+
+// the table file and the assets the items built from it need
+#[derive(SystemParam)]
+struct TableSource<'w, 's> {
+    events: EventReader<'w, 's, AssetEvent<Table>>,
+    table_assets: Res<'w, TableAssets>,
+    tables: Res<'w, Assets<Table>>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    materials: ResMut<'w, Assets<ColorMaterial>>,
+}
+
+fn build_table(
+    mut commands: Commands,
+    mut source: TableSource,
+    items: Query<Entity, With<TableItem>>,
+    mut border: ResMut<Border>,
+    window_query: Query<&Window>,
+    mut app_state: ResMut<NextState<ResetState>>,
+    mut rules: ResMut<ScoreRules>,
+) {
+    let mut changed = false;
+    for event in source.events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle }
+                if *handle == source.table_assets.table =>
+            {
+                changed = true;
+            }
+            _ => {}
+        }
+    }
+    let Some(table) = source.tables.get(&source.table_assets.table) else {
+        return;
+    };
+    if !changed {
+        return;
+    }
+
+    for e in items.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+
+    let window = window_query.single();
+    let scale = window.width().min(window.height());
+    let to_world = |p: [f32; 2]| scale_vec2(Vec2::from(p), scale);
+
+    rules.rollover_bonus = table.rollover_bonus;
+
+    border.points.clear();
+    for point in table.border.iter() {
+        border.push(to_world(*point));
+    }
+
+    for bumper in table.bumpers.iter() {
+        let radius = bumper.radius * scale;
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: source.meshes.add(shape::Circle::new(radius).into()).into(),
+                material: source.materials.add(ColorMaterial::from(Color::ORANGE)),
+                transform: Transform::from_translation(to_world(bumper.position).extend(0.)),
+                ..Default::default()
+            },
+            Obstacle {
+                radius,
+                push_velocity: bumper.push_velocity,
+                points: bumper.points,
+            },
+            TableItem,
+            Keep,
+            Name::new("Bumper"),
+        ));
+    }
+
+    for flipper in table.flippers.iter() {
+        commands.spawn((
+            TransformBundle {
+                local: Transform {
+                    translation: to_world(flipper.position).extend(0.),
+                    rotation: Quat::from_rotation_z(flipper.rest_angle),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            VisibilityBundle::default(),
+            Flipper::new(
+                flipper.radius * scale,
+                flipper.length * scale,
+                flipper.max_rotation,
+                flipper.rest_angle,
+                flipper.angular_velocity,
+                flipper.max_rotation.signum(),
+                flipper.key,
+            ),
+            Restitution(0.0),
+            TableItem,
+            Keep,
+            Name::new("Flipper"),
+        ));
+    }
+
+    for wall in table.walls.iter() {
+        commands.spawn((
+            Wall {
+                points: wall.iter().map(|p| to_world(*p).extend(0.)).collect(),
+            },
+            TableItem,
+            Keep,
+            Name::new("Wall"),
+        ));
+    }
+
+    for slingshot in table.slingshots.iter() {
+        commands.spawn((
+            Slingshot {
+                start: to_world(slingshot.start).extend(0.),
+                end: to_world(slingshot.end).extend(0.),
+                kick_velocity: slingshot.kick_velocity,
+                points: slingshot.points,
+            },
+            TableItem,
+            Keep,
+            Name::new("Slingshot"),
+        ));
+    }
+
+    if let Some(plunger) = table.plunger.as_ref() {
+        let rest = to_world(plunger.position).extend(0.);
+        let width = plunger.width * scale;
+        let height = 0.1 * scale;
+        commands
+            .spawn((
+                TransformBundle::from_transform(Transform::from_translation(rest)),
+                VisibilityBundle::default(),
+                Plunger {
+                    rest,
+                    width,
+                    max_pull: plunger.max_pull * scale,
+                    pull_speed: plunger.pull_speed * scale,
+                    max_speed: plunger.max_speed * scale,
+                    key: plunger.key,
+                    ..default()
+                },
+                TableItem,
+                Keep,
+                Name::new("Plunger"),
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    MaterialMesh2dBundle {
+                        mesh: source
+                            .meshes
+                            .add(shape::Quad::new(Vec2::new(width, height)).into())
+                            .into(),
+                        material: source.materials.add(ColorMaterial::from(Color::GRAY)),
+                        transform: Transform::from_xyz(0.0, -height * 0.5, 0.),
+                        ..Default::default()
+                    },
+                    Name::new("Rod"),
+                ));
+            });
+    }
+
+    for sensor in table.sensors.iter() {
+        commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(
+                to_world(sensor.position).extend(0.),
+            )),
+            Sensor {
+                kind: sensor.kind,
+                half_size: Vec2::from(sensor.half_size) * scale,
+                points: sensor.points,
+                ..default()
+            },
+            TableItem,
+            Keep,
+            Name::new(format!("{:?} Sensor", sensor.kind)),
+        ));
+    }
+
+    info!(
+        "Table loaded, {} bumpers, {} flippers, {} slingshots and {} sensors",
+        table.bumpers.len(),
+        table.flippers.len(),
+        table.slingshots.len(),
+        table.sensors.len()
+    );
+
+    // fresh balls for the new layout
+    app_state.set(ResetState::Reset);
+}
@@ -1,6 +1,10 @@
-use bevy::prelude::*;
+use bevy::{diagnostic::Diagnostics, prelude::*};
 
-use crate::{reset::Keep, Score};
+use crate::{
+    energy::{ANGULAR_MOMENTUM, KINETIC_ENERGY, LINEAR_MOMENTUM, POTENTIAL_ENERGY, TOTAL_ENERGY},
+    reset::Keep,
+    Score,
+};
 
 pub struct UiPlugin;
 
@@ -8,7 +12,8 @@ impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<FontAssets>()
             .add_startup_system(setup)
-            .add_system(update_score);
+            .add_system(update_score)
+            .add_system(update_energy);
     }
 }
 
@@ -59,6 +64,14 @@ fn setup(mut commands: Commands, fonts: ResMut<FontAssets>) {
                             color: Color::GOLD,
                         },
                     },
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle {
+                            font: fonts.ui_font.clone(),
+                            font_size: 20.0,
+                            color: Color::BLACK,
+                        },
+                    },
                 ],
                 ..Default::default()
             },
@@ -78,3 +91,25 @@ fn update_score(score: Res<Score>, mut query: Query<&mut Text, With<ScoreText>>)
         text.sections[1].value = format!("{:?}", score.0);
     }
 }
+
+// shown under the score, away from the flippers, bumpers and slingshots any change in energy
+// comes from the solver
+fn update_energy(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, With<ScoreText>>) {
+    let average = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.average())
+            .unwrap_or_default()
+    };
+    for mut text in query.iter_mut() {
+        text.sections[2].value = format!(
+            "\nEnergy: {:.0} (kinetic {:.0}, potential {:.0})\n\
+             Momentum: linear {:.0}, angular {:.0}",
+            average(TOTAL_ENERGY),
+            average(KINETIC_ENERGY),
+            average(POTENTIAL_ENERGY),
+            average(LINEAR_MOMENTUM),
+            average(ANGULAR_MOMENTUM),
+        );
+    }
+}
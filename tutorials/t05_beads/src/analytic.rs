@@ -0,0 +1,225 @@
+use bevy::prelude::*;
+
+use crate::{components::Bead, reset::Keep, resources::Config, simulate};
+
+// a bead on a circle moved by its equation of motion next to a simulated one, showing how far
+// the simulation is off for the number of sub steps
+pub struct AnalyticPlugin;
+
+impl Plugin for AnalyticPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FontAssets>()
+            .add_startup_system(setup)
+            .add_systems((simulate_analytic, update_readout).chain().after(simulate))
+            .register_type::<AnalyticBead>();
+    }
+}
+
+#[derive(Resource)]
+pub struct FontAssets {
+    pub ui_font: Handle<Font>,
+}
+
+impl FromWorld for FontAssets {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let ui_font = asset_server.load("fonts/FiraSans-Bold.ttf");
+        Self { ui_font }
+    }
+}
+
+// the angle is measured from +x around the circle's center
+#[derive(Reflect, Component)]
+#[reflect(Component)]
+pub struct AnalyticBead {
+    pub center: Vec3,
+    pub wire_radius: f32,
+    pub angle: f32,
+    pub angular_velocity: f32,
+    // the simulated bead it's compared to
+    pub bead: Entity,
+    // per unit mass, of the simulated bead when it was spawned
+    pub start_energy: f32,
+    pub error: f32,
+    pub max_error: f32,
+    pub energy_drift: f32,
+    pub analytic_energy_drift: f32,
+}
+
+impl Default for AnalyticBead {
+    fn default() -> Self {
+        Self {
+            center: Vec3::ZERO,
+            wire_radius: 100.0,
+            angle: 0.0,
+            angular_velocity: 0.0,
+            bead: Entity::PLACEHOLDER,
+            start_energy: 0.0,
+            error: 0.0,
+            max_error: 0.0,
+            energy_drift: 0.0,
+            analytic_energy_drift: 0.0,
+        }
+    }
+}
+
+impl AnalyticBead {
+    // starting at rest where the simulated bead is
+    pub fn new(
+        bead: Entity,
+        position: Vec3,
+        center: Vec3,
+        wire_radius: f32,
+        gravity: Vec2,
+    ) -> Self {
+        let offset = (position - center).truncate();
+        Self {
+            center,
+            wire_radius,
+            angle: offset.y.atan2(offset.x),
+            bead,
+            start_energy: bead_energy(Vec2::ZERO, offset, wire_radius, gravity),
+            ..default()
+        }
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.center + Vec3::new(self.angle.cos(), self.angle.sin(), 0.) * self.wire_radius
+    }
+
+    fn velocity(&self) -> Vec2 {
+        Vec2::new(-self.angle.sin(), self.angle.cos()) * self.angular_velocity * self.wire_radius
+    }
+
+    // there's no closed form for large swings, small rk4 steps on the angle are accurate far
+    // beyond anything the simulation gets to
+    fn step(&mut self, dt: f32, gravity: Vec2) {
+        let acc = |angle: f32| gravity.dot(Vec2::new(-angle.sin(), angle.cos())) / self.wire_radius;
+        let (a, w) = (self.angle, self.angular_velocity);
+
+        let (k1a, k1w) = (w, acc(a));
+        let (k2a, k2w) = (w + 0.5 * dt * k1w, acc(a + 0.5 * dt * k1a));
+        let (k3a, k3w) = (w + 0.5 * dt * k2w, acc(a + 0.5 * dt * k2a));
+        let (k4a, k4w) = (w + dt * k3w, acc(a + dt * k3a));
+
+        self.angle += dt / 6. * (k1a + 2. * k2a + 2. * k3a + k4a);
+        self.angular_velocity += dt / 6. * (k1w + 2. * k2w + 2. * k3w + k4w);
+    }
+}
+
+// per unit mass, the height is measured from the lowest point of the circle
+pub fn bead_energy(velocity: Vec2, offset: Vec2, wire_radius: f32, gravity: Vec2) -> f32 {
+    0.5 * velocity.length_squared() + gravity.length() * wire_radius - offset.dot(gravity)
+}
+
+fn simulate_analytic(
+    mut query: Query<(&mut AnalyticBead, &mut Transform)>,
+    beads: Query<(&Bead, &Transform), Without<AnalyticBead>>,
+    config: Res<Config>,
+    time: Res<Time>,
+) {
+    const STEPS: u32 = 100;
+    let dt = time.delta_seconds();
+    if dt == 0.0 {
+        return;
+    }
+    let gravity = config.gravity * config.pixels_per_meter;
+
+    for (mut analytic, mut transform) in query.iter_mut() {
+        for _ in 0..STEPS {
+            analytic.step(dt / STEPS as f32, gravity);
+        }
+        let position = analytic.position();
+        transform.translation = position + Vec3::Z;
+
+        let analytic_energy = bead_energy(
+            analytic.velocity(),
+            (position - analytic.center).truncate(),
+            analytic.wire_radius,
+            gravity,
+        );
+        analytic.analytic_energy_drift = analytic_energy / analytic.start_energy - 1.0;
+
+        let Ok((bead, bead_transform)) = beads.get(analytic.bead) else {
+            continue;
+        };
+        analytic.error = bead_transform
+            .translation
+            .truncate()
+            .distance(position.truncate());
+        analytic.max_error = analytic.max_error.max(analytic.error);
+        let energy = bead_energy(
+            bead.velocity,
+            (bead_transform.translation - analytic.center).truncate(),
+            analytic.wire_radius,
+            gravity,
+        );
+        analytic.energy_drift = energy / analytic.start_energy - 1.0;
+    }
+}
+
+#[derive(Component)]
+pub struct ReadoutText;
+
+fn setup(mut commands: Commands, fonts: Res<FontAssets>) {
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            // the energy diagnostics fill in the first section, the comparison the second
+            text: Text::from_sections([
+                TextSection::new(
+                    "",
+                    TextStyle {
+                        font: fonts.ui_font.clone(),
+                        font_size: 20.0,
+                        color: Color::BLACK,
+                    },
+                ),
+                TextSection::new(
+                    "",
+                    TextStyle {
+                        font: fonts.ui_font.clone(),
+                        font_size: 20.0,
+                        color: Color::BLACK,
+                    },
+                ),
+            ]),
+            ..Default::default()
+        },
+        Keep,
+        Name::new("ui Readout"),
+        ReadoutText,
+    ));
+}
+
+fn update_readout(
+    query: Query<&AnalyticBead>,
+    config: Res<Config>,
+    mut text: Query<&mut Text, With<ReadoutText>>,
+) {
+    let value = match query.iter().next() {
+        Some(analytic) => format!(
+            "\nSub steps: {}\nError: {:.3} px, max {:.3} px\n\
+             Energy drift: {:+.4} %\nAnalytic energy drift: {:+.6} %",
+            config.sub_steps,
+            analytic.error,
+            analytic.max_error,
+            analytic.energy_drift * 100.,
+            analytic.analytic_energy_drift * 100.,
+        ),
+        None => String::new(),
+    };
+    for mut text in text.iter_mut() {
+        if text.sections[1].value != value {
+            text.sections[1].value = value.clone();
+        }
+    }
+}
@@ -1,21 +1,189 @@
 use bevy::prelude::*;
 
-use crate::resources::Config;
+use crate::{curve::*, resources::Config};
 
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WireShape {
+    #[default]
+    Circle,
+    Polyline,
+    Bezier,
+    CatmullRom,
+}
+
+// points are relative to the wire's transform
 #[derive(Reflect, Component, Default)]
 #[reflect(Component)]
 pub struct Wire {
+    pub shape: WireShape,
+    // circles only
     pub radius: f32,
+    pub points: Vec<Vec2>,
+    pub closed: bool,
     pub line_segments: u32,
+    // the curve as a polyline, beads are kept on this
+    pub samples: Vec<Vec2>,
 }
 
-#[derive(Reflect, Component, Default)]
+impl Wire {
+    pub fn circle(radius: f32, line_segments: u32) -> Self {
+        Self {
+            shape: WireShape::Circle,
+            radius,
+            closed: true,
+            line_segments,
+            samples: sample_circle(radius, line_segments),
+            ..default()
+        }
+    }
+
+    pub fn polyline(points: Vec<Vec2>, closed: bool) -> Self {
+        Self {
+            shape: WireShape::Polyline,
+            samples: points.clone(),
+            points,
+            closed,
+            line_segments: 1,
+            ..default()
+        }
+    }
+
+    pub fn bezier(points: Vec<Vec2>, closed: bool, line_segments: u32) -> Self {
+        Self {
+            shape: WireShape::Bezier,
+            samples: sample_bezier(&points, closed, line_segments),
+            points,
+            closed,
+            line_segments,
+            ..default()
+        }
+    }
+
+    pub fn catmull_rom(points: Vec<Vec2>, closed: bool, line_segments: u32) -> Self {
+        Self {
+            shape: WireShape::CatmullRom,
+            samples: sample_catmull_rom(&points, closed, line_segments),
+            points,
+            closed,
+            line_segments,
+            ..default()
+        }
+    }
+
+    fn segment_count(&self) -> usize {
+        match (self.closed, self.samples.len()) {
+            (_, 0 | 1) => 0,
+            (true, n) => n,
+            (false, n) => n - 1,
+        }
+    }
+
+    fn segment(&self, index: usize) -> (Vec2, Vec2) {
+        let n = self.samples.len();
+        (self.samples[index], self.samples[(index + 1) % n])
+    }
+
+    // first and last point of an open wire
+    pub fn ends(&self) -> Option<(Vec2, Vec2)> {
+        match (self.closed, self.samples.first(), self.samples.last()) {
+            (false, Some(first), Some(last)) => Some((*first, *last)),
+            _ => None,
+        }
+    }
+
+    // point `fraction` of the way through the samples, for placing beads
+    pub fn sample_point(&self, fraction: f32) -> Vec2 {
+        if self.samples.is_empty() {
+            return Vec2::ZERO;
+        }
+        let last = if self.closed {
+            self.samples.len()
+        } else {
+            self.samples.len() - 1
+        };
+        let i = ((fraction.clamp(0., 1.) * last as f32) as usize).min(last);
+        self.samples[i % self.samples.len()]
+    }
+
+    // closest point on the wire and the tangent there, searching only the segments near the
+    // last one so a bead can't jump across to a part of the wire passing close by
+    pub fn closest_point(&self, p: Vec2, near: Option<usize>) -> Option<(Vec2, Vec2, usize)> {
+        if self.shape == WireShape::Circle {
+            let dir = p.normalize_or_zero();
+            if dir == Vec2::ZERO {
+                return None;
+            }
+            return Some((dir * self.radius, dir.perp(), 0));
+        }
+
+        let count = self.segment_count();
+        if count == 0 {
+            return None;
+        }
+        const WINDOW: isize = 8;
+        let candidates: Box<dyn Iterator<Item = usize>> = match near {
+            Some(index) => Box::new((-WINDOW..=WINDOW).filter_map(move |offset| {
+                let i = index as isize + offset;
+                if self.closed {
+                    Some(i.rem_euclid(count as isize) as usize)
+                } else if i >= 0 && i < count as isize {
+                    Some(i as usize)
+                } else {
+                    None
+                }
+            })),
+            None => Box::new(0..count),
+        };
+
+        let mut best: Option<(Vec2, Vec2, usize)> = None;
+        let mut best_dist = f32::MAX;
+        for i in candidates {
+            let (a, b) = self.segment(i);
+            let ab = b - a;
+            let len2 = ab.length_squared();
+            let t = if len2 > 0.0 {
+                ((p - a).dot(ab) / len2).clamp(0., 1.)
+            } else {
+                0.0
+            };
+            let closest = a + ab * t;
+            let dist = closest.distance_squared(p);
+            if dist < best_dist {
+                best_dist = dist;
+                best = Some((closest, ab.normalize_or_zero(), i));
+            }
+        }
+        best
+    }
+}
+
+#[derive(Reflect, Component)]
 #[reflect(Component)]
 pub struct Bead {
     pub radius: f32,
     pub mass: f32,
     pub prev_position: Vec2,
     pub velocity: Vec2,
+    // the wire it's on, beads only hit beads on the same wire
+    pub wire: Entity,
+    // wire segment it was last projected on
+    pub segment: Option<usize>,
+    // direction the velocity is kept to on sampled wires, zero on circles
+    pub tangent: Vec2,
+}
+
+impl Default for Bead {
+    fn default() -> Self {
+        Self {
+            radius: 10.0,
+            mass: 100.0,
+            prev_position: Vec2::ZERO,
+            velocity: Vec2::ZERO,
+            wire: Entity::PLACEHOLDER,
+            segment: None,
+            tangent: Vec2::ZERO,
+        }
+    }
 }
 
 impl Bead {
@@ -25,19 +193,40 @@ impl Bead {
         transform.translation += self.velocity.extend(0.0) * dt;
     }
 
-    pub fn keep_on_wire(&mut self, wire_center: Vec3, wire_radius: f32, transform: &mut Transform) {
-        let mut dir = transform.translation - wire_center;
-        let len = dir.length();
-        if len == 0.0 {
+    pub fn keep_on_wire(&mut self, wire: &Wire, wire_origin: Vec3, transform: &mut Transform) {
+        let origin = wire_origin.truncate();
+        let Some((closest, tangent, segment)) =
+            wire.closest_point(transform.translation.truncate() - origin, self.segment)
+        else {
             return;
-        }
-        dir = dir.normalize_or_zero();
+        };
+        self.segment = Some(segment);
+        self.tangent = if wire.shape == WireShape::Circle {
+            Vec2::ZERO
+        } else {
+            tangent
+        };
+        transform.translation = (origin + closest).extend(transform.translation.z);
 
-        let lambda = wire_radius - len;
-        transform.translation += dir * lambda;
+        // the ends of an open wire stop the bead
+        if let Some((first, last)) = wire.ends() {
+            let v = self.velocity.dot(tangent);
+            if (closest.distance(first) < 1e-3 && v < 0.0)
+                || (closest.distance(last) < 1e-3 && v > 0.0)
+            {
+                self.velocity = Vec2::ZERO;
+            }
+        }
     }
 
     pub fn end_step(&mut self, transform: &mut Transform, dt: f32) {
+        // a sampled wire is only straight between samples, the projected positions cut every
+        // corner and the velocity derived from them loses speed there, so the bead keeps the
+        // tangential part of its velocity instead, circles use the pbd velocity below
+        if self.tangent != Vec2::ZERO {
+            self.velocity = self.tangent * self.velocity.dot(self.tangent);
+            return;
+        }
         self.velocity = transform.translation.truncate() - self.prev_position;
         if dt > 0.0 {
             self.velocity *= 1.0 / dt;
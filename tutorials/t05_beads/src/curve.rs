@@ -0,0 +1,113 @@
+use bevy::{
+    math::cubic_splines::{Bezier, CardinalSpline, CubicCurve, CubicGenerator},
+    prelude::*,
+};
+use std::f32::consts::PI;
+
+// every curve is turned into a polyline, `subdivisions` is the number of lines per curve segment
+
+pub fn sample_circle(radius: f32, subdivisions: u32) -> Vec<Vec2> {
+    (0..subdivisions)
+        .map(|i| {
+            let angle = 2. * PI * i as f32 / subdivisions as f32;
+            Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+// cubic bezier segments sharing their end points, 3n + 1 points for an open curve and 3n for a
+// closed one where the last segment ends at the first point
+pub fn sample_bezier(points: &[Vec2], closed: bool, subdivisions: u32) -> Vec<Vec2> {
+    let mut points = points.to_vec();
+    if closed && !points.is_empty() {
+        points.push(points[0]);
+    }
+    let segments = points
+        .windows(4)
+        .step_by(3)
+        .map(|p| [p[0], p[1], p[2], p[3]])
+        .collect::<Vec<_>>();
+    sample_curve(
+        &Bezier::new(segments).to_curve(),
+        segments_len(&points, 3),
+        closed,
+        subdivisions,
+    )
+}
+
+// passes through every point, the end points of an open curve are repeated so it reaches them
+pub fn sample_catmull_rom(points: &[Vec2], closed: bool, subdivisions: u32) -> Vec<Vec2> {
+    if points.len() < 2 {
+        return points.to_vec();
+    }
+    let n = points.len();
+    let padded = if closed {
+        let mut padded = vec![points[n - 1]];
+        padded.extend_from_slice(points);
+        padded.extend_from_slice(&[points[0], points[1 % n]]);
+        padded
+    } else {
+        let mut padded = vec![points[0]];
+        padded.extend_from_slice(points);
+        padded.push(points[n - 1]);
+        padded
+    };
+    let curve = CardinalSpline::new_catmull_rom(padded.clone()).to_curve();
+    sample_curve(&curve, padded.len() - 3, closed, subdivisions)
+}
+
+// the curve of fastest descent from start to end, start has to be the higher one
+pub fn sample_cycloid(start: Vec2, end: Vec2, subdivisions: u32) -> Vec<Vec2> {
+    let d = end - start;
+    if d.x == 0.0 || d.y >= 0.0 {
+        return vec![start, end];
+    }
+    // x = r (t - sin t), y = -r (1 - cos t), find the t where the ratio of x to y matches
+    let ratio = d.x.abs() / -d.y;
+    let f = |t: f32| (t - t.sin()) / (1. - t.cos());
+    let (mut low, mut high) = (1e-3, 2. * PI - 1e-3);
+    for _ in 0..50 {
+        let mid = 0.5 * (low + high);
+        if f(mid) < ratio {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let t_end = 0.5 * (low + high);
+    let r = -d.y / (1. - t_end.cos());
+
+    (0..=subdivisions)
+        .map(|i| {
+            let t = t_end * i as f32 / subdivisions as f32;
+            start + Vec2::new(d.x.signum() * r * (t - t.sin()), -r * (1. - t.cos()))
+        })
+        .collect()
+}
+
+fn segments_len(points: &[Vec2], stride: usize) -> usize {
+    if points.len() < stride + 1 {
+        0
+    } else {
+        (points.len() - 1) / stride
+    }
+}
+
+fn sample_curve(
+    curve: &CubicCurve<Vec2>,
+    segments: usize,
+    closed: bool,
+    subdivisions: u32,
+) -> Vec<Vec2> {
+    if segments == 0 {
+        return Vec::new();
+    }
+    let mut samples = curve
+        .iter_positions(segments * subdivisions.max(1) as usize)
+        .collect::<Vec<_>>();
+    // the wire closes itself, the last sample would be the first one again
+    if closed {
+        samples.pop();
+    }
+    samples
+}
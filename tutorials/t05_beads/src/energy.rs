@@ -0,0 +1,129 @@
+use std::{iter::Sum, ops::Add};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+};
+
+use crate::{analytic::ReadoutText, simulate};
+
+// summed over the beads after each substep, in pixels with the mass a bead is given on spawn
+pub const KINETIC_ENERGY: DiagnosticId =
+    DiagnosticId::from_u128(13867590127751224470960935107946372903);
+pub const POTENTIAL_ENERGY: DiagnosticId =
+    DiagnosticId::from_u128(277113820954133604197385624960163440316);
+pub const TOTAL_ENERGY: DiagnosticId =
+    DiagnosticId::from_u128(90846231777094325613790127443650124795);
+pub const LINEAR_MOMENTUM: DiagnosticId =
+    DiagnosticId::from_u128(224593718048913566274303716822745938169);
+pub const ANGULAR_MOMENTUM: DiagnosticId =
+    DiagnosticId::from_u128(146272053306487114590538470126335290058);
+
+pub struct EnergyDiagnosticsPlugin;
+
+impl Plugin for EnergyDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StepEnergy>()
+            .add_startup_system(setup_diagnostics)
+            .add_system(record_energy.after(simulate))
+            .add_system(update_energy.after(record_energy));
+    }
+}
+
+// potential energy is zero at the origin, angular momentum is about the origin
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Energy {
+    pub kinetic: f32,
+    pub potential: f32,
+    pub linear_momentum: Vec2,
+    pub angular_momentum: f32,
+}
+
+impl Energy {
+    pub fn particle(mass: f32, position: Vec2, velocity: Vec2, gravity: Vec2) -> Self {
+        let momentum = velocity * mass;
+        Self {
+            kinetic: 0.5 * mass * velocity.length_squared(),
+            potential: -mass * gravity.dot(position),
+            linear_momentum: momentum,
+            angular_momentum: position.perp_dot(momentum),
+        }
+    }
+
+    pub fn total(&self) -> f32 {
+        self.kinetic + self.potential
+    }
+}
+
+impl Add for Energy {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            kinetic: self.kinetic + other.kinetic,
+            potential: self.potential + other.potential,
+            linear_momentum: self.linear_momentum + other.linear_momentum,
+            angular_momentum: self.angular_momentum + other.angular_momentum,
+        }
+    }
+}
+
+impl Sum for Energy {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+// the energy after every substep of this frame, simulate fills it in
+#[derive(Resource, Default)]
+pub struct StepEnergy(Vec<Energy>);
+
+impl StepEnergy {
+    pub fn add(&mut self, step: usize, energy: Energy) {
+        if self.0.len() <= step {
+            self.0.resize(step + 1, Energy::default());
+        }
+        self.0[step] = self.0[step] + energy;
+    }
+}
+
+fn setup_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(KINETIC_ENERGY, "kinetic_energy", 20));
+    diagnostics.add(Diagnostic::new(POTENTIAL_ENERGY, "potential_energy", 20));
+    diagnostics.add(Diagnostic::new(TOTAL_ENERGY, "total_energy", 20));
+    diagnostics.add(Diagnostic::new(LINEAR_MOMENTUM, "linear_momentum", 20));
+    diagnostics.add(Diagnostic::new(ANGULAR_MOMENTUM, "angular_momentum", 20));
+}
+
+// one measurement per substep
+fn record_energy(mut steps: ResMut<StepEnergy>, mut diagnostics: ResMut<Diagnostics>) {
+    for energy in steps.0.drain(..) {
+        diagnostics.add_measurement(KINETIC_ENERGY, || energy.kinetic as f64);
+        diagnostics.add_measurement(POTENTIAL_ENERGY, || energy.potential as f64);
+        diagnostics.add_measurement(TOTAL_ENERGY, || energy.total() as f64);
+        diagnostics.add_measurement(LINEAR_MOMENTUM, || energy.linear_momentum.length() as f64);
+        diagnostics.add_measurement(ANGULAR_MOMENTUM, || energy.angular_momentum as f64);
+    }
+}
+
+// the wire does no work, anything the total gains or loses comes from the projection
+// it's the first section of the analytic readout, so it shows without the comparison too
+fn update_energy(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, With<ReadoutText>>) {
+    let average = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.average())
+            .unwrap_or_default()
+    };
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!(
+            "Energy: {:.0} (kinetic {:.0}, potential {:.0})\n\
+             Momentum: linear {:.0}, angular {:.0}",
+            average(TOTAL_ENERGY),
+            average(KINETIC_ENERGY),
+            average(POTENTIAL_ENERGY),
+            average(LINEAR_MOMENTUM),
+            average(ANGULAR_MOMENTUM),
+        );
+    }
+}
@@ -1,15 +1,20 @@
+mod analytic;
 mod components;
+mod curve;
+mod energy;
 mod reset;
 mod resources;
 
+use analytic::*;
 use components::*;
+use curve::sample_cycloid;
+use energy::*;
 use reset::*;
 use resources::*;
 
 use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
 use bevy_inspector_egui::quick::ResourceInspectorPlugin;
 use bevy_prototype_debug_lines::{DebugLines, DebugLinesPlugin};
-use std::f32::consts::PI;
 
 fn main() {
     App::new()
@@ -19,11 +24,14 @@ fn main() {
         .add_plugin(ResourceInspectorPlugin::<Config>::default())
         .add_plugin(DebugLinesPlugin::default())
         .add_plugin(ResetPlugin)
+        .add_plugin(AnalyticPlugin)
+        .add_plugin(EnergyDiagnosticsPlugin)
         .add_startup_system(setup)
         .add_system(draw_wires)
-        .add_system(spawn_beads.in_schedule(OnEnter(ResetState::Playing)))
+        .add_system(spawn_scene.in_schedule(OnEnter(ResetState::Playing)))
         .add_system(simulate)
         .register_type::<Wire>()
+        .register_type::<Bead>()
         .register_type::<Config>()
         .run()
 }
@@ -42,68 +50,218 @@ fn setup(mut commands: Commands) {
         Keep,
     ));
 
-    // Setup Wire
-    commands.spawn((
-        TransformBundle {
-            local: Transform::from_xyz(0., 0., 0.),
-            ..Default::default()
-        },
-        Wire {
-            radius: 100.,
-            line_segments: 70,
-        },
-        Name::new("Wire"),
-        Keep,
-    ));
-
     info!("Press 'R' to reset");
+    info!("Change the scene in the inspector and reset to build it");
 }
 
-fn spawn_beads(
+fn spawn_scene(
     mut commands: Commands,
-    wires: Query<(&Wire, &Transform)>,
     config: Res<Config>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    for (wire, trans) in wires.iter() {
-        for i in 0..config.bead_count {
-            let radius = 10.0 + (fastrand::f32() * 10.0);
-            let mut pos =
-                Transform::from_translation(trans.translation + Vec3::new(wire.radius, 0., 0.));
-            pos.rotate_around(
+    let spawn_wire = |commands: &mut Commands, name: &str, origin: Vec2, wire: Wire| {
+        commands
+            .spawn((
+                TransformBundle::from_transform(Transform::from_translation(origin.extend(0.))),
+                wire,
+                Name::new(format!("{} Wire", name)),
+            ))
+            .id()
+    };
+    let mut beads = Vec::new();
+
+    match config.scene {
+        WireScene::Circle => {
+            let wire = Wire::circle(100., 70);
+            for i in 0..config.bead_count {
+                let pos = wire.sample_point(i as f32 / config.bead_count as f32);
+                let radius = 10.0 + (fastrand::f32() * 10.0);
+                beads.push((Vec2::ZERO, pos, radius, Color::RED));
+            }
+            let e = spawn_wire(&mut commands, "Circle", Vec2::ZERO, wire);
+            spawn_beads(&mut commands, &mut meshes, &mut materials, e, &beads);
+        }
+        WireScene::Brachistochrone => {
+            let start = Vec2::new(-280., 150.);
+            let end = Vec2::new(280., -150.);
+            let wires = [
+                ("Line", Wire::polyline(vec![start, end], false), Color::BLUE),
+                (
+                    "Bezier",
+                    Wire::bezier(
+                        vec![start, Vec2::new(-200., -170.), Vec2::new(0., -170.), end],
+                        false,
+                        50,
+                    ),
+                    Color::GREEN,
+                ),
+                (
+                    "Cycloid",
+                    Wire::polyline(sample_cycloid(start, end, 100), false),
+                    Color::RED,
+                ),
+            ];
+            for (name, wire, color) in wires {
+                let e = spawn_wire(&mut commands, name, Vec2::ZERO, wire);
+                spawn_beads(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    e,
+                    &[(Vec2::ZERO, start, 10., color)],
+                );
+            }
+            info!("Line is blue, bezier green and cycloid red, the cycloid wins");
+        }
+        WireScene::LoopTheLoop => {
+            // has to start higher than two and a half loop radii above the bottom of the loop
+            let points = [
+                (-320., 170.),
+                (-220., 20.),
+                (-120., -120.),
+                (-10., -140.),
+                (57., -117.),
+                (80., -60.),
+                (57., -3.),
+                (0., 20.),
+                (-57., -3.),
+                (-80., -60.),
+                (-57., -117.),
+                (10., -142.),
+                (120., -140.),
+                (320., -140.),
+            ]
+            .map(Vec2::from);
+            let wire = Wire::catmull_rom(points.to_vec(), false, 20);
+            let e = spawn_wire(&mut commands, "Loop", Vec2::ZERO, wire);
+            spawn_beads(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                e,
+                &[(Vec2::ZERO, points[0], 10., Color::RED)],
+            );
+        }
+        WireScene::ClosedCurves => {
+            let blob = [
+                (-100., 0.),
+                (-60., 90.),
+                (40., 60.),
+                (100., -20.),
+                (20., -100.),
+                (-70., -80.),
+            ]
+            .map(Vec2::from);
+            let heart = [
+                (0., -100.),
+                (120., -40.),
+                (80., 100.),
+                (0., 40.),
+                (-80., 100.),
+                (-120., -40.),
+            ]
+            .map(Vec2::from);
+            let wires = [
+                (
+                    "Catmull-Rom",
+                    Vec2::new(-160., 0.),
+                    Wire::catmull_rom(blob.to_vec(), true, 20),
+                ),
+                (
+                    "Bezier",
+                    Vec2::new(160., 0.),
+                    Wire::bezier(heart.to_vec(), true, 50),
+                ),
+            ];
+            for (name, origin, wire) in wires {
+                let beads = (0..config.bead_count)
+                    .map(|i| {
+                        let pos = wire.sample_point(i as f32 / config.bead_count as f32);
+                        (origin, pos, 10., Color::RED)
+                    })
+                    .collect::<Vec<_>>();
+                let e = spawn_wire(&mut commands, name, origin, wire);
+                spawn_beads(&mut commands, &mut meshes, &mut materials, e, &beads);
+            }
+        }
+        WireScene::Analytic => {
+            let radius = 100.;
+            let wire = Wire::circle(radius, 70);
+            let start = wire.sample_point(0.);
+            let e = spawn_wire(&mut commands, "Circle", Vec2::ZERO, wire);
+            let beads = spawn_beads(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                e,
+                &[(Vec2::ZERO, start, 10., Color::RED)],
+            );
+            let analytic = AnalyticBead::new(
+                beads[0],
+                start.extend(0.),
                 Vec3::ZERO,
-                Quat::from_rotation_z(2. * PI / config.bead_count as f32 * i as f32),
+                radius,
+                config.gravity * config.pixels_per_meter,
             );
-
             commands.spawn((
                 MaterialMesh2dBundle {
-                    mesh: meshes.add(shape::Circle::new(radius).into()).into(),
-                    material: materials.add(ColorMaterial::from(Color::RED)),
-                    transform: pos,
+                    mesh: meshes.add(shape::Circle::new(5.).into()).into(),
+                    material: materials.add(ColorMaterial::from(Color::GREEN)),
+                    // drawn on top of the simulated bead
+                    transform: Transform::from_translation(analytic.position() + Vec3::Z),
                     ..Default::default()
                 },
-                Bead {
-                    radius,
-                    mass: radius * radius,
-                    ..default()
-                },
-                Name::new(format!("Bead {}", i)),
+                analytic,
+                Name::new("Analytic Bead"),
             ));
+            info!("The green bead follows the equation of motion, change the sub steps to compare");
         }
     }
 }
 
+// (wire origin, position on the wire, radius, color)
+fn spawn_beads(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    wire: Entity,
+    beads: &[(Vec2, Vec2, f32, Color)],
+) -> Vec<Entity> {
+    let mut entities = Vec::new();
+    for (i, (origin, pos, radius, color)) in beads.iter().enumerate() {
+        let e = commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: meshes.add(shape::Circle::new(*radius).into()).into(),
+                material: materials.add(ColorMaterial::from(*color)),
+                transform: Transform::from_translation((*origin + *pos).extend(0.)),
+                ..Default::default()
+            },
+            Bead {
+                radius: *radius,
+                mass: radius * radius,
+                wire,
+                ..default()
+            },
+            Name::new(format!("Bead {}", i)),
+        ));
+        entities.push(e.id());
+    }
+    entities
+}
+
 fn draw_wires(mut lines: ResMut<DebugLines>, query: Query<(&Wire, &Transform)>) {
     for (wire, transform) in query.iter() {
-        let mut last = transform.translation + Vec3::new(0.0, wire.radius, 0.);
-        let mut trans = transform.clone();
-        let rotation = Quat::from_rotation_z(2. * std::f32::consts::PI / wire.line_segments as f32);
-        for _ in 0..wire.line_segments {
-            trans.rotate(rotation);
-            let current = trans.transform_point(Vec3::new(0.0, wire.radius, 0.));
-            lines.line_colored(last, current, 0.0, Color::BLACK);
-            last = current;
+        let points = &wire.samples;
+        let count = if wire.closed {
+            points.len()
+        } else {
+            points.len().saturating_sub(1)
+        };
+        for i in 0..count {
+            let start = transform.translation + points[i].extend(0.);
+            let end = transform.translation + points[(i + 1) % points.len()].extend(0.);
+            lines.line_colored(start, end, 0.0, Color::BLACK);
         }
     }
 }
@@ -113,20 +271,19 @@ fn simulate(
     wires: Query<(&Wire, &Transform), Without<Bead>>,
     config: Res<Config>,
     time: Res<Time>,
+    mut step_energy: ResMut<StepEnergy>,
 ) {
     let sdt = time.delta_seconds() / config.sub_steps as f32;
+    let gravity = config.gravity * config.pixels_per_meter;
 
-    for _ in 0..config.sub_steps {
+    for step in 0..config.sub_steps as usize {
         for (mut bead, mut transform) in beads.iter_mut() {
             bead.start_step(&mut transform, sdt, &config);
         }
 
-        // Note: Assuming only 1 wire, all beads use it
-        for (wire, wire_trans) in wires.iter() {
-            let wire_center = wire_trans.translation;
-
-            for (mut bead, mut transform) in beads.iter_mut() {
-                bead.keep_on_wire(wire_center, wire.radius, &mut transform);
+        for (mut bead, mut transform) in beads.iter_mut() {
+            if let Ok((wire, wire_trans)) = wires.get(bead.wire) {
+                bead.keep_on_wire(wire, wire_trans.translation, &mut transform);
             }
         }
         for (mut bead, mut transform) in beads.iter_mut() {
@@ -145,6 +302,15 @@ fn simulate(
                 &config,
             )
         }
+
+        let energy = beads
+            .iter()
+            .map(|(bead, transform)| {
+                let position = transform.translation.truncate();
+                Energy::particle(bead.mass, position, bead.velocity, gravity)
+            })
+            .sum();
+        step_energy.add(step, energy);
     }
 }
 
@@ -155,6 +321,9 @@ fn handle_bead_bead_collision(
     trans_b: &mut Transform,
     config: &Config,
 ) {
+    if bead_a.wire != bead_b.wire {
+        return;
+    }
     let mut dir = (trans_b.translation - trans_a.translation).truncate();
     let d = dir.length();
     if d == 0.0 || d > bead_a.radius + bead_b.radius {
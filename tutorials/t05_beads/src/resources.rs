@@ -10,6 +10,20 @@ pub struct Config {
     pub sub_steps: u32,
     pub gravity: Vec2,
     pub restitution: f32,
+    // which wires to build on reset
+    pub scene: WireScene,
+}
+
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WireScene {
+    #[default]
+    Circle,
+    // the same drop along a line, a bezier and a cycloid, the cycloid is fastest
+    Brachistochrone,
+    LoopTheLoop,
+    ClosedCurves,
+    // one bead on a circle next to the solution of its equation of motion
+    Analytic,
 }
 
 impl Default for Config {
@@ -20,6 +34,7 @@ impl Default for Config {
             sub_steps: 100,
             gravity: Vec2::new(0.0, -9.81),
             restitution: 1.0,
+            scene: WireScene::Circle,
         }
     }
 }
@@ -3,7 +3,16 @@ use bevy::prelude::*;
 #[derive(Reflect, Resource, Default)]
 #[reflect(Resource)]
 pub struct Pendulms {
-    pub list: Vec<Vec<Entity>>,
+    pub list: Vec<Pendulm>,
+}
+
+#[derive(Reflect, FromReflect, Default)]
+pub struct Pendulm {
+    // from the origin out
+    pub segments: Vec<Entity>,
+    pub color: Color,
+    // where the last bob was a frame ago, the trail continues from it
+    pub last_tip: Option<Vec3>,
 }
 
 #[derive(Reflect, Component, Default)]
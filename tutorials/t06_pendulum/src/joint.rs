@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+use std::f32::consts::PI;
+
+use crate::rigid_body::{solve_angular, solve_positional, Body};
+
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, Default, PartialEq)]
+pub enum JointKind {
+    // rotation about the joint axis only, limits in radians
+    Hinge { limits: Option<Vec2> },
+    // free rotation about the anchor, swing is the largest angle between the joint axes of the
+    // two bodies and twist the range of rotation about them
+    Spherical {
+        swing: Option<f32>,
+        twist: Option<Vec2>,
+    },
+    // sliding along the joint axis without rotating, limits in meters
+    Prismatic { limits: Option<Vec2> },
+    #[default]
+    Fixed,
+}
+
+// connects two rigid bodies, or body_b to the world when body_a is None, anchors and frames are
+// local to each body or in world space for the world, the x axis of a frame is the joint axis
+// and its y axis the zero for limit angles
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Joint {
+    pub kind: JointKind,
+    pub body_a: Option<Entity>,
+    pub body_b: Entity,
+    pub anchor_a: Vec3,
+    pub anchor_b: Vec3,
+    pub frame_a: Quat,
+    pub frame_b: Quat,
+    // m / N for the anchor, rad / Nm for the angular part
+    pub compliance: f32,
+}
+
+impl Default for Joint {
+    fn default() -> Self {
+        Self {
+            kind: JointKind::default(),
+            body_a: None,
+            body_b: Entity::PLACEHOLDER,
+            anchor_a: Vec3::ZERO,
+            anchor_b: Vec3::ZERO,
+            frame_a: Quat::IDENTITY,
+            frame_b: Quat::IDENTITY,
+            compliance: 0.0,
+        }
+    }
+}
+
+impl Joint {
+    // anchor and axis in world space, the current poses of the bodies become the rest pose
+    pub fn new(
+        kind: JointKind,
+        a: Option<(Entity, &Transform)>,
+        b: (Entity, &Transform),
+        anchor: Vec3,
+        axis: Vec3,
+    ) -> Self {
+        let frame = Quat::from_rotation_arc(Vec3::X, axis.normalize());
+        let (body_a, anchor_a, frame_a) = match a {
+            Some((e, trans)) => (
+                Some(e),
+                trans.rotation.inverse() * (anchor - trans.translation),
+                trans.rotation.inverse() * frame,
+            ),
+            None => (None, anchor, frame),
+        };
+        let (body_b, trans) = b;
+        Self {
+            kind,
+            body_a,
+            body_b,
+            anchor_a,
+            anchor_b: trans.rotation.inverse() * (anchor - trans.translation),
+            frame_a,
+            frame_b: trans.rotation.inverse() * frame,
+            compliance: 0.0,
+        }
+    }
+
+    // a and b are the solver indices of body_a and body_b
+    pub fn solve(&self, bodies: &mut [Body], a: Option<usize>, b: usize, dt: f32) {
+        let alpha = self.compliance / dt / dt;
+
+        match self.kind {
+            JointKind::Hinge { limits } => {
+                let (frame_a, frame_b) = self.frames(bodies, a, b);
+                let corr = (frame_a * Vec3::X).cross(frame_b * Vec3::X);
+                solve_angular(bodies, a, Some(b), corr, alpha);
+
+                if let Some(limits) = limits {
+                    let (frame_a, frame_b) = self.frames(bodies, a, b);
+                    if let Some(corr) = limit_angle(
+                        frame_a * Vec3::X,
+                        frame_a * Vec3::Y,
+                        frame_b * Vec3::Y,
+                        limits.x,
+                        limits.y,
+                    ) {
+                        solve_angular(bodies, a, Some(b), corr, alpha);
+                    }
+                }
+            }
+            JointKind::Spherical { swing, twist } => {
+                if let Some(swing) = swing {
+                    let (frame_a, frame_b) = self.frames(bodies, a, b);
+                    let (a0, a1) = (frame_a * Vec3::X, frame_b * Vec3::X);
+                    let n = a0.cross(a1).normalize_or_zero();
+                    if n != Vec3::ZERO {
+                        if let Some(corr) = limit_angle(n, a0, a1, -swing, swing) {
+                            solve_angular(bodies, a, Some(b), corr, alpha);
+                        }
+                    }
+                }
+
+                if let Some(twist) = twist {
+                    let (frame_a, frame_b) = self.frames(bodies, a, b);
+                    let n = (frame_a * Vec3::X + frame_b * Vec3::X).normalize_or_zero();
+                    let (b0, b1) = (frame_a * Vec3::Y, frame_b * Vec3::Y);
+                    let n0 = (b0 - n * n.dot(b0)).normalize_or_zero();
+                    let n1 = (b1 - n * n.dot(b1)).normalize_or_zero();
+                    if n != Vec3::ZERO && n0 != Vec3::ZERO && n1 != Vec3::ZERO {
+                        if let Some(corr) = limit_angle(n, n0, n1, twist.x, twist.y) {
+                            solve_angular(bodies, a, Some(b), corr, alpha);
+                        }
+                    }
+                }
+            }
+            JointKind::Prismatic { .. } | JointKind::Fixed => {
+                let (frame_a, frame_b) = self.frames(bodies, a, b);
+                solve_angular(bodies, a, Some(b), rotation_between(frame_a, frame_b), alpha);
+            }
+        }
+
+        // anchors last, a gap is what the eye notices first
+        let (pos_a, rot_a) = pose(bodies, a);
+        let r_a = rot_a * self.anchor_a;
+        let r_b = bodies[b].rot * self.anchor_b;
+        let mut corr = bodies[b].pos + r_b - (pos_a + r_a);
+        if let JointKind::Prismatic { limits } = self.kind {
+            let axis = rot_a * self.frame_a * Vec3::X;
+            let along = corr.dot(axis);
+            corr -= axis * limits.map_or(along, |l| along.clamp(l.x, l.y));
+        }
+        solve_positional(bodies, a, Some(b), r_a, r_b, corr, alpha);
+    }
+
+    fn frames(&self, bodies: &[Body], a: Option<usize>, b: usize) -> (Quat, Quat) {
+        let (_, rot_a) = pose(bodies, a);
+        (rot_a * self.frame_a, bodies[b].rot * self.frame_b)
+    }
+}
+
+// the world sits at the origin
+fn pose(bodies: &[Body], i: Option<usize>) -> (Vec3, Quat) {
+    match i {
+        Some(i) => (bodies[i].pos, bodies[i].rot),
+        None => (Vec3::ZERO, Quat::IDENTITY),
+    }
+}
+
+// rotation vector that turns a into b
+fn rotation_between(a: Quat, b: Quat) -> Vec3 {
+    let dq = b * a.inverse();
+    let v = Vec3::new(dq.x, dq.y, dq.z) * 2.0;
+    if dq.w < 0.0 {
+        -v
+    } else {
+        v
+    }
+}
+
+// the angle from n0 to n1 about n, both perpendicular to n, clamped into [min, max], returns the
+// rotation of body a that gets there or None if already inside
+fn limit_angle(n: Vec3, n0: Vec3, n1: Vec3, min: f32, max: f32) -> Option<Vec3> {
+    let mut phi = n0.cross(n1).dot(n).clamp(-1.0, 1.0).asin();
+    if n0.dot(n1) < 0.0 {
+        phi = PI - phi;
+    }
+    if phi > PI {
+        phi -= 2.0 * PI;
+    }
+    if phi < -PI {
+        phi += 2.0 * PI;
+    }
+
+    if phi >= min && phi <= max {
+        return None;
+    }
+    let n0 = Quat::from_axis_angle(n, phi.clamp(min, max)) * n0;
+    Some(n0.cross(n1))
+}
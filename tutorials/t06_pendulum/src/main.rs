@@ -1,12 +1,22 @@
+mod analytic;
 mod components;
+mod energy;
+mod joint;
+mod pendulum;
 mod reset;
 mod resources;
+mod rigid_body;
 
 use std::f32::consts::PI;
 
+use analytic::*;
 use components::*;
+use energy::*;
+use joint::*;
+use pendulum::*;
 use reset::*;
 use resources::*;
+use rigid_body::*;
 
 use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
 use bevy_inspector_egui::quick::{ResourceInspectorPlugin, WorldInspectorPlugin};
@@ -22,14 +32,20 @@ fn main() {
         .add_plugin(ResourceInspectorPlugin::<Config>::default())
         .add_plugin(DebugLinesPlugin::default())
         .add_plugin(ResetPlugin)
+        .add_plugin(AnalyticPlugin)
+        .add_plugin(EnergyDiagnosticsPlugin)
         .add_startup_system(setup)
         .add_system(spawn_pendulum.in_schedule(OnEnter(ResetState::Playing)))
         .add_systems(
-            (simulate, draw_lengths)
+            (simulate, simulate_rigid_bodies, draw_lengths)
                 .chain()
                 .in_set(OnUpdate(ResetState::Playing)),
         )
         .register_type::<Config>()
+        .register_type::<RigidBody>()
+        .register_type::<Velocity>()
+        .register_type::<Joint>()
+        .register_type::<JointKind>()
         .run()
 }
 
@@ -55,65 +71,69 @@ fn spawn_pendulum(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut pendulms: ResMut<Pendulms>,
+    config: Res<Config>,
 ) {
-    let mut pos = Transform::default();
-    let mut segements = Vec::new();
-    for (index, (length, mass, angle)) in [
-        (0.5f32, 1.0f32, -PI * 0.5),
-        (1.0, 0.5, PI * 0.5),
-        (2.0, 0.1, 0.0),
-    ]
-    .iter()
-    .enumerate()
-    {
-        let radius = 0.05 + mass.sqrt() * 0.3;
-        pos.rotate_local_z(*angle);
-        pos.translation = pos.transform_point(Vec3::new(0.0, *length, 0.));
-        let id = commands
-            .spawn((
-                MaterialMesh2dBundle {
-                    mesh: meshes.add(shape::Circle::new(radius).into()).into(),
-                    material: materials.add(ColorMaterial::from(Color::RED)),
-                    transform: pos.clone(),
-                    ..Default::default()
-                },
-                PendulmSegment {
-                    length: *length,
-                    radius,
-                    mass: *mass,
-                    prev_pos: pos.translation.truncate(),
-                    ..default()
-                },
-                Name::new(format!("Pendulm Segment {}", index)),
-            ))
-            .id();
-        segements.push(id);
+    if config.compare_analytic {
+        // the analytic solution is for a single point mass, not a rigid ball
+        let builder = PendulmBuilder::new(vec![PendulmLink::new(2.0, 1.0, -PI * 0.5)]);
+        let pendulm = builder.spawn(&mut commands, &mut meshes, &mut materials);
+        let analytic = AnalyticPendulum::new(
+            pendulm.segments[0],
+            builder.positions()[0].translation,
+            config.gravity,
+        );
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: meshes.add(shape::Circle::new(0.1).into()).into(),
+                material: materials.add(ColorMaterial::from(Color::GREEN)),
+                // drawn on top of the simulated bob
+                transform: Transform::from_translation(analytic.position() + Vec3::Z),
+                ..Default::default()
+            },
+            analytic,
+            Name::new("Analytic Pendulm"),
+        ));
+        pendulms.list.push(pendulm);
+        info!("The green bob follows the equation of motion, change the sub steps to compare");
+        return;
     }
 
-    pendulms.list.push(segements);
+    for i in 0..config.copies {
+        let pendulm = PendulmBuilder::new(config.links.clone())
+            .rigid(config.rigid_links)
+            .perturb(config.perturbation * i as f32)
+            .color(Color::hsl(
+                360. * i as f32 / config.copies.max(1) as f32,
+                0.9,
+                0.6,
+            ))
+            .name(format!("Pendulm {}", i))
+            .spawn(&mut commands, &mut meshes, &mut materials);
+        pendulms.list.push(pendulm);
+    }
 }
 
 fn draw_lengths(
     mut lines: ResMut<DebugLines>,
-    query: Query<&Transform, With<PendulmSegment>>,
-    pendulms: Res<Pendulms>,
-    mut last_pos: Local<Option<Vec3>>,
+    query: Query<&Transform>,
+    mut pendulms: ResMut<Pendulms>,
+    config: Res<Config>,
 ) {
-    for p in pendulms.list.iter() {
+    for p in pendulms.list.iter_mut() {
         let mut last = Vec3::ZERO;
-        for (index, e) in p.iter().enumerate() {
-            let trans = query.get(*e).unwrap();
+        for e in p.segments.iter() {
+            let Ok(trans) = query.get(*e) else {
+                continue;
+            };
             lines.line_colored(last, trans.translation, 0.0, Color::GRAY);
             last = trans.translation;
+        }
 
-            // Draw trail of last segment
-            if index == p.len() - 1 {
-                if let Some(pos) = *last_pos {
-                    lines.line_colored(last, pos, 1.5, Color::RED);
-                }
-                *last_pos = Some(last);
-            }
+        // each pendulum continues its own trail from its last bob
+        if let Some(pos) = p.last_tip {
+            lines.line_colored(last, pos, config.trail_duration, p.color);
         }
+        p.last_tip = Some(last);
     }
 }
 
@@ -122,14 +142,16 @@ fn simulate(
     pendulms: Res<Pendulms>,
     config: Res<Config>,
     time: Res<Time>,
+    mut step_energy: ResMut<StepEnergy>,
 ) {
     if time.delta_seconds() == 0.0 {
         return;
     }
 
     let sdt = time.delta_seconds() / config.sub_steps as f32;
+    let gravity = config.gravity.extend(0.0);
 
-    for _ in 0..config.sub_steps {
+    for step in 0..config.sub_steps as usize {
         for (mut p, mut transform) in query.iter_mut() {
             p.velocity += config.gravity * sdt;
             p.prev_pos = transform.translation.truncate();
@@ -139,6 +161,11 @@ fn simulate(
         let mut p0 = PendulmSegment::default();
         let mut t0 = Transform::default();
         for p in pendulms.list.iter() {
+            let p = &p.segments;
+            // rigid links are simulated as bodies
+            if p.iter().any(|e| !query.contains(*e)) {
+                continue;
+            }
             for (index, e) in p.iter().enumerate() {
                 let (p0, t0, p1, t1) = match index {
                     0 => {
@@ -174,5 +201,13 @@ fn simulate(
         for (mut p, transform) in query.iter_mut() {
             p.velocity = ((transform.translation - p.prev_pos.extend(0.)) / sdt).truncate();
         }
+
+        let energy = query
+            .iter()
+            .map(|(p, transform)| {
+                Energy::particle(p.mass, transform.translation, p.velocity.extend(0.0), gravity)
+            })
+            .sum();
+        step_energy.add(step, energy);
     }
 }
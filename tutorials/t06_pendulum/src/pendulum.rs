@@ -0,0 +1,133 @@
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+
+use crate::{
+    components::{Pendulm, PendulmSegment},
+    joint::{Joint, JointKind},
+    resources::PendulmLink,
+    rigid_body::{RigidBody, Velocity},
+};
+
+// chain of links hanging from the origin, each link's angle is relative to the one before
+pub struct PendulmBuilder {
+    pub links: Vec<PendulmLink>,
+    // hinged rigid bodies instead of distance constrained particles
+    pub rigid: bool,
+    pub color: Color,
+    pub name: String,
+}
+
+impl PendulmBuilder {
+    pub fn new(links: Vec<PendulmLink>) -> Self {
+        Self {
+            links,
+            rigid: false,
+            color: Color::RED,
+            name: "Pendulm".to_string(),
+        }
+    }
+
+    pub fn rigid(mut self, rigid: bool) -> Self {
+        self.rigid = rigid;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    // the first link's angle is turned by angle, to start copies a tiny bit apart
+    pub fn perturb(mut self, angle: f32) -> Self {
+        if let Some(link) = self.links.first_mut() {
+            link.angle += angle;
+        }
+        self
+    }
+
+    // where the bob at the end of each link starts
+    pub fn positions(&self) -> Vec<Transform> {
+        let mut pos = Transform::default();
+        self.links
+            .iter()
+            .map(|link| {
+                pos.rotate_local_z(link.angle);
+                pos.translation = pos.transform_point(Vec3::new(0.0, link.length, 0.));
+                pos
+            })
+            .collect()
+    }
+
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<ColorMaterial>,
+    ) -> Pendulm {
+        let mut prev_pos = Transform::default();
+        let mut segments = Vec::new();
+        let material = materials.add(ColorMaterial::from(self.color));
+
+        for (index, (link, pos)) in self.links.iter().zip(self.positions()).enumerate() {
+            let radius = 0.05 + link.mass.sqrt() * 0.3;
+            let bundle = (
+                MaterialMesh2dBundle {
+                    mesh: meshes.add(shape::Circle::new(radius).into()).into(),
+                    material: material.clone(),
+                    transform: pos,
+                    ..Default::default()
+                },
+                Name::new(format!("{} Segment {}", self.name, index)),
+            );
+
+            let id = if self.rigid {
+                // the body sits on the bob and hinges about z at the previous bob, or the origin
+                let id = commands
+                    .spawn((
+                        bundle,
+                        RigidBody::ball(link.mass, radius),
+                        Velocity::default(),
+                    ))
+                    .id();
+                let prev = segments.last().map(|&e| (e, &prev_pos));
+                let anchor = prev.map_or(Vec3::ZERO, |(_, t)| t.translation);
+                commands.spawn((
+                    Joint::new(
+                        JointKind::Hinge { limits: None },
+                        prev,
+                        (id, &pos),
+                        anchor,
+                        Vec3::Z,
+                    ),
+                    Name::new(format!("{} Hinge {}", self.name, index)),
+                ));
+                id
+            } else {
+                commands
+                    .spawn((
+                        bundle,
+                        PendulmSegment {
+                            length: link.length,
+                            radius,
+                            mass: link.mass,
+                            prev_pos: pos.translation.truncate(),
+                            ..default()
+                        },
+                    ))
+                    .id()
+            };
+            prev_pos = pos;
+            segments.push(id);
+        }
+
+        Pendulm {
+            segments,
+            color: self.color,
+            last_tip: None,
+        }
+    }
+}
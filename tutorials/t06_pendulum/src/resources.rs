@@ -1,5 +1,6 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::{prelude::ReflectInspectorOptions, InspectorOptions};
+use std::f32::consts::PI;
 
 #[derive(Reflect, Resource, InspectorOptions)]
 #[reflect(Resource, InspectorOptions)]
@@ -7,6 +8,37 @@ pub struct Config {
     #[inspector(min = 1, max = 500)]
     pub sub_steps: u32,
     pub gravity: Vec2,
+    // hinged rigid bodies instead of distance constrained particles, applied on reset
+    pub rigid_links: bool,
+    // a single particle pendulum next to the solution of its equation of motion, applied on
+    // reset
+    pub compare_analytic: bool,
+    // from the origin out, applied on reset
+    pub links: Vec<PendulmLink>,
+    // pendulums spawned on top of each other, each one's first angle turned a bit further
+    pub copies: u32,
+    // radians
+    pub perturbation: f32,
+    // seconds the trails stay
+    pub trail_duration: f32,
+}
+
+// angle in radians, relative to the link before
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, Default)]
+pub struct PendulmLink {
+    pub length: f32,
+    pub mass: f32,
+    pub angle: f32,
+}
+
+impl PendulmLink {
+    pub fn new(length: f32, mass: f32, angle: f32) -> Self {
+        Self {
+            length,
+            mass,
+            angle,
+        }
+    }
 }
 
 impl Default for Config {
@@ -14,6 +46,16 @@ impl Default for Config {
         Self {
             sub_steps: 100,
             gravity: Vec2::new(0.0, -9.81),
+            rigid_links: false,
+            compare_analytic: false,
+            links: vec![
+                PendulmLink::new(0.5, 1.0, -PI * 0.5),
+                PendulmLink::new(1.0, 0.5, PI * 0.5),
+                PendulmLink::new(2.0, 0.1, 0.0),
+            ],
+            copies: 5,
+            perturbation: 0.001,
+            trail_duration: 1.5,
         }
     }
 }
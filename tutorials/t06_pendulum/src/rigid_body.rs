@@ -0,0 +1,275 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    energy::{Energy, StepEnergy},
+    joint::Joint,
+    resources::Config,
+};
+
+// xpbd rigid bodies after Müller et al. "Detailed Rigid Body Simulation with Extended Position
+// Based Dynamics", only joints and gravity, no contacts
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct RigidBody {
+    pub inv_mass: f32,
+    // diagonal of the inverse inertia tensor in the local frame
+    pub inv_inertia: Vec3,
+}
+
+impl Default for RigidBody {
+    fn default() -> Self {
+        Self::fixed()
+    }
+}
+
+impl RigidBody {
+    pub fn new(mass: f32, inertia: Vec3) -> Self {
+        Self {
+            inv_mass: 1.0 / mass,
+            inv_inertia: inertia.recip(),
+        }
+    }
+
+    // solid ball, what a pendulum bob is closest to
+    pub fn ball(mass: f32, radius: f32) -> Self {
+        Self::new(mass, Vec3::splat(0.4 * mass * radius * radius))
+    }
+
+    pub fn fixed() -> Self {
+        Self {
+            inv_mass: 0.0,
+            inv_inertia: Vec3::ZERO,
+        }
+    }
+}
+
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct Velocity {
+    pub linear: Vec3,
+    // world space, rad / s
+    pub angular: Vec3,
+}
+
+// solver copy of a body, written back to the components after all substeps
+pub struct Body {
+    pub pos: Vec3,
+    pub rot: Quat,
+    prev_pos: Vec3,
+    prev_rot: Quat,
+    vel: Vec3,
+    omega: Vec3,
+    inv_mass: f32,
+    inv_inertia: Vec3,
+}
+
+impl Body {
+    fn integrate(&mut self, dt: f32, gravity: Vec3) {
+        self.prev_pos = self.pos;
+        self.prev_rot = self.rot;
+        if self.inv_mass == 0.0 {
+            return;
+        }
+
+        self.vel += gravity * dt;
+        self.pos += self.vel * dt;
+
+        let omega = self.rot.inverse() * self.omega;
+        let inertia = self.inv_inertia.recip();
+        let torque = -omega.cross(inertia * omega);
+        self.omega += self.rot * (self.inv_inertia * torque) * dt;
+
+        self.rot = rotate(self.rot, self.omega * dt);
+    }
+
+    fn update_velocities(&mut self, dt: f32) {
+        if self.inv_mass == 0.0 {
+            return;
+        }
+
+        self.vel = (self.pos - self.prev_pos) / dt;
+        let dq = self.rot * self.prev_rot.inverse();
+        self.omega = Vec3::new(dq.x, dq.y, dq.z) * (2.0 / dt);
+        if dq.w < 0.0 {
+            self.omega = -self.omega;
+        }
+    }
+
+    // fixed bodies don't move and have no energy to count
+    fn energy(&self, gravity: Vec3) -> Energy {
+        if self.inv_mass == 0.0 {
+            return Energy::default();
+        }
+        Energy::rigid_body(
+            1.0 / self.inv_mass,
+            self.inv_inertia.recip(),
+            self.pos,
+            self.rot,
+            self.vel,
+            self.omega,
+            gravity,
+        )
+    }
+
+    // inverse mass seen by a correction along normal at the world offset r from the center
+    fn generalized_inv_mass(&self, r: Vec3, normal: Vec3) -> f32 {
+        self.inv_mass + self.angular_inv_mass(r.cross(normal))
+    }
+
+    // inverse inertia about the world axis n
+    fn angular_inv_mass(&self, n: Vec3) -> f32 {
+        let n = self.rot.inverse() * n;
+        n.dot(self.inv_inertia * n)
+    }
+
+    // applies the positional impulse p at the world offset r from the center
+    fn apply_impulse(&mut self, p: Vec3, r: Vec3) {
+        self.pos += p * self.inv_mass;
+        self.apply_angular_impulse(r.cross(p));
+    }
+
+    fn apply_angular_impulse(&mut self, p: Vec3) {
+        let dw = self.rot * (self.inv_inertia * (self.rot.inverse() * p));
+        self.rot = rotate(self.rot, dw);
+    }
+}
+
+// moves the world offsets r_a and r_b of the bodies together, corr is where the point of b is
+// minus where the point of a is, a body of None is the static world, alpha = compliance / dt²
+pub fn solve_positional(
+    bodies: &mut [Body],
+    a: Option<usize>,
+    b: Option<usize>,
+    r_a: Vec3,
+    r_b: Vec3,
+    corr: Vec3,
+    alpha: f32,
+) {
+    let c = corr.length();
+    if c == 0.0 {
+        return;
+    }
+    let n = corr / c;
+    let w_a = a.map_or(0.0, |a| bodies[a].generalized_inv_mass(r_a, n));
+    let w_b = b.map_or(0.0, |b| bodies[b].generalized_inv_mass(r_b, n));
+    let w = w_a + w_b + alpha;
+    if w == 0.0 {
+        return;
+    }
+
+    let p = n * (c / w);
+    if let Some(a) = a {
+        bodies[a].apply_impulse(p, r_a);
+    }
+    if let Some(b) = b {
+        bodies[b].apply_impulse(-p, r_b);
+    }
+}
+
+// rotates a by the rotation vector corr and b by its opposite, split by their inverse inertia
+pub fn solve_angular(
+    bodies: &mut [Body],
+    a: Option<usize>,
+    b: Option<usize>,
+    corr: Vec3,
+    alpha: f32,
+) {
+    let theta = corr.length();
+    if theta == 0.0 {
+        return;
+    }
+    let n = corr / theta;
+    let w_a = a.map_or(0.0, |a| bodies[a].angular_inv_mass(n));
+    let w_b = b.map_or(0.0, |b| bodies[b].angular_inv_mass(n));
+    let w = w_a + w_b + alpha;
+    if w == 0.0 {
+        return;
+    }
+
+    let p = n * (theta / w);
+    if let Some(a) = a {
+        bodies[a].apply_angular_impulse(p);
+    }
+    if let Some(b) = b {
+        bodies[b].apply_angular_impulse(-p);
+    }
+}
+
+// q + 0.5 * [w, 0] * q, normalized
+fn rotate(q: Quat, w: Vec3) -> Quat {
+    let dq = Quat::from_xyzw(w.x, w.y, w.z, 0.0) * q;
+    (q + dq * 0.5).normalize()
+}
+
+pub fn simulate_rigid_bodies(
+    mut query: Query<(Entity, &mut Transform, &mut Velocity, &RigidBody)>,
+    joint_query: Query<&Joint>,
+    time: Res<Time>,
+    config: Res<Config>,
+    mut step_energy: ResMut<StepEnergy>,
+) {
+    if time.delta_seconds() == 0.0 {
+        return;
+    }
+
+    let sdt = time.delta_seconds() / config.sub_steps as f32;
+
+    let mut bodies = query
+        .iter()
+        .map(|(_, trans, vel, rb)| Body {
+            pos: trans.translation,
+            rot: trans.rotation,
+            prev_pos: trans.translation,
+            prev_rot: trans.rotation,
+            vel: vel.linear,
+            omega: vel.angular,
+            inv_mass: rb.inv_mass,
+            inv_inertia: rb.inv_inertia,
+        })
+        .collect::<Vec<_>>();
+
+    // joints to solver indices, joints to despawned bodies are skipped
+    let index = query
+        .iter()
+        .enumerate()
+        .map(|(i, (e, ..))| (e, i))
+        .collect::<HashMap<_, _>>();
+    let joints = joint_query
+        .iter()
+        .filter_map(|joint| {
+            let b = *index.get(&joint.body_b)?;
+            let a = match joint.body_a {
+                Some(a) => Some(*index.get(&a)?),
+                None => None,
+            };
+            Some((joint, a, b))
+        })
+        .collect::<Vec<_>>();
+
+    let gravity = config.gravity.extend(0.0);
+    for step in 0..config.sub_steps as usize {
+        for body in bodies.iter_mut() {
+            body.integrate(sdt, gravity);
+        }
+
+        for &(joint, a, b) in joints.iter() {
+            joint.solve(&mut bodies, a, b, sdt);
+        }
+
+        for body in bodies.iter_mut() {
+            body.update_velocities(sdt);
+        }
+
+        step_energy.add(step, bodies.iter().map(|body| body.energy(gravity)).sum());
+    }
+
+    for ((_, mut trans, mut vel, rb), body) in query.iter_mut().zip(bodies.iter()) {
+        if rb.inv_mass == 0.0 {
+            continue;
+        }
+        trans.translation = body.pos;
+        trans.rotation = body.rot;
+        vel.linear = body.vel;
+        vel.angular = body.omega;
+    }
+}
@@ -3,8 +3,10 @@ use bevy_inspector_egui::quick::ResourceInspectorPlugin;
 use std::f32::consts::FRAC_PI_2;
 
 use crate::{
+    collider::Collider,
     components::{Ball, Velocity},
     intersect::ray_sphere_intersect,
+    rigid_body::RigidBody,
 };
 
 pub struct CameraGrabberPlugin;
@@ -106,12 +108,22 @@ fn handle_grab_none(
     }
 }
 
+// balls are picked by their radius, rigid bodies by their collider's bounding sphere
+type PickData = (
+    Entity,
+    &'static Transform,
+    &'static mut Velocity,
+    Option<&'static Ball>,
+    Option<&'static Collider>,
+);
+type Grabbable = Or<(With<Ball>, With<RigidBody>)>;
+
 fn handle_grab_start(
     mut grabbed: ResMut<Grabbed>,
     window_query: Query<&Window>,
     camera_query: Query<(&GlobalTransform, &Camera), With<CameraGrabber>>,
     mut grab_next_state: ResMut<NextState<GrabState>>,
-    mut query_balls: Query<(Entity, &Transform, &mut Velocity, &Ball)>,
+    mut query_balls: Query<PickData>,
 ) {
     grabbed.time = 0.;
 
@@ -122,13 +134,19 @@ fn handle_grab_start(
         let ray = camera.viewport_to_world(camera_trans, cusor_pos).unwrap();
 
         // Bevy Mod Picker is not updated for 0.10 yet, doing our own raycast
-        let mut closest = std::f32::MAX;
+        let mut closest = f32::MAX;
         let mut closest_entity = None;
         let mut closest_offset = Vec3::ZERO;
         let mut closest_pos = Vec3::ZERO;
-        for (e, trans, _vel, ball) in query_balls.iter() {
+        for (e, trans, _vel, ball, collider) in query_balls.iter() {
+            // rigid bodies are picked by their bounding sphere
+            let radius = match (ball, collider) {
+                (Some(ball), _) => ball.0,
+                (None, Some(collider)) => collider.bounding_radius(),
+                (None, None) => continue,
+            };
             if let Some((t0, t1)) =
-                ray_sphere_intersect(ray.origin, ray.direction, trans.translation, ball.0)
+                ray_sphere_intersect(ray.origin, ray.direction, trans.translation, radius)
             {
                 let t = t0.min(t1);
 
@@ -164,7 +182,7 @@ fn handle_grab_move(
     mut grabbed: ResMut<Grabbed>,
     mut grab_next_state: ResMut<NextState<GrabState>>,
     time: Res<Time>,
-    mut query_balls: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+    mut query_balls: Query<(&mut Transform, &mut Velocity), Grabbable>,
     window_query: Query<&Window>,
     camera_query: Query<(&GlobalTransform, &Camera), With<CameraGrabber>>,
 ) {
@@ -0,0 +1,360 @@
+use bevy::prelude::*;
+use std::f32::consts::PI;
+
+// collision shape of a rigid body in its local frame, capsules run along local y
+#[derive(Reflect, FromReflect, Component, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component)]
+pub enum Collider {
+    Sphere { radius: f32 },
+    Box { half_extents: Vec3 },
+    Capsule { radius: f32, half_height: f32 },
+}
+
+impl Default for Collider {
+    fn default() -> Self {
+        Self::Sphere { radius: 0.5 }
+    }
+}
+
+impl Collider {
+    // mass and the diagonal of the inertia tensor in the local frame
+    pub fn mass_properties(&self, density: f32) -> (f32, Vec3) {
+        match *self {
+            Collider::Sphere { radius } => {
+                let m = 4.0 / 3.0 * PI * radius.powi(3) * density;
+                (m, Vec3::splat(0.4 * m * radius * radius))
+            }
+            Collider::Box { half_extents: h } => {
+                let m = 8.0 * h.x * h.y * h.z * density;
+                let h2 = h * h;
+                (
+                    m,
+                    Vec3::new(h2.y + h2.z, h2.x + h2.z, h2.x + h2.y) * (m / 3.0),
+                )
+            }
+            Collider::Capsule {
+                radius: r,
+                half_height,
+            } => {
+                // cylinder plus two half spheres shifted out to the caps
+                let h = 2.0 * half_height;
+                let m_cyl = PI * r * r * h * density;
+                let m_caps = 4.0 / 3.0 * PI * r.powi(3) * density;
+                let axial = m_cyl * r * r / 2.0 + m_caps * 0.4 * r * r;
+                let across = m_cyl * (h * h / 12.0 + r * r / 4.0)
+                    + m_caps * (0.4 * r * r + h * h / 4.0 + 3.0 * h * r / 8.0);
+                (m_cyl + m_caps, Vec3::new(across, axial, across))
+            }
+        }
+    }
+
+    // for raycasting and broad tests
+    pub fn bounding_radius(&self) -> f32 {
+        match *self {
+            Collider::Sphere { radius } => radius,
+            Collider::Box { half_extents } => half_extents.length(),
+            Collider::Capsule {
+                radius,
+                half_height,
+            } => radius + half_height,
+        }
+    }
+
+    pub fn to_world(self, trans: &Transform) -> WorldShape {
+        match self {
+            Collider::Sphere { radius } => WorldShape::Round {
+                a: trans.translation,
+                b: trans.translation,
+                radius,
+            },
+            Collider::Box { half_extents } => WorldShape::Box {
+                center: trans.translation,
+                rotation: trans.rotation,
+                half_extents,
+            },
+            Collider::Capsule {
+                radius,
+                half_height,
+            } => {
+                let axis = trans.rotation * Vec3::Y * half_height;
+                WorldShape::Round {
+                    a: trans.translation - axis,
+                    b: trans.translation + axis,
+                    radius,
+                }
+            }
+        }
+    }
+}
+
+// spheres and capsules are both a segment with a radius
+#[derive(Clone, Copy, Debug)]
+pub enum WorldShape {
+    Round { a: Vec3, b: Vec3, radius: f32 },
+    Box { center: Vec3, rotation: Quat, half_extents: Vec3 },
+}
+
+// point_a is the deepest point of shape a inside shape b and point_b the matching point on the
+// surface of b, normal points from b to a, both points in world space
+#[derive(Clone, Copy, Debug)]
+pub struct ContactPoint {
+    pub point_a: Vec3,
+    pub point_b: Vec3,
+    pub normal: Vec3,
+}
+
+impl WorldShape {
+    // against the half space below the plane normal . x = offset
+    pub fn plane_contacts(&self, normal: Vec3, offset: f32, contacts: &mut Vec<ContactPoint>) {
+        let mut add = |p: Vec3, radius: f32| {
+            let d = normal.dot(p) - offset - radius;
+            if d < 0.0 {
+                let point_a = p - normal * radius;
+                contacts.push(ContactPoint {
+                    point_a,
+                    point_b: point_a - normal * d,
+                    normal,
+                });
+            }
+        };
+
+        match *self {
+            WorldShape::Round { a, b, radius } => {
+                add(a, radius);
+                if a != b {
+                    add(b, radius);
+                }
+            }
+            WorldShape::Box { .. } => {
+                for corner in self.corners() {
+                    add(corner, 0.0);
+                }
+            }
+        }
+    }
+
+    pub fn contacts(&self, other: &WorldShape, contacts: &mut Vec<ContactPoint>) {
+        match (*self, *other) {
+            (
+                WorldShape::Round {
+                    a: a0,
+                    b: b0,
+                    radius: r0,
+                },
+                WorldShape::Round {
+                    a: a1,
+                    b: b1,
+                    radius: r1,
+                },
+            ) => {
+                let (p0, p1) = closest_points_segment_segment(a0, b0, a1, b1);
+                if let Some(c) = sphere_sphere(p0, r0, p1, r1) {
+                    contacts.push(c);
+                }
+            }
+            (WorldShape::Round { a, b, radius }, WorldShape::Box { .. }) => {
+                round_box(a, b, radius, other, contacts);
+            }
+            (WorldShape::Box { .. }, WorldShape::Round { a, b, radius }) => {
+                let start = contacts.len();
+                round_box(a, b, radius, self, contacts);
+                flip(&mut contacts[start..]);
+            }
+            (WorldShape::Box { .. }, WorldShape::Box { .. }) => {
+                // corners of each box inside the other, edge on edge contacts are missed
+                for corner in self.corners() {
+                    if let Some(c) = other.box_point(corner) {
+                        contacts.push(c);
+                    }
+                }
+                let start = contacts.len();
+                for corner in other.corners() {
+                    if let Some(c) = self.box_point(corner) {
+                        contacts.push(c);
+                    }
+                }
+                flip(&mut contacts[start..]);
+            }
+        }
+    }
+
+    fn corners(&self) -> Vec<Vec3> {
+        match *self {
+            WorldShape::Round { .. } => vec![],
+            WorldShape::Box {
+                center,
+                rotation,
+                half_extents,
+            } => (0..8)
+                .map(|i| {
+                    let sign = Vec3::new(
+                        if i & 1 == 0 { -1.0 } else { 1.0 },
+                        if i & 2 == 0 { -1.0 } else { 1.0 },
+                        if i & 4 == 0 { -1.0 } else { 1.0 },
+                    );
+                    center + rotation * (half_extents * sign)
+                })
+                .collect(),
+        }
+    }
+
+    // closest point of a box to p, inside points stay where they are
+    fn box_closest_point(&self, p: Vec3) -> Vec3 {
+        match *self {
+            WorldShape::Round { .. } => p,
+            WorldShape::Box {
+                center,
+                rotation,
+                half_extents,
+            } => {
+                let local = rotation.inverse() * (p - center);
+                center + rotation * local.clamp(-half_extents, half_extents)
+            }
+        }
+    }
+
+    // contact for a point inside a box, pushed out through the nearest face
+    fn box_point(&self, p: Vec3) -> Option<ContactPoint> {
+        let WorldShape::Box {
+            center,
+            rotation,
+            half_extents,
+        } = *self
+        else {
+            return None;
+        };
+
+        let local = rotation.inverse() * (p - center);
+        let depth = half_extents - local.abs();
+        if depth.min_element() <= 0.0 {
+            return None;
+        }
+
+        let axis = if depth.x <= depth.y && depth.x <= depth.z {
+            Vec3::X
+        } else if depth.y <= depth.z {
+            Vec3::Y
+        } else {
+            Vec3::Z
+        };
+        let local_normal = axis * local.dot(axis).signum();
+        let surface = local + local_normal * depth.dot(axis);
+        Some(ContactPoint {
+            point_a: p,
+            point_b: center + rotation * surface,
+            normal: rotation * local_normal,
+        })
+    }
+}
+
+fn sphere_sphere(p0: Vec3, r0: f32, p1: Vec3, r1: f32) -> Option<ContactPoint> {
+    let d = p0 - p1;
+    let len = d.length();
+    if len >= r0 + r1 {
+        return None;
+    }
+    let normal = if len > 0.0 { d / len } else { Vec3::Y };
+    Some(ContactPoint {
+        point_a: p0 - normal * r0,
+        point_b: p1 + normal * r1,
+        normal,
+    })
+}
+
+// the ends of the segment and the segment point nearest to the box, found by projecting back
+// and forth between the two, are tested as spheres
+fn round_box(a: Vec3, b: Vec3, radius: f32, shape: &WorldShape, contacts: &mut Vec<ContactPoint>) {
+    let mut candidates = vec![a];
+    if a != b {
+        candidates.push(b);
+        let mut p = (a + b) * 0.5;
+        for _ in 0..4 {
+            let q = shape.box_closest_point(p);
+            p = closest_point_on_segment(q, a, b);
+        }
+        candidates.push(p);
+    }
+
+    for p in candidates {
+        let q = shape.box_closest_point(p);
+        let d = p - q;
+        let len = d.length();
+        if len >= radius {
+            continue;
+        }
+
+        if len > 0.0 {
+            let normal = d / len;
+            contacts.push(ContactPoint {
+                point_a: p - normal * radius,
+                point_b: q,
+                normal,
+            });
+        } else if let Some(c) = shape.box_point(p) {
+            // center inside the box
+            contacts.push(ContactPoint {
+                point_a: p - c.normal * radius,
+                ..c
+            });
+        }
+    }
+}
+
+fn flip(contacts: &mut [ContactPoint]) {
+    for c in contacts.iter_mut() {
+        *c = ContactPoint {
+            point_a: c.point_b,
+            point_b: c.point_a,
+            normal: -c.normal,
+        };
+    }
+}
+
+pub fn closest_point_on_segment(p: Vec3, a: Vec3, b: Vec3) -> Vec3 {
+    let ab = b - a;
+    let len2 = ab.length_squared();
+    if len2 == 0.0 {
+        return a;
+    }
+    let t = ((p - a).dot(ab) / len2).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+// closest points between segments a0-b0 and a1-b1
+fn closest_points_segment_segment(a0: Vec3, b0: Vec3, a1: Vec3, b1: Vec3) -> (Vec3, Vec3) {
+    let d0 = b0 - a0;
+    let d1 = b1 - a1;
+    let r = a0 - a1;
+    let l0 = d0.length_squared();
+    let l1 = d1.length_squared();
+
+    if l0 == 0.0 && l1 == 0.0 {
+        return (a0, a1);
+    }
+    if l0 == 0.0 {
+        return (a0, closest_point_on_segment(a0, a1, b1));
+    }
+    if l1 == 0.0 {
+        return (closest_point_on_segment(a1, a0, b0), a1);
+    }
+
+    let b = d0.dot(d1);
+    let c = d0.dot(r);
+    let f = d1.dot(r);
+    let denom = l0 * l1 - b * b;
+    // parallel segments pick any point
+    let mut s = if denom != 0.0 {
+        ((b * f - c * l1) / denom).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let mut t = (b * s + f) / l1;
+    if t < 0.0 {
+        t = 0.0;
+        s = (-c / l0).clamp(0.0, 1.0);
+    } else if t > 1.0 {
+        t = 1.0;
+        s = ((b - c) / l0).clamp(0.0, 1.0);
+    }
+    (a0 + d0 * s, a1 + d1 * t)
+}
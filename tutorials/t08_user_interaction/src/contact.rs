@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{prelude::ReflectInspectorOptions, InspectorOptions};
+
+// how a body bounces and grips, missing on a body means the defaults
+#[derive(Reflect, FromReflect, Component, InspectorOptions, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component, InspectorOptions)]
+pub struct ContactMaterial {
+    // share of the approach speed a bounce gives back
+    #[inspector(min = 0., max = 1.)]
+    pub restitution: f32,
+    // coulomb coefficients, sticking holds up to static times the normal force and sliding is
+    // slowed by dynamic times it
+    #[inspector(min = 0., max = 2.)]
+    pub static_friction: f32,
+    #[inspector(min = 0., max = 2.)]
+    pub dynamic_friction: f32,
+    // torque against rolling as a fraction of normal force times the radius
+    #[inspector(min = 0., max = 0.5)]
+    pub rolling_resistance: f32,
+}
+
+impl Default for ContactMaterial {
+    fn default() -> Self {
+        Self {
+            restitution: 0.7,
+            static_friction: 0.6,
+            dynamic_friction: 0.4,
+            rolling_resistance: 0.02,
+        }
+    }
+}
+
+impl ContactMaterial {
+    // the average for two bodies touching
+    pub fn combine(&self, other: &Self) -> Self {
+        Self {
+            restitution: (self.restitution + other.restitution) * 0.5,
+            static_friction: (self.static_friction + other.static_friction) * 0.5,
+            dynamic_friction: (self.dynamic_friction + other.dynamic_friction) * 0.5,
+            rolling_resistance: (self.rolling_resistance + other.rolling_resistance) * 0.5,
+        }
+    }
+}
+
+// a solid ball against the static half space above the plane normal . x = offset, pushes it out and
+// changes its velocity and angular velocity by the contact impulses, rest_speed is the normal
+// speed below which it doesn't bounce anymore
+pub fn ball_plane_contact(
+    pos: &mut Vec3,
+    vel: &mut Vec3,
+    omega: &mut Vec3,
+    radius: f32,
+    (normal, offset): (Vec3, f32),
+    material: &ContactMaterial,
+    rest_speed: f32,
+) {
+    let depth = offset + radius - normal.dot(*pos);
+    if depth <= 0.0 {
+        return;
+    }
+    *pos += normal * depth;
+
+    // velocity of the touching point
+    let r = -normal * radius;
+    let v = *vel + omega.cross(r);
+    let vn = v.dot(normal);
+    if vn >= 0.0 {
+        return;
+    }
+
+    // impulses per unit mass, the mass cancels against the static world
+    let restitution = if -vn > rest_speed {
+        material.restitution
+    } else {
+        0.0
+    };
+    let jn = -(1.0 + restitution) * vn;
+    *vel += normal * jn;
+
+    // a solid ball has I = 2/5 m r², so a tangential impulse at the surface changes the point
+    // velocity by 1 + r² m / I = 3.5 times its linear share
+    let vt = v - normal * vn;
+    let speed = vt.length();
+    if speed > 0.0 {
+        let stick = speed / 3.5;
+        let jt = if stick <= material.static_friction * jn {
+            stick
+        } else {
+            (material.dynamic_friction * jn).min(stick)
+        };
+        let t = vt / speed;
+        *vel -= t * jt;
+        *omega += r.cross(-t * jt) * (2.5 / (radius * radius));
+    }
+
+    // rolling resistance only acts on rolling, not on spinning about the normal
+    let rolling = *omega - normal * omega.dot(normal);
+    let spin = rolling.length();
+    if spin > 0.0 {
+        let dw = (material.rolling_resistance * jn * 2.5 / radius).min(spin);
+        *omega -= rolling / spin * dw;
+    }
+}
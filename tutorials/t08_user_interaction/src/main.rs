@@ -1,13 +1,23 @@
 mod camera_grabber;
+mod collider;
 mod components;
+mod contact;
+mod energy;
 mod intersect;
+mod joint;
 mod reset;
 mod resources;
+mod rigid_body;
 
 use camera_grabber::*;
+use collider::*;
 use components::*;
+use contact::*;
+use energy::*;
+use joint::*;
 use reset::*;
 use resources::*;
+use rigid_body::*;
 
 use bevy::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
@@ -19,13 +29,18 @@ fn main() {
         .add_plugin(WorldInspectorPlugin::default())
         .add_plugin(ResetPlugin)
         .add_plugin(CameraGrabberPlugin)
+        .add_plugin(RigidBodyPlugin)
+        .add_plugin(EnergyDiagnosticsPlugin)
         .init_resource::<Config>()
         .add_startup_system(setup)
         .add_system(simulate)
         .add_system(spawn_ball.in_schedule(OnEnter(ResetState::Playing)))
+        .add_system(spawn_rigid_bodies.in_schedule(OnEnter(ResetState::Playing)))
+        .add_system(spawn_joints.in_schedule(OnEnter(ResetState::Playing)))
         .register_type::<Config>()
         .register_type::<Ball>()
         .register_type::<Velocity>()
+        .register_type::<ContactMaterial>()
         .run();
 }
 
@@ -105,65 +120,356 @@ fn spawn_ball(
             ..default()
         },
         Velocity(Vec3::new(-3., 8., -6.) * config.scale),
+        AngularVelocity::default(),
+        ContactMaterial::default(),
         Ball(size),
         Name::new("Ball"),
     ));
 }
 
+// a pile of crates with a sphere and a capsule dropped on top, tilted so they tumble
+fn spawn_rigid_bodies(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let density = 500.0;
+    let crate_material = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.6, 0.4, 0.2),
+        perceptual_roughness: 0.9,
+        ..default()
+    });
+    let crate_mesh = meshes.add(Mesh::from(shape::Box::new(1.0, 1.0, 1.0)));
+    let crate_collider = Collider::Box {
+        half_extents: Vec3::splat(0.5),
+    };
+
+    for i in 0..6 {
+        let transform = Transform::from_xyz(4.0 + (i % 2) as f32 * 0.3, 1.0 + 1.5 * i as f32, 0.0)
+            .with_rotation(Quat::from_euler(
+                EulerRot::XYZ,
+                0.3 * i as f32,
+                0.5 * i as f32,
+                0.2,
+            ));
+        commands.spawn((
+            PbrBundle {
+                mesh: crate_mesh.clone(),
+                material: crate_material.clone(),
+                transform,
+                ..default()
+            },
+            RigidBody::new(&crate_collider, density),
+            crate_collider,
+            Velocity::default(),
+            AngularVelocity::default(),
+            // rough wood, barely bounces
+            ContactMaterial {
+                restitution: 0.1,
+                static_friction: 0.8,
+                dynamic_friction: 0.6,
+                ..default()
+            },
+            Name::new(format!("Crate {i}")),
+        ));
+    }
+
+    let radius = 0.6;
+    let sphere = Collider::Sphere { radius };
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::UVSphere {
+                radius,
+                sectors: 32,
+                stacks: 16,
+            })),
+            material: materials.add(StandardMaterial {
+                base_color: Color::BLUE,
+                ..default()
+            }),
+            transform: Transform::from_xyz(4.2, 11.0, 0.1),
+            ..default()
+        },
+        RigidBody::new(&sphere, density),
+        sphere,
+        Velocity::default(),
+        AngularVelocity::default(),
+        // rubber
+        ContactMaterial {
+            restitution: 0.8,
+            static_friction: 1.0,
+            dynamic_friction: 0.8,
+            rolling_resistance: 0.01,
+        },
+        Name::new("Sphere"),
+    ));
+
+    let (radius, half_height) = (0.3, 0.7);
+    let capsule = Collider::Capsule {
+        radius,
+        half_height,
+    };
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Capsule {
+                radius,
+                depth: 2.0 * half_height,
+                ..default()
+            })),
+            material: materials.add(StandardMaterial {
+                base_color: Color::YELLOW,
+                ..default()
+            }),
+            transform: Transform::from_xyz(3.8, 13.0, -0.2)
+                .with_rotation(Quat::from_rotation_z(1.0)),
+            ..default()
+        },
+        RigidBody::new(&capsule, density),
+        capsule,
+        Velocity::default(),
+        AngularVelocity::default(),
+        Name::new("Capsule"),
+    ));
+}
+
+fn spawn_body(
+    commands: &mut Commands,
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    transform: Transform,
+    collider: Collider,
+    name: &str,
+) -> Entity {
+    commands
+        .spawn((
+            PbrBundle {
+                mesh,
+                material,
+                transform,
+                ..default()
+            },
+            RigidBody::new(&collider, 500.0),
+            collider,
+            Velocity::default(),
+            AngularVelocity::default(),
+            Name::new(name.to_string()),
+        ))
+        .id()
+}
+
+// one of each joint kind, all but the welded pair hang from the world
+fn spawn_joints(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let material = materials.add(StandardMaterial {
+        base_color: Color::SILVER,
+        metallic: 0.5,
+        ..default()
+    });
+
+    // plank on a hinge, starts level and swings down until the limit stops it
+    let half_extents = Vec3::new(1.0, 0.1, 0.2);
+    let trans = Transform::from_xyz(-3.0, 6.0, 0.0);
+    let plank = spawn_body(
+        &mut commands,
+        meshes.add(Mesh::from(shape::Box::new(2.0, 0.2, 0.4))),
+        material.clone(),
+        trans,
+        Collider::Box { half_extents },
+        "Hinged Plank",
+    );
+    commands.spawn((
+        Joint::new(
+            JointKind::Hinge {
+                limits: Some(Vec2::new(-1.2, 1.2)),
+            },
+            None,
+            (plank, &trans),
+            Vec3::new(-4.0, 6.0, 0.0),
+            Vec3::Z,
+        ),
+        Name::new("Hinge"),
+    ));
+
+    // chain of capsules on ball joints, laid out level
+    let (radius, half_height) = (0.15, 0.35);
+    let link_length = 2.0 * (radius + half_height);
+    let capsule_mesh = meshes.add(Mesh::from(shape::Capsule {
+        radius,
+        depth: 2.0 * half_height,
+        ..default()
+    }));
+    let start = Vec3::new(-8.0, 9.0, -3.0);
+    let mut prev: Option<(Entity, Transform)> = None;
+    for i in 0..6 {
+        let trans = Transform::from_translation(start + Vec3::X * link_length * (i as f32 + 0.5))
+            .with_rotation(Quat::from_rotation_z(FRAC_PI_2));
+        let link = spawn_body(
+            &mut commands,
+            capsule_mesh.clone(),
+            material.clone(),
+            trans,
+            Collider::Capsule {
+                radius,
+                half_height,
+            },
+            &format!("Chain Link {i}"),
+        );
+        commands.spawn((
+            Joint::new(
+                JointKind::Spherical {
+                    swing: Some(0.8),
+                    twist: Some(Vec2::new(-0.3, 0.3)),
+                },
+                prev.as_ref().map(|(e, t)| (*e, t)),
+                (link, &trans),
+                start + Vec3::X * link_length * i as f32,
+                Vec3::X,
+            ),
+            Name::new(format!("Ball Joint {i}")),
+        ));
+        prev = Some((link, trans));
+    }
+
+    // block sliding down a tilted rail until it hits the end
+    let axis = Vec3::new(1.0, 0.5, 0.0).normalize();
+    let anchor = Vec3::new(0.0, 4.0, -5.0);
+    let trans = Transform::from_translation(anchor);
+    let block = spawn_body(
+        &mut commands,
+        meshes.add(Mesh::from(shape::Box::new(0.6, 0.6, 0.6))),
+        material.clone(),
+        trans,
+        Collider::Box {
+            half_extents: Vec3::splat(0.3),
+        },
+        "Slider",
+    );
+    commands.spawn((
+        Joint::new(
+            JointKind::Prismatic {
+                limits: Some(Vec2::new(-2.0, 2.0)),
+            },
+            None,
+            (block, &trans),
+            anchor,
+            axis,
+        ),
+        Name::new("Prismatic"),
+    ));
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Box::new(4.6, 0.05, 0.05))),
+            material: material.clone(),
+            transform: Transform::from_translation(anchor - Vec3::Z * 0.35)
+                .with_rotation(Quat::from_rotation_arc(Vec3::X, axis)),
+            ..default()
+        },
+        Name::new("Rail"),
+    ));
+
+    // two boxes welded into an L that tumbles as one
+    let half_extents = Vec3::new(0.8, 0.2, 0.2);
+    let trans_a = Transform::from_xyz(-2.0, 8.0, 3.0).with_rotation(Quat::from_rotation_x(0.4));
+    let trans_b = trans_a
+        * Transform::from_xyz(-0.6, 0.6, 0.0).with_rotation(Quat::from_rotation_z(FRAC_PI_2));
+    let bar_mesh = meshes.add(Mesh::from(shape::Box::new(1.6, 0.4, 0.4)));
+    let bar_a = spawn_body(
+        &mut commands,
+        bar_mesh.clone(),
+        material.clone(),
+        trans_a,
+        Collider::Box { half_extents },
+        "Welded A",
+    );
+    let bar_b = spawn_body(
+        &mut commands,
+        bar_mesh,
+        material,
+        trans_b,
+        Collider::Box { half_extents },
+        "Welded B",
+    );
+    commands.spawn((
+        Joint::new(
+            JointKind::Fixed,
+            Some((bar_a, &trans_a)),
+            (bar_b, &trans_b),
+            trans_a.transform_point(Vec3::new(-0.6, 0.0, 0.0)),
+            Vec3::X,
+        ),
+        Name::new("Weld"),
+    ));
+}
+
 fn simulate(
-    mut query: Query<(Entity, &mut Transform, &mut Velocity, &Ball)>,
+    mut query: Query<(
+        Entity,
+        &mut Transform,
+        &mut Velocity,
+        &mut AngularVelocity,
+        &Ball,
+        &ContactMaterial,
+    )>,
     time: Res<Time>,
     config: Res<Config>,
     grabbed: Res<Grabbed>,
+    mut step_energy: ResMut<StepEnergy>,
 ) {
     let sdt = time.delta_seconds() / config.sub_steps as f32;
 
-    for (e, mut trans, mut velocity, ball) in query.iter_mut() {
+    // ground and walls, normals point into the box
+    let walls = [
+        (Vec3::Y, 0.0),
+        (Vec3::NEG_Y, -config.half_size),
+        (Vec3::X, -config.half_size),
+        (Vec3::NEG_X, -config.half_size),
+        (Vec3::Z, -config.half_size),
+        (Vec3::NEG_Z, -config.half_size),
+    ];
+    // slower than gravity adds in a couple of steps is resting, not bouncing
+    let rest_speed = 2.0 * config.gravity.length() * sdt * config.scale;
+
+    for (e, mut trans, mut velocity, mut omega, ball, material) in query.iter_mut() {
         if grabbed.entity == Some(e) {
             continue;
         }
 
+        // the ball has no mass of its own, it counts as a solid sphere as dense as the crates
+        let (mass, inertia) = Collider::Sphere { radius: ball.0 }.mass_properties(500.0);
+
         // sub steps
-        for _ in 0..config.sub_steps {
+        for step in 0..config.sub_steps as usize {
             velocity.0 += config.gravity * sdt * config.scale;
-            trans.translation += velocity.0 * sdt * config.scale
-        }
+            trans.translation += velocity.0 * sdt * config.scale;
+            trans.rotation = Quat::from_scaled_axis(omega.0 * sdt * config.scale) * trans.rotation;
 
-        // keep ball in bounds
-        let limit = Vec3::new(
-            config.half_size - ball.0,
-            config.half_size - ball.0,
-            config.half_size - ball.0,
-        );
-        if trans.translation.x < -limit.x {
-            trans.translation.x = -limit.x;
-            velocity.0.x = -velocity.0.x;
-        }
-
-        if trans.translation.x > limit.x {
-            trans.translation.x = limit.x;
-            velocity.0.x = -velocity.0.x;
-        }
-
-        if trans.translation.y < ball.0 {
-            // limit to ground + ball radius
-            trans.translation.y = ball.0;
-            velocity.0.y = -velocity.0.y;
-        }
-
-        if trans.translation.y > limit.y {
-            trans.translation.y = limit.y;
-            velocity.0.y = -velocity.0.y;
-        }
-
-        if trans.translation.z < -limit.z {
-            trans.translation.z = -limit.z;
-            velocity.0.z = -velocity.0.z;
-        }
+            for wall in walls {
+                ball_plane_contact(
+                    &mut trans.translation,
+                    &mut velocity.0,
+                    &mut omega.0,
+                    ball.0,
+                    wall,
+                    material,
+                    rest_speed,
+                );
+            }
 
-        if trans.translation.z > limit.z {
-            trans.translation.z = limit.z;
-            velocity.0.z = -velocity.0.z;
+            step_energy.add(
+                step,
+                Energy::rigid_body(
+                    mass,
+                    inertia,
+                    trans.translation,
+                    trans.rotation,
+                    velocity.0,
+                    omega.0,
+                    config.gravity,
+                ),
+            );
         }
     }
 }
@@ -0,0 +1,588 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    camera_grabber::Grabbed,
+    collider::{Collider, ContactPoint},
+    components::Velocity,
+    contact::ContactMaterial,
+    energy::{Energy, StepEnergy},
+    joint::{Joint, JointKind},
+    resources::Config,
+};
+
+// xpbd rigid bodies after Müller et al. "Detailed Rigid Body Simulation with Extended Position
+// Based Dynamics", the pose lives in the Transform and the linear velocity in Velocity
+pub struct RigidBodyPlugin;
+
+impl Plugin for RigidBodyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(simulate_rigid_bodies)
+            .register_type::<RigidBody>()
+            .register_type::<AngularVelocity>()
+            .register_type::<Collider>()
+            .register_type::<ContactMaterial>()
+            .register_type::<Joint>()
+            .register_type::<JointKind>();
+    }
+}
+
+// zero inverse mass and inertia make a body static
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct RigidBody {
+    pub inv_mass: f32,
+    // diagonal of the inverse inertia tensor in the local frame
+    pub inv_inertia: Vec3,
+}
+
+impl Default for RigidBody {
+    fn default() -> Self {
+        Self::fixed()
+    }
+}
+
+impl RigidBody {
+    pub fn new(collider: &Collider, density: f32) -> Self {
+        let (mass, inertia) = collider.mass_properties(density);
+        Self {
+            inv_mass: 1.0 / mass,
+            inv_inertia: inertia.recip(),
+        }
+    }
+
+    pub fn fixed() -> Self {
+        Self {
+            inv_mass: 0.0,
+            inv_inertia: Vec3::ZERO,
+        }
+    }
+}
+
+// world space, rad / s
+#[derive(Reflect, Component, Default, Deref, DerefMut)]
+#[reflect(Component)]
+pub struct AngularVelocity(pub Vec3);
+
+// solver copy of a body, written back to the components after all substeps
+pub struct Body {
+    pub pos: Vec3,
+    pub rot: Quat,
+    prev_pos: Vec3,
+    prev_rot: Quat,
+    vel: Vec3,
+    omega: Vec3,
+    inv_mass: f32,
+    inv_inertia: Vec3,
+    collider: Collider,
+    material: ContactMaterial,
+}
+
+impl Body {
+    fn integrate(&mut self, dt: f32, gravity: Vec3) {
+        self.prev_pos = self.pos;
+        self.prev_rot = self.rot;
+        if self.inv_mass == 0.0 {
+            return;
+        }
+
+        self.vel += gravity * dt;
+        self.pos += self.vel * dt;
+
+        // gyroscopic term, lets long bodies tumble instead of spinning about any axis
+        let omega = self.rot.inverse() * self.omega;
+        let inertia = self.inv_inertia.recip();
+        let torque = -omega.cross(inertia * omega);
+        self.omega += self.rot * (self.inv_inertia * torque) * dt;
+
+        self.rot = rotate(self.rot, self.omega * dt);
+    }
+
+    fn update_velocities(&mut self, dt: f32) {
+        if self.inv_mass == 0.0 {
+            return;
+        }
+
+        self.vel = (self.pos - self.prev_pos) / dt;
+        let dq = self.rot * self.prev_rot.inverse();
+        self.omega = Vec3::new(dq.x, dq.y, dq.z) * (2.0 / dt);
+        if dq.w < 0.0 {
+            self.omega = -self.omega;
+        }
+    }
+
+    // static and grabbed bodies have no energy to count
+    fn energy(&self, gravity: Vec3) -> Energy {
+        if self.inv_mass == 0.0 {
+            return Energy::default();
+        }
+        Energy::rigid_body(
+            1.0 / self.inv_mass,
+            self.inv_inertia.recip(),
+            self.pos,
+            self.rot,
+            self.vel,
+            self.omega,
+            gravity,
+        )
+    }
+
+    // inverse mass seen by a correction along normal at the world offset r from the center
+    fn generalized_inv_mass(&self, r: Vec3, normal: Vec3) -> f32 {
+        self.inv_mass + self.angular_inv_mass(r.cross(normal))
+    }
+
+    // inverse inertia about the world axis n
+    fn angular_inv_mass(&self, n: Vec3) -> f32 {
+        let n = self.rot.inverse() * n;
+        n.dot(self.inv_inertia * n)
+    }
+
+    // applies the positional impulse p at the world offset r from the center
+    fn apply_impulse(&mut self, p: Vec3, r: Vec3) {
+        self.pos += p * self.inv_mass;
+        self.apply_angular_impulse(r.cross(p));
+    }
+
+    fn apply_angular_impulse(&mut self, p: Vec3) {
+        let dw = self.rot * (self.inv_inertia * (self.rot.inverse() * p));
+        self.rot = rotate(self.rot, dw);
+    }
+
+    // velocity of the point at the world offset r from the center
+    fn point_velocity(&self, r: Vec3) -> Vec3 {
+        self.vel + self.omega.cross(r)
+    }
+
+    // the velocity level versions of the two above
+    fn apply_velocity_impulse(&mut self, p: Vec3, r: Vec3) {
+        self.vel += p * self.inv_mass;
+        self.apply_angular_velocity_impulse(r.cross(p));
+    }
+
+    fn apply_angular_velocity_impulse(&mut self, p: Vec3) {
+        self.omega += self.rot * (self.inv_inertia * (self.rot.inverse() * p));
+    }
+}
+
+// inverse mass of the pair for a correction along n at the world offsets r_a and r_b
+fn pair_inv_mass(
+    bodies: &[Body],
+    a: Option<usize>,
+    b: Option<usize>,
+    r_a: Vec3,
+    r_b: Vec3,
+    n: Vec3,
+) -> f32 {
+    a.map_or(0.0, |a| bodies[a].generalized_inv_mass(r_a, n))
+        + b.map_or(0.0, |b| bodies[b].generalized_inv_mass(r_b, n))
+}
+
+// moves the world offsets r_a and r_b of the bodies together, corr is where the point of b is
+// minus where the point of a is, a body of None is the static world, alpha = compliance / dt²,
+// returns the lagrange multiplier, the positional impulse applied
+pub fn solve_positional(
+    bodies: &mut [Body],
+    a: Option<usize>,
+    b: Option<usize>,
+    r_a: Vec3,
+    r_b: Vec3,
+    corr: Vec3,
+    alpha: f32,
+) -> f32 {
+    let c = corr.length();
+    if c == 0.0 {
+        return 0.0;
+    }
+    let n = corr / c;
+    let w = pair_inv_mass(bodies, a, b, r_a, r_b, n) + alpha;
+    if w == 0.0 {
+        return 0.0;
+    }
+
+    let lambda = c / w;
+    let p = n * lambda;
+    if let Some(a) = a {
+        bodies[a].apply_impulse(p, r_a);
+    }
+    if let Some(b) = b {
+        bodies[b].apply_impulse(-p, r_b);
+    }
+    lambda
+}
+
+// rotates a by the rotation vector corr and b by its opposite, split by their inverse inertia
+pub fn solve_angular(
+    bodies: &mut [Body],
+    a: Option<usize>,
+    b: Option<usize>,
+    corr: Vec3,
+    alpha: f32,
+) {
+    let theta = corr.length();
+    if theta == 0.0 {
+        return;
+    }
+    let n = corr / theta;
+    let w_a = a.map_or(0.0, |a| bodies[a].angular_inv_mass(n));
+    let w_b = b.map_or(0.0, |b| bodies[b].angular_inv_mass(n));
+    let w = w_a + w_b + alpha;
+    if w == 0.0 {
+        return;
+    }
+
+    let p = n * (theta / w);
+    if let Some(a) = a {
+        bodies[a].apply_angular_impulse(p);
+    }
+    if let Some(b) = b {
+        bodies[b].apply_angular_impulse(-p);
+    }
+}
+
+// q + 0.5 * [w, 0] * q, normalized
+fn rotate(q: Quat, w: Vec3) -> Quat {
+    let dq = Quat::from_xyzw(w.x, w.y, w.z, 0.0) * q;
+    (q + dq * 0.5).normalize()
+}
+
+// a contact in the local frames of the bodies, b is None for the static world
+struct Contact {
+    a: usize,
+    b: Option<usize>,
+    r_a: Vec3,
+    r_b: Vec3,
+    normal: Vec3,
+    material: ContactMaterial,
+    // relative normal velocity before the solve, what restitution reflects
+    normal_speed: f32,
+    // normal impulse of this substep, zero if the contact didn't push
+    lambda_n: f32,
+}
+
+impl Contact {
+    fn new(bodies: &[Body], a: usize, b: Option<usize>, c: &ContactPoint) -> Self {
+        let body_a = &bodies[a];
+        let (r_b, material, v_b) = match b {
+            Some(b) => {
+                let body_b = &bodies[b];
+                (
+                    body_b.rot.inverse() * (c.point_b - body_b.pos),
+                    body_a.material.combine(&body_b.material),
+                    body_b.point_velocity(c.point_b - body_b.pos),
+                )
+            }
+            None => (c.point_b, body_a.material, Vec3::ZERO),
+        };
+        let v_a = body_a.point_velocity(c.point_a - body_a.pos);
+        Self {
+            a,
+            b,
+            r_a: body_a.rot.inverse() * (c.point_a - body_a.pos),
+            r_b,
+            normal: c.normal,
+            material,
+            normal_speed: (v_a - v_b).dot(c.normal),
+            lambda_n: 0.0,
+        }
+    }
+
+    // world offsets and points, the world's point is its own offset
+    fn world_points(&self, bodies: &[Body]) -> (Vec3, Vec3, Vec3, Vec3) {
+        let body_a = &bodies[self.a];
+        let r_a = body_a.rot * self.r_a;
+        match self.b {
+            Some(b) => {
+                let r_b = bodies[b].rot * self.r_b;
+                (r_a, body_a.pos + r_a, r_b, bodies[b].pos + r_b)
+            }
+            None => (r_a, body_a.pos + r_a, Vec3::ZERO, self.r_b),
+        }
+    }
+}
+
+// jointed pairs don't collide, their shapes usually overlap at the anchor
+fn find_contacts(
+    bodies: &[Body],
+    half_size: f32,
+    jointed: &HashSet<(usize, usize)>,
+) -> Vec<Contact> {
+    let mut contacts = vec![];
+    let mut points = vec![];
+
+    // ground and walls
+    let walls = [
+        (Vec3::Y, 0.0),
+        (Vec3::X, -half_size),
+        (Vec3::NEG_X, -half_size),
+        (Vec3::Z, -half_size),
+        (Vec3::NEG_Z, -half_size),
+    ];
+    let shapes = bodies
+        .iter()
+        .map(|b| {
+            b.collider
+                .to_world(&Transform::from_translation(b.pos).with_rotation(b.rot))
+        })
+        .collect::<Vec<_>>();
+
+    for (i, shape) in shapes.iter().enumerate() {
+        if bodies[i].inv_mass == 0.0 {
+            continue;
+        }
+        points.clear();
+        for (normal, offset) in walls {
+            shape.plane_contacts(normal, offset, &mut points);
+        }
+        contacts.extend(points.iter().map(|c| Contact::new(bodies, i, None, c)));
+    }
+
+    for i in 0..bodies.len() {
+        for j in i + 1..bodies.len() {
+            if bodies[i].inv_mass == 0.0 && bodies[j].inv_mass == 0.0 || jointed.contains(&(i, j)) {
+                continue;
+            }
+            let reach = bodies[i].collider.bounding_radius() + bodies[j].collider.bounding_radius();
+            if bodies[i].pos.distance_squared(bodies[j].pos) > reach * reach {
+                continue;
+            }
+            points.clear();
+            shapes[i].contacts(&shapes[j], &mut points);
+            contacts.extend(points.iter().map(|c| Contact::new(bodies, i, Some(j), c)));
+        }
+    }
+
+    contacts
+}
+
+// non penetration, infinitely stiff so no compliance
+fn solve_contact(bodies: &mut [Body], contact: &mut Contact) {
+    let (r_a, p_a, r_b, p_b) = contact.world_points(bodies);
+    let depth = (p_b - p_a).dot(contact.normal);
+    if depth <= 0.0 {
+        return;
+    }
+    contact.lambda_n = solve_positional(
+        bodies,
+        Some(contact.a),
+        contact.b,
+        r_a,
+        r_b,
+        contact.normal * depth,
+        0.0,
+    );
+}
+
+// holds the contact points where they were at the start of the substep as long as that takes
+// less than the static coefficient times the normal impulse, run after all contacts pushed so
+// the sideways drift of one corner being lifted by another is caught too
+fn solve_static_friction(bodies: &mut [Body], contact: &Contact) {
+    if contact.lambda_n == 0.0 {
+        return;
+    }
+    let (r_a, p_a, r_b, p_b) = contact.world_points(bodies);
+    let body_a = &bodies[contact.a];
+    let prev_a = body_a.prev_pos + body_a.prev_rot * contact.r_a;
+    let prev_b = match contact.b {
+        Some(b) => bodies[b].prev_pos + bodies[b].prev_rot * contact.r_b,
+        None => contact.r_b,
+    };
+    let slip = (p_a - prev_a) - (p_b - prev_b);
+    let slip = slip - contact.normal * slip.dot(contact.normal);
+    let len = slip.length();
+    if len == 0.0 {
+        return;
+    }
+    let w = pair_inv_mass(bodies, Some(contact.a), contact.b, r_a, r_b, slip / len);
+    if w > 0.0 && len / w < contact.material.static_friction * contact.lambda_n {
+        solve_positional(bodies, Some(contact.a), contact.b, r_a, r_b, -slip, 0.0);
+    }
+}
+
+fn apply_velocity_impulse(
+    bodies: &mut [Body],
+    a: Option<usize>,
+    b: Option<usize>,
+    p: Vec3,
+    r_a: Vec3,
+    r_b: Vec3,
+) {
+    if let Some(a) = a {
+        bodies[a].apply_velocity_impulse(p, r_a);
+    }
+    if let Some(b) = b {
+        bodies[b].apply_velocity_impulse(-p, r_b);
+    }
+}
+
+// restitution, dynamic friction and rolling resistance on the velocities after they were
+// derived from the positions, rest_speed is the normal speed below which nothing bounces
+fn solve_contact_velocities(bodies: &mut [Body], contact: &Contact, dt: f32, rest_speed: f32) {
+    if contact.lambda_n == 0.0 {
+        return;
+    }
+    let (a, b, n) = (Some(contact.a), contact.b, contact.normal);
+    let (r_a, _, r_b, _) = contact.world_points(bodies);
+    let v_b = b.map_or(Vec3::ZERO, |b| bodies[b].point_velocity(r_b));
+    let v = bodies[contact.a].point_velocity(r_a) - v_b;
+    let vn = v.dot(n);
+    let vt = v - n * vn;
+    // normal force times dt
+    let impulse_n = contact.lambda_n / dt;
+
+    // friction can't take out more than the dynamic coefficient times the normal impulse
+    let speed = vt.length();
+    if speed > 0.0 {
+        let t = vt / speed;
+        let w = pair_inv_mass(bodies, a, b, r_a, r_b, t);
+        if w > 0.0 {
+            let p = -t * (contact.material.dynamic_friction * impulse_n).min(speed / w);
+            apply_velocity_impulse(bodies, a, b, p, r_a, r_b);
+        }
+    }
+
+    let restitution = if -contact.normal_speed > rest_speed {
+        contact.material.restitution
+    } else {
+        0.0
+    };
+    let dv = -vn + (-restitution * contact.normal_speed).max(0.0);
+    let w = pair_inv_mass(bodies, a, b, r_a, r_b, n);
+    if w > 0.0 {
+        apply_velocity_impulse(bodies, a, b, n * (dv / w), r_a, r_b);
+    }
+
+    // torque of normal force times lever arm against the relative rotation
+    let omega_b = b.map_or(Vec3::ZERO, |b| bodies[b].omega);
+    let omega = bodies[contact.a].omega - omega_b;
+    let spin = omega.length();
+    if spin > 0.0 {
+        let axis = omega / spin;
+        let w = bodies[contact.a].angular_inv_mass(axis)
+            + b.map_or(0.0, |b| bodies[b].angular_inv_mass(axis));
+        if w > 0.0 {
+            let p = axis
+                * (contact.material.rolling_resistance * impulse_n * r_a.length()).min(spin / w);
+            bodies[contact.a].apply_angular_velocity_impulse(-p);
+            if let Some(b) = b {
+                bodies[b].apply_angular_velocity_impulse(p);
+            }
+        }
+    }
+}
+
+type BodyData = (
+    Entity,
+    &'static mut Transform,
+    &'static mut Velocity,
+    &'static mut AngularVelocity,
+    &'static RigidBody,
+    &'static Collider,
+    Option<&'static ContactMaterial>,
+);
+
+pub fn simulate_rigid_bodies(
+    mut query: Query<BodyData>,
+    joint_query: Query<&Joint>,
+    time: Res<Time>,
+    config: Res<Config>,
+    grabbed: Res<Grabbed>,
+    mut step_energy: ResMut<StepEnergy>,
+) {
+    let sdt = time.delta_seconds() / config.sub_steps as f32;
+
+    // zero time blows up on startup
+    if sdt == 0. {
+        return;
+    }
+
+    let mut bodies = query
+        .iter()
+        .map(|(e, trans, vel, omega, rb, collider, material)| {
+            // grabbed bodies are moved by the mouse and push everything else around
+            let dynamic = grabbed.entity != Some(e);
+            Body {
+                pos: trans.translation,
+                rot: trans.rotation,
+                prev_pos: trans.translation,
+                prev_rot: trans.rotation,
+                vel: vel.0,
+                omega: omega.0,
+                inv_mass: if dynamic { rb.inv_mass } else { 0.0 },
+                inv_inertia: if dynamic { rb.inv_inertia } else { Vec3::ZERO },
+                collider: *collider,
+                material: material.copied().unwrap_or_default(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // joints to solver indices, joints to despawned bodies are skipped
+    let index = query
+        .iter()
+        .enumerate()
+        .map(|(i, (e, ..))| (e, i))
+        .collect::<HashMap<_, _>>();
+    let joints = joint_query
+        .iter()
+        .filter_map(|joint| {
+            let b = *index.get(&joint.body_b)?;
+            let a = match joint.body_a {
+                Some(a) => Some(*index.get(&a)?),
+                None => None,
+            };
+            Some((joint, a, b))
+        })
+        .collect::<Vec<_>>();
+    let jointed = joints
+        .iter()
+        .filter_map(|&(_, a, b)| a.map(|a| (a.min(b), a.max(b))))
+        .collect::<HashSet<_>>();
+
+    // slower than gravity adds in a couple of substeps is resting, not bouncing
+    let rest_speed = 2.0 * config.gravity.length() * sdt;
+
+    for step in 0..config.sub_steps as usize {
+        for body in bodies.iter_mut() {
+            body.integrate(sdt, config.gravity);
+        }
+
+        for &(joint, a, b) in joints.iter() {
+            joint.solve(&mut bodies, a, b, sdt);
+        }
+
+        let mut contacts = find_contacts(&bodies, config.half_size, &jointed);
+        for contact in contacts.iter_mut() {
+            solve_contact(&mut bodies, contact);
+        }
+        for contact in contacts.iter() {
+            solve_static_friction(&mut bodies, contact);
+        }
+
+        for body in bodies.iter_mut() {
+            body.update_velocities(sdt);
+        }
+
+        for contact in contacts.iter() {
+            solve_contact_velocities(&mut bodies, contact, sdt, rest_speed);
+        }
+
+        let energy = bodies.iter().map(|body| body.energy(config.gravity)).sum();
+        step_energy.add(step, energy);
+    }
+
+    for ((e, mut trans, mut vel, mut omega, rb, ..), body) in query.iter_mut().zip(bodies.iter()) {
+        if grabbed.entity == Some(e) {
+            omega.0 = Vec3::ZERO;
+            continue;
+        }
+        if rb.inv_mass == 0.0 {
+            continue;
+        }
+        trans.translation = body.pos;
+        trans.rotation = body.rot;
+        vel.0 = body.vel;
+        omega.0 = body.omega;
+    }
+}
@@ -5,6 +5,7 @@ use bevy::{
 use bevy_inspector_egui::prelude::*;
 
 use crate::{
+    energy::Energy,
     intersect::{ray_sphere_intersect, ray_triangle_intersect},
     models::TetMesh,
 };
@@ -148,6 +149,19 @@ impl SoftBody {
         }
     }
 
+    // pinned and grabbed particles have no mass to count
+    pub fn energy(&self, gravity: Vec3) -> Energy {
+        self.positions
+            .chunks_exact(3)
+            .zip(self.velocities.chunks_exact(3))
+            .zip(self.inv_mass.iter())
+            .filter(|(_, &w)| w > 0.0)
+            .map(|((p, v), &w)| {
+                Energy::particle(1.0 / w, Vec3::from_slice(p), Vec3::from_slice(v), gravity)
+            })
+            .sum()
+    }
+
     pub fn pre_solve(&mut self, dt: f32, gravity: Vec3) {
         for i in 0..self.num_particles {
             if self.inv_mass[i] == 0.0 {
@@ -1,5 +1,6 @@
 mod camera_grabber;
 mod components;
+mod energy;
 mod intersect;
 mod models;
 mod reset;
@@ -7,6 +8,7 @@ mod resources;
 
 use camera_grabber::*;
 use components::*;
+use energy::*;
 use models::*;
 use reset::*;
 use resources::*;
@@ -22,6 +24,7 @@ fn main() {
         .insert_resource(ClearColor(Color::BLACK))
         .add_plugin(ResetPlugin)
         .add_plugin(CameraGrabberPlugin)
+        .add_plugin(EnergyDiagnosticsPlugin)
         .init_resource::<Config>()
         .add_startup_system(setup)
         .add_system(simulate_softbody)
@@ -139,6 +142,7 @@ fn simulate_softbody(
     time: Res<Time>,
     config: Res<Config>,
     mut meshes: ResMut<Assets<Mesh>>,
+    mut step_energy: ResMut<StepEnergy>,
 ) {
     let sdt = time.delta_seconds() / config.sub_steps as f32;
 
@@ -147,7 +151,7 @@ fn simulate_softbody(
         return;
     }
 
-    for _step in 0..config.sub_steps {
+    for step in 0..config.sub_steps as usize {
         for (_e, mut _trans, mut sb, _mesh_handle) in query.iter_mut() {
             sb.pre_solve(sdt, config.gravity);
         }
@@ -159,6 +163,12 @@ fn simulate_softbody(
         for (_e, mut _trans, mut sb, _mesh_handle) in query.iter_mut() {
             sb.post_solve(sdt);
         }
+
+        let energy = query
+            .iter()
+            .map(|(_e, _trans, sb, _mesh_handle)| sb.energy(config.gravity))
+            .sum();
+        step_energy.add(step, energy);
     }
 
     // update mesh
@@ -175,6 +185,7 @@ fn simulate_ball(
     time: Res<Time>,
     config: Res<Config>,
     grabbed: Res<Grabbed>,
+    mut step_energy: ResMut<StepEnergy>,
 ) {
     let sdt = time.delta_seconds() / config.sub_steps as f32;
 
@@ -183,10 +194,14 @@ fn simulate_ball(
             continue;
         }
 
-        // sub steps
-        for _ in 0..config.sub_steps {
+        // sub steps, the ball has no mass of its own and counts as 1 kg
+        for step in 0..config.sub_steps as usize {
             velocity.0 += config.gravity * sdt * config.scale;
-            trans.translation += velocity.0 * sdt * config.scale
+            trans.translation += velocity.0 * sdt * config.scale;
+            step_energy.add(
+                step,
+                Energy::particle(1.0, trans.translation, velocity.0, config.gravity),
+            );
         }
 
         // keep ball in bounds
@@ -0,0 +1,109 @@
+use std::{iter::Sum, ops::Add};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+};
+
+use crate::{
+    components::{Ball, Velocity},
+    resources::Config,
+    simulate_ball,
+};
+
+// summed over the balls after each step, there are no substeps here so that's once a frame, the
+// balls have no mass of their own and count as 1 kg each
+pub const KINETIC_ENERGY: DiagnosticId =
+    DiagnosticId::from_u128(325606194416851283327209846785090617017);
+pub const POTENTIAL_ENERGY: DiagnosticId =
+    DiagnosticId::from_u128(57113027348290836915203776290046351216);
+pub const TOTAL_ENERGY: DiagnosticId =
+    DiagnosticId::from_u128(196541383904611264890762517368340918571);
+pub const LINEAR_MOMENTUM: DiagnosticId =
+    DiagnosticId::from_u128(268095710023459371288151633309574862207);
+pub const ANGULAR_MOMENTUM: DiagnosticId =
+    DiagnosticId::from_u128(137984560233791850627443916097215738624);
+
+pub struct EnergyDiagnosticsPlugin;
+
+impl Plugin for EnergyDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_diagnostics)
+            .add_system(measure_energy.after(simulate_ball));
+    }
+}
+
+// potential energy is zero on the floor of the box, angular momentum is about its corner
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Energy {
+    pub kinetic: f32,
+    pub potential: f32,
+    pub linear_momentum: Vec3,
+    pub angular_momentum: Vec3,
+}
+
+impl Energy {
+    pub fn particle(mass: f32, position: Vec3, velocity: Vec3, gravity: Vec3) -> Self {
+        let momentum = velocity * mass;
+        Self {
+            kinetic: 0.5 * mass * velocity.length_squared(),
+            potential: -mass * gravity.dot(position),
+            linear_momentum: momentum,
+            angular_momentum: position.cross(momentum),
+        }
+    }
+
+    pub fn total(&self) -> f32 {
+        self.kinetic + self.potential
+    }
+}
+
+impl Add for Energy {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            kinetic: self.kinetic + other.kinetic,
+            potential: self.potential + other.potential,
+            linear_momentum: self.linear_momentum + other.linear_momentum,
+            angular_momentum: self.angular_momentum + other.angular_momentum,
+        }
+    }
+}
+
+impl Sum for Energy {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+fn setup_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(KINETIC_ENERGY, "kinetic_energy", 20).with_suffix("J"));
+    diagnostics.add(Diagnostic::new(POTENTIAL_ENERGY, "potential_energy", 20).with_suffix("J"));
+    diagnostics.add(Diagnostic::new(TOTAL_ENERGY, "total_energy", 20).with_suffix("J"));
+    diagnostics.add(Diagnostic::new(LINEAR_MOMENTUM, "linear_momentum", 20).with_suffix("kg m/s"));
+    diagnostics.add(
+        Diagnostic::new(ANGULAR_MOMENTUM, "angular_momentum", 20).with_suffix("kg m^2/s"),
+    );
+}
+
+fn measure_energy(
+    query: Query<(&Transform, &Velocity), With<Ball>>,
+    config: Res<Config>,
+    mut diagnostics: ResMut<Diagnostics>,
+) {
+    let energy = query
+        .iter()
+        .map(|(trans, velocity)| {
+            Energy::particle(1.0, trans.translation, velocity.0, config.gravity)
+        })
+        .sum::<Energy>();
+
+    diagnostics.add_measurement(KINETIC_ENERGY, || energy.kinetic as f64);
+    diagnostics.add_measurement(POTENTIAL_ENERGY, || energy.potential as f64);
+    diagnostics.add_measurement(TOTAL_ENERGY, || energy.total() as f64);
+    diagnostics.add_measurement(LINEAR_MOMENTUM, || energy.linear_momentum.length() as f64);
+    diagnostics.add_measurement(ANGULAR_MOMENTUM, || {
+        energy.angular_momentum.length() as f64
+    });
+}
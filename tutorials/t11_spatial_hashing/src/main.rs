@@ -1,11 +1,13 @@
 mod camera_controller;
 mod components;
+mod energy;
 mod reset;
 mod resources;
 mod text_overlay;
 
 use camera_controller::*;
 use components::*;
+use energy::*;
 use reset::*;
 use resources::*;
 use text_overlay::*;
@@ -25,6 +27,7 @@ fn main() {
         .add_plugin(ResetPlugin)
         .add_plugin(CameraControllerPlugin)
         .add_plugin(TextOverlayPlugin)
+        .add_plugin(EnergyDiagnosticsPlugin)
         .init_resource::<Config>()
         .init_resource::<BallAssets>()
         .add_startup_system(setup)
@@ -1,4 +1,7 @@
-use crate::BALL_COUNT;
+use crate::{
+    energy::{ANGULAR_MOMENTUM, KINETIC_ENERGY, LINEAR_MOMENTUM, POTENTIAL_ENERGY, TOTAL_ENERGY},
+    BALL_COUNT,
+};
 
 use super::Keep;
 use bevy::{
@@ -12,13 +15,17 @@ impl Plugin for TextOverlayPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(FrameTimeDiagnosticsPlugin::default())
             .add_startup_system(setup_overlay)
-            .add_system(update_fps);
+            .add_system(update_fps)
+            .add_system(update_energy);
     }
 }
 
 #[derive(Component)]
 struct FpsText;
 
+#[derive(Component)]
+struct EnergyText;
+
 const UI_SIZE: f32 = 20.0;
 
 fn setup_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
@@ -102,6 +109,62 @@ fn setup_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
         Name::new("ui Ball Count"),
         Keep,
     ));
+
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(50.),
+                    ..Default::default()
+                },
+                align_self: AlignSelf::FlexEnd,
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: "Energy: ".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::WHITE,
+                        },
+                    },
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::GREEN,
+                        },
+                    },
+                    TextSection {
+                        value: "  Momentum: ".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::WHITE,
+                        },
+                    },
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::GREEN,
+                        },
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Name::new("ui Energy"),
+        Keep,
+        EnergyText,
+    ));
 }
 
 fn update_fps(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, With<FpsText>>) {
@@ -120,3 +183,26 @@ fn update_fps(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, With<Fp
         }
     }
 }
+
+// the collisions are elastic, without gravity the kinetic energy should stay where it started
+fn update_energy(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, With<EnergyText>>) {
+    let average = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.average())
+            .unwrap_or_default()
+    };
+    for mut text in query.iter_mut() {
+        text.sections[1].value = format!(
+            "{:.2} J (kinetic {:.2}, potential {:.2})",
+            average(TOTAL_ENERGY),
+            average(KINETIC_ENERGY),
+            average(POTENTIAL_ENERGY),
+        );
+        text.sections[3].value = format!(
+            "linear {:.3}, angular {:.3}",
+            average(LINEAR_MOMENTUM),
+            average(ANGULAR_MOMENTUM),
+        );
+    }
+}
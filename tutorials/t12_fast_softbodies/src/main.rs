@@ -1,15 +1,23 @@
 mod camera_grabber;
+mod damping;
+mod energy;
 mod intersect;
+mod material;
 mod models;
 mod resources;
+mod shape_match;
 mod softbody;
 mod spatial_hash;
 mod state;
 mod text_overlay;
 
 use camera_grabber::*;
+use damping::*;
+use energy::*;
+use material::*;
 use models::*;
 use resources::*;
+use shape_match::*;
 use softbody::*;
 use state::*;
 use text_overlay::*;
@@ -17,11 +25,14 @@ use text_overlay::*;
 use bevy_atmosphere::prelude::*;
 
 use bevy::{
+    diagnostic::Diagnostics,
+    ecs::system::SystemParam,
     pbr::{
         wireframe::{Wireframe, WireframePlugin},
         NotShadowCaster,
     },
     prelude::*,
+    utils::Instant,
 };
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
@@ -34,15 +45,18 @@ fn main() {
         .add_plugin(TetMeshPlugin)
         .add_plugin(StatePlugin)
         .add_plugin(TextOverlayPlugin)
+        .add_plugin(EnergyDiagnosticsPlugin)
         .add_plugin(CameraGrabberPlugin)
         .add_plugin(AtmospherePlugin)
         .add_plugin(WireframePlugin)
         .add_asset::<SoftBody>()
+        .add_asset::<ShapeMatchBody>()
         //.insert_resource(ClearColor(Color::BLACK))
         .init_resource::<DragonAssets>()
         .init_resource::<Config>()
         .add_startup_system(setup)
         .add_system(spawn_dragon.in_schedule(OnEnter(AppState::Playing)))
+        .add_system(spawn_jelly.in_schedule(OnEnter(AppState::Playing)))
         .add_system(simulate_softbody.in_set(OnUpdate(AppState::Playing)))
         //.add_system(fix_added_softbody.in_set(OnUpdate(AppState::Playing)).before(simulate_softbody))
         // debug
@@ -54,6 +68,7 @@ fn main() {
         )
         .add_system(remove_debug_children.in_schedule(OnExit(DebugState::On)))
         .register_type::<Config>()
+        .register_type::<Damping>()
         .register_type::<Ball>()
         .register_type::<Velocity>()
         .register_type::<SoftBody>()
@@ -162,11 +177,40 @@ fn spawn_dragon(
     dragon_assets: Res<DragonAssets>,
     mut tet_meshes: ResMut<Assets<TetMesh>>,
     mut softbodies: ResMut<Assets<SoftBody>>,
+    config: Res<Config>,
 ) {
     info!("Spawning dragon");
 
     let dragon = tet_meshes.get_mut(&dragon_assets.tet_mesh).unwrap();
-    let sb = SoftBody::new(dragon, 20., 0.0);
+    let mut sb = SoftBody::new(dragon, 20., 0.0);
+    sb.damping = config.damping;
+    sb.set_material(BodyMaterial {
+        density: config.density,
+    });
+    if config.paint {
+        let soft = PaintSample {
+            compliance: 4.0,
+            damping: 2.0,
+            density: 1.0,
+        };
+        let stiff = PaintSample {
+            compliance: 0.25,
+            ..default()
+        };
+        // painted in the tet mesh if it has colors, otherwise soft in the middle
+        let paint = match dragon.vertex_colors() {
+            Some(colors) => MaterialPaint::from_vertex_colors(&colors, stiff, soft),
+            None => MaterialPaint::radial(
+                &sb.particle_positions(),
+                sb.center_of_mass(),
+                0.3,
+                soft,
+                stiff,
+            ),
+        };
+        sb.paint(&paint);
+    }
+    info!("Dragon mass {:.2} kg", sb.total_mass());
     let mesh_handle = meshes.add(Mesh::from(&sb));
     let sb_handle = softbodies.add(sb);
 
@@ -188,12 +232,68 @@ fn spawn_dragon(
     ));
 }
 
+// meshless, only needs the particles of the mesh
+fn spawn_jelly(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut shape_match_bodies: ResMut<Assets<ShapeMatchBody>>,
+) {
+    info!("Spawning jelly");
+
+    let mesh = Mesh::from(shape::UVSphere {
+        radius: 0.3,
+        sectors: 24,
+        stacks: 12,
+    });
+    let body = ShapeMatchBody::clustered(&mesh, &Transform::from_xyz(1.0, 1.0, 0.), 0.3, 0.2);
+
+    commands.spawn((
+        PbrBundle {
+            // mesh will be replaced every frame
+            mesh: meshes.add(mesh),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgba(0.2, 0.8, 0.2, 0.8),
+                alpha_mode: AlphaMode::Blend,
+                perceptual_roughness: 0.2,
+                ..default()
+            }),
+            transform: Transform::from_xyz(1.0, 1.0, 0.),
+            ..default()
+        },
+        shape_match_bodies.add(body),
+        Name::new("Jelly"),
+    ));
+}
+
+type SoftBodyData = (
+    &'static mut Transform,
+    &'static Handle<SoftBody>,
+    &'static Handle<Mesh>,
+);
+type ShapeMatchData = (
+    &'static mut Transform,
+    &'static Handle<ShapeMatchBody>,
+    &'static Handle<Mesh>,
+);
+
+// both kinds of simulated body with the assets they're stored in
+#[derive(SystemParam)]
+struct Bodies<'w, 's> {
+    soft: Query<'w, 's, SoftBodyData, Without<Handle<ShapeMatchBody>>>,
+    shape_match: Query<'w, 's, ShapeMatchData, Without<Handle<SoftBody>>>,
+    softbodies: ResMut<'w, Assets<SoftBody>>,
+    shape_match_bodies: ResMut<'w, Assets<ShapeMatchBody>>,
+}
+
 fn simulate_softbody(
-    mut query: Query<(&mut Transform, &Handle<SoftBody>, &Handle<Mesh>)>,
+    mut bodies: Bodies,
     time: Res<Time>,
     config: Res<Config>,
+    mut diagnostics: ResMut<Diagnostics>,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut softbodies: ResMut<Assets<SoftBody>>,
+    mut last_damping: Local<Option<Damping>>,
+    mut step_energy: ResMut<StepEnergy>,
 ) {
     let sdt = time.delta_seconds() / config.sub_steps as f32;
 
@@ -202,32 +302,88 @@ fn simulate_softbody(
         return;
     }
 
-    for _step in 0..config.sub_steps {
-        for (mut _trans, sb_handle, _mesh_handle) in query.iter_mut() {
-            let sb = softbodies.get_mut(sb_handle).unwrap();
+    // bodies get the config damping when spawned, only edits are pushed to existing ones so
+    // a body can still be given its own
+    let damping_changed = last_damping
+        .replace(config.damping)
+        .is_some_and(|last| last != config.damping);
+    for (mut _trans, sb_handle, _mesh_handle) in bodies.soft.iter_mut() {
+        let sb = bodies.softbodies.get_mut(sb_handle).unwrap();
+        sb.parallel = config.parallel;
+        sb.plastic = config.plastic;
+        if damping_changed {
+            sb.damping = config.damping;
+        }
+    }
+
+    let start = Instant::now();
+    for step in 0..config.sub_steps as usize {
+        for (mut _trans, sb_handle, _mesh_handle) in bodies.soft.iter_mut() {
+            let sb = bodies.softbodies.get_mut(sb_handle).unwrap();
             sb.pre_solve(sdt, config.gravity);
         }
 
-        for (mut _trans, sb_handle, _mesh_handle) in query.iter_mut() {
-            let sb = softbodies.get_mut(sb_handle).unwrap();
+        for (mut _trans, body_handle, _mesh_handle) in bodies.shape_match.iter_mut() {
+            let body = bodies.shape_match_bodies.get_mut(body_handle).unwrap();
+            body.pre_solve(sdt, config.gravity);
+        }
+
+        for (mut _trans, sb_handle, _mesh_handle) in bodies.soft.iter_mut() {
+            let sb = bodies.softbodies.get_mut(sb_handle).unwrap();
             sb.solve(sdt);
         }
 
-        for (mut _trans, sb_handle, _mesh_handle) in query.iter_mut() {
-            let sb = softbodies.get_mut(sb_handle).unwrap();
+        for (mut _trans, body_handle, _mesh_handle) in bodies.shape_match.iter_mut() {
+            let body = bodies.shape_match_bodies.get_mut(body_handle).unwrap();
+            body.solve(sdt);
+        }
+
+        for (mut _trans, sb_handle, _mesh_handle) in bodies.soft.iter_mut() {
+            let sb = bodies.softbodies.get_mut(sb_handle).unwrap();
             sb.post_solve(sdt);
+            sb.solve_plasticity();
         }
+
+        for (mut _trans, body_handle, _mesh_handle) in bodies.shape_match.iter_mut() {
+            let body = bodies.shape_match_bodies.get_mut(body_handle).unwrap();
+            body.post_solve(sdt);
+        }
+
+        let energy = bodies
+            .soft
+            .iter()
+            .filter_map(|(_, handle, _)| bodies.softbodies.get(handle))
+            .map(|sb| sb.energy(config.gravity))
+            .chain(
+                bodies
+                    .shape_match
+                    .iter()
+                    .filter_map(|(_, handle, _)| bodies.shape_match_bodies.get(handle))
+                    .map(|body| body.energy(config.gravity)),
+            )
+            .sum();
+        step_energy.add(step, energy);
     }
+    diagnostics.add_measurement(SOLVE_TIME, || start.elapsed().as_secs_f64() * 1000.0);
 
     // update mesh, kind of hacky
-    for (mut trans, sb_handle, mesh_handle) in query.iter_mut() {
-        let sb = softbodies.get_mut(sb_handle).unwrap();
+    for (mut trans, sb_handle, mesh_handle) in bodies.soft.iter_mut() {
+        let sb = bodies.softbodies.get_mut(sb_handle).unwrap();
         sb.update_transform(&mut trans);
 
         if let Some(mesh) = meshes.get_mut(mesh_handle) {
             sb.update_visual_mesh(&trans, mesh);
         }
     }
+
+    for (mut trans, body_handle, mesh_handle) in bodies.shape_match.iter_mut() {
+        let body = bodies.shape_match_bodies.get_mut(body_handle).unwrap();
+        body.update_transform(&mut trans);
+
+        if let Some(mesh) = meshes.get_mut(mesh_handle) {
+            body.update_visual_mesh(&trans, mesh);
+        }
+    }
 }
 
 #[derive(Component)]
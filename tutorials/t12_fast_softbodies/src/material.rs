@@ -0,0 +1,19 @@
+use bevy::prelude::*;
+
+// what a soft body is made of, each particle gets density * tet volume / 4 of every tet it is
+// in, the default is the unit density the demo is tuned for
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, PartialEq)]
+pub struct BodyMaterial {
+    // kg / m³
+    pub density: f32,
+}
+
+impl Default for BodyMaterial {
+    fn default() -> Self {
+        Self { density: 1.0 }
+    }
+}
+
+impl BodyMaterial {
+    pub const RUBBER: Self = Self { density: 1100.0 };
+}
@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::{prelude::ReflectInspectorOptions, InspectorOptions};
 
+use crate::{damping::Damping, material::BodyMaterial};
+
 #[derive(Reflect, Resource, InspectorOptions)]
 #[reflect(Resource, InspectorOptions)]
 pub struct Config {
@@ -10,6 +12,18 @@ pub struct Config {
     #[inspector(min = 0, max = 100)]
     pub sub_steps: u32,
     pub gravity: Vec3,
+    // soft bodies keep their dents, copied to every soft body each frame
+    pub plastic: bool,
+    // kg / m³ of spawned soft bodies, takes effect on reset
+    #[inspector(min = 0.1, max = 2000.)]
+    pub density: f32,
+    // the tet mesh vertex colors if it has any, otherwise a soft belly and stiff limbs, instead
+    // of one uniform material, takes effect on reset
+    pub paint: bool,
+    // solve each constraint color on the task pool
+    pub parallel: bool,
+    // given to bodies as they spawn, edits are pushed to the existing ones
+    pub damping: Damping,
 }
 
 impl Default for Config {
@@ -19,6 +33,11 @@ impl Default for Config {
             half_size: 10.,
             sub_steps: 10,
             gravity: Vec3::new(0., -9.81, 0.),
+            plastic: false,
+            density: BodyMaterial::default().density,
+            paint: false,
+            parallel: true,
+            damping: Damping::default(),
         }
     }
 }
@@ -0,0 +1,343 @@
+use bevy::{
+    math::{Mat3A, Vec3A},
+    prelude::*,
+    reflect::TypeUuid,
+    render::mesh::{Indices, VertexAttributeValues},
+};
+use bevy_inspector_egui::prelude::*;
+
+use crate::{energy::Energy, softbody::write_flat_mesh};
+
+// Meshless deformables, Müller et al. 2005 "Meshless Deformations Based on Shape Matching"
+// every cluster finds the rotation that best fits its rest shape onto the current particles
+// and pulls the particles toward that goal, overlapping clusters give a soft jelly look
+
+#[derive(Reflect, Default, InspectorOptions, TypeUuid)]
+#[uuid = "6a3f1c44-4d1e-4a55-9b8f-0f3c2e7d9a51"]
+pub struct ShapeMatchBody {
+    num_particles: usize,
+    positions: Vec<Vec3A>,
+    prev_positions: Vec<Vec3A>,
+    velocities: Vec<Vec3A>,
+    inv_mass: Vec<f32>,
+
+    // clusters, members of cluster i are cluster_ids[cluster_starts[i]..cluster_starts[i + 1]]
+    cluster_starts: Vec<usize>,
+    cluster_ids: Vec<usize>,
+    // rest offsets from the cluster center, one per cluster_ids entry
+    rest_offsets: Vec<Vec3A>,
+    rotations: Vec<Quat>,
+
+    // how far particles move toward their goal each substep, 1 is rigid
+    #[inspector(min = 0., max = 1.)]
+    pub stiffness: f32,
+
+    deltas: Vec<Vec3A>,
+    counts: Vec<u32>,
+
+    indices: Vec<usize>,
+    pub radius: f32, // for raycasting
+}
+
+impl ShapeMatchBody {
+    // the mesh vertices are the particles, one cluster over all of them
+    pub fn new(mesh: &Mesh, offset: &Transform, stiffness: f32) -> Self {
+        let mut result = Self::from_mesh(mesh, offset, stiffness);
+        let all = (0..result.num_particles).collect::<Vec<_>>();
+        result.set_clusters(&[all]);
+        result
+    }
+
+    // overlapping spherical clusters on a grid of cluster_size, lets the body bend instead of
+    // only wobbling around its rigid shape
+    pub fn clustered(mesh: &Mesh, offset: &Transform, stiffness: f32, cluster_size: f32) -> Self {
+        let mut result = Self::from_mesh(mesh, offset, stiffness);
+
+        let mut min = Vec3A::splat(f32::MAX);
+        let mut max = Vec3A::splat(f32::MIN);
+        for i in 0..result.num_particles {
+            min = min.min(result.particle(i));
+            max = max.max(result.particle(i));
+        }
+
+        // radius reaches the cell corners so every particle is in its own cell's cluster, unless
+        // that cell was too sparse to keep
+        let r = cluster_size;
+        let cells = Vec3::from(((max - min) / cluster_size).ceil().max(Vec3A::ONE)).as_uvec3();
+        let mut clusters = vec![];
+        let mut centers = vec![];
+        for x in 0..cells.x {
+            for y in 0..cells.y {
+                for z in 0..cells.z {
+                    let center =
+                        min + (Vec3A::new(x as f32, y as f32, z as f32) + 0.5) * cluster_size;
+                    let members = (0..result.num_particles)
+                        .filter(|&i| result.particle(i).distance_squared(center) <= r * r)
+                        .collect::<Vec<_>>();
+                    // need a few particles to get a rotation out of
+                    if members.len() >= 4 {
+                        clusters.push(members);
+                        centers.push(center);
+                    }
+                }
+            }
+        }
+        if clusters.is_empty() {
+            clusters.push((0..result.num_particles).collect());
+        }
+
+        // whatever the sparse cells left out joins the nearest cluster, a particle in no
+        // cluster would never be pulled back toward the shape
+        let mut covered = vec![false; result.num_particles];
+        for &id in clusters.iter().flatten() {
+            covered[id] = true;
+        }
+        for i in (0..result.num_particles).filter(|&i| !covered[i]) {
+            let p = result.particle(i);
+            let nearest = (0..centers.len())
+                .min_by(|&a, &b| {
+                    p.distance_squared(centers[a])
+                        .total_cmp(&p.distance_squared(centers[b]))
+                })
+                .unwrap();
+            clusters[nearest].push(i);
+        }
+
+        result.set_clusters(&clusters);
+        result
+    }
+
+    fn from_mesh(mesh: &Mesh, offset: &Transform, stiffness: f32) -> Self {
+        let positions = match mesh
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .expect("Shape match mesh requires ATTRIBUTE_POSITION")
+        {
+            VertexAttributeValues::Float32x3(positions) => positions
+                .iter()
+                .map(|&v| Vec3A::from(offset.transform_point(v.into())))
+                .collect::<Vec<_>>(),
+            _ => panic!("Wrong attribute type"),
+        };
+        let indices = match mesh.indices() {
+            Some(Indices::U16(v)) => v.iter().map(|&i| i as usize).collect(),
+            Some(Indices::U32(v)) => v.iter().map(|&i| i as usize).collect(),
+            None => vec![],
+        };
+
+        let num_particles = positions.len();
+        Self {
+            num_particles,
+            positions: positions.clone(),
+            prev_positions: positions,
+            velocities: vec![Vec3A::ZERO; num_particles],
+            inv_mass: vec![1.0; num_particles],
+            cluster_starts: vec![0],
+            cluster_ids: vec![],
+            rest_offsets: vec![],
+            rotations: vec![],
+            stiffness,
+            deltas: vec![Vec3A::ZERO; num_particles],
+            counts: vec![0; num_particles],
+            indices,
+            radius: 0.0,
+        }
+    }
+
+    fn set_clusters(&mut self, clusters: &[Vec<usize>]) {
+        self.cluster_starts = vec![0];
+        self.cluster_ids.clear();
+        self.rest_offsets.clear();
+        self.rotations.clear();
+
+        for members in clusters.iter() {
+            let center = self.center(members.iter().copied());
+            for &id in members.iter() {
+                self.cluster_ids.push(id);
+                self.rest_offsets.push(self.particle(id) - center);
+            }
+            self.cluster_starts.push(self.cluster_ids.len());
+            self.rotations.push(Quat::IDENTITY);
+        }
+    }
+
+    pub fn num_clusters(&self) -> usize {
+        self.rotations.len()
+    }
+
+    pub fn vert_count(&self) -> usize {
+        self.num_particles
+    }
+
+    // pinned and grabbed particles have no mass to count
+    pub fn energy(&self, gravity: Vec3) -> Energy {
+        self.positions
+            .iter()
+            .zip(self.velocities.iter())
+            .zip(self.inv_mass.iter())
+            .filter(|(_, &w)| w > 0.0)
+            .map(|((&p, &v), &w)| Energy::particle(1.0 / w, p.into(), v.into(), gravity))
+            .sum()
+    }
+
+    pub fn pre_solve(&mut self, dt: f32, gravity: Vec3) {
+        let gravity = Vec3A::from(gravity) * dt;
+        for (((pos, prev), vel), &w) in self
+            .positions
+            .iter_mut()
+            .zip(self.prev_positions.iter_mut())
+            .zip(self.velocities.iter_mut())
+            .zip(self.inv_mass.iter())
+        {
+            if w == 0.0 {
+                continue;
+            }
+            *vel += gravity;
+            *prev = *pos;
+            *pos += *vel * dt;
+            if pos.y < 0.0 {
+                *pos = *prev;
+                pos.y = 0.0;
+            }
+        }
+    }
+
+    pub fn solve(&mut self, _dt: f32) {
+        self.deltas.fill(Vec3A::ZERO);
+        self.counts.fill(0);
+
+        for c in 0..self.num_clusters() {
+            let start = self.cluster_starts[c];
+            let end = self.cluster_starts[c + 1];
+            let center = self.center(self.cluster_ids[start..end].iter().copied());
+
+            // A_pq, how the rest offsets map onto the current ones
+            let mut apq = Mat3A::ZERO;
+            for k in start..end {
+                let id = self.cluster_ids[k];
+                let p = self.particle(id) - center;
+                let q = self.rest_offsets[k];
+                let m = self.mass(id);
+                apq += Mat3A::from_cols(p * (q.x * m), p * (q.y * m), p * (q.z * m));
+            }
+
+            let rot = extract_rotation(apq, self.rotations[c], 10);
+            self.rotations[c] = rot;
+
+            for k in start..end {
+                let id = self.cluster_ids[k];
+                let goal = center + rot * self.rest_offsets[k];
+                let delta = goal - self.particle(id);
+                self.deltas[id] += delta;
+                self.counts[id] += 1;
+            }
+        }
+
+        // particles in several clusters move toward the average of their goals
+        for i in 0..self.num_particles {
+            if self.inv_mass[i] == 0.0 || self.counts[i] == 0 {
+                continue;
+            }
+            self.positions[i] += self.deltas[i] * (self.stiffness / self.counts[i] as f32);
+        }
+    }
+
+    pub fn post_solve(&mut self, dt: f32) {
+        let inv_dt = 1.0 / dt;
+        for (((vel, pos), prev), &w) in self
+            .velocities
+            .iter_mut()
+            .zip(self.positions.iter())
+            .zip(self.prev_positions.iter())
+            .zip(self.inv_mass.iter())
+        {
+            if w == 0.0 {
+                continue;
+            }
+            *vel = (*pos - *prev) * inv_dt;
+        }
+    }
+
+    // moves the transform to the center, call before update_visual_mesh
+    pub fn update_transform(&mut self, trans: &mut Transform) {
+        let center = self.center(0..self.num_particles);
+        self.radius = (0..self.num_particles)
+            .map(|i| self.particle(i).distance(center))
+            .fold(0.0, f32::max);
+        trans.translation = center.into();
+    }
+
+    pub fn update_visual_mesh(&mut self, trans: &Transform, mesh: &mut Mesh) {
+        let offset = Vec3A::from(trans.translation);
+        write_flat_mesh(mesh, &self.indices, self.num_particles, |i| {
+            self.positions[i] - offset
+        });
+    }
+
+    fn particle(&self, i: usize) -> Vec3A {
+        self.positions[i]
+    }
+
+    fn mass(&self, i: usize) -> f32 {
+        // pinned particles still count, as if they were very heavy
+        if self.inv_mass[i] == 0.0 {
+            1.0e6
+        } else {
+            1.0 / self.inv_mass[i]
+        }
+    }
+
+    // mass weighted center of the given particles
+    fn center(&self, ids: impl Iterator<Item = usize>) -> Vec3A {
+        let mut center = Vec3A::ZERO;
+        let mut total = 0.0;
+        for id in ids {
+            let m = self.mass(id);
+            center += self.particle(id) * m;
+            total += m;
+        }
+        if total > 0.0 {
+            center / total
+        } else {
+            center
+        }
+    }
+}
+
+// Müller et al. 2016 "A Robust Method to Extract the Rotational Part of Deformations",
+// warm started from last substep's rotation so a few iterations are enough
+fn extract_rotation(a: Mat3A, mut q: Quat, max_iter: usize) -> Quat {
+    for _ in 0..max_iter {
+        let r = Mat3A::from_quat(q);
+        let dot = r.x_axis.dot(a.x_axis) + r.y_axis.dot(a.y_axis) + r.z_axis.dot(a.z_axis);
+        let omega =
+            (r.x_axis.cross(a.x_axis) + r.y_axis.cross(a.y_axis) + r.z_axis.cross(a.z_axis))
+                * (1.0 / (dot.abs() + 1.0e-9));
+        let w = omega.length();
+        if w < 1.0e-9 {
+            break;
+        }
+        q = (Quat::from_axis_angle((omega / w).into(), w) * q).normalize();
+    }
+    q
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clustered_covers_every_particle() {
+        let mesh = Mesh::from(shape::UVSphere {
+            radius: 1.0,
+            sectors: 16,
+            stacks: 8,
+        });
+        // cells small enough that some of them catch fewer than 4 particles
+        let body = ShapeMatchBody::clustered(&mesh, &Transform::IDENTITY, 1.0, 0.3);
+        let mut covered = vec![false; body.num_particles];
+        for &id in body.cluster_ids.iter() {
+            covered[id] = true;
+        }
+        assert!(covered.iter().all(|&c| c));
+    }
+}
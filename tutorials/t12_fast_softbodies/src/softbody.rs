@@ -1,12 +1,14 @@
 use bevy::{
+    math::{Mat3A, Vec3A},
     prelude::*,
+    tasks::ComputeTaskPool,
     render::{mesh::{Indices, VertexAttributeValues}, render_resource::PrimitiveTopology}, reflect::TypeUuid,
 };
 use bevy_inspector_egui::prelude::*;
 
 use crate::{
     intersect::{ray_sphere_intersect, ray_triangle_intersect},
-    models::TetMesh, spatial_hash::SpatialHash,
+    damping::Damping, energy::Energy, material::BodyMaterial, models::{MaterialPaint, TetMesh}, spatial_hash::SpatialHash,
 };
 
 #[derive(Reflect, Component)]
@@ -29,28 +31,55 @@ pub struct SoftBody {
     // visual mesh
     #[inspector()]
     visual_indices: Vec<usize>,
-    visual_vertices: Vec<f32>,
+    visual_vertices: Vec<Vec3A>,
     num_vis_verts: usize,
     skinning_info: Vec<f32>,
 
-    // tet mesh
+    // tet mesh, particles are Vec3A like the rest of the solver math
     num_particles: usize,
     num_tets: usize,
-    positions: Vec<f32>,
-    prev_positions: Vec<f32>,
-    velocities: Vec<f32>,
+    positions: Vec<Vec3A>,
+    prev_positions: Vec<Vec3A>,
+    velocities: Vec<Vec3A>,
     tet_ids: Vec<usize>,
     edge_ids: Vec<usize>,
     rest_volumn: Vec<f32>,
     edge_lengths: Vec<f32>,
     inv_mass: Vec<f32>,
+    masses: Vec<f32>,
+    material: BodyMaterial,
+
+    // painted variation, compliance scales per constraint, damping per particle, density per tet
+    edge_scales: Vec<f32>,
+    volume_scales: Vec<f32>,
+    paint_damping: Vec<f32>,
+    density_scales: Vec<f32>,
     #[inspector(min = 0., max = 100.)]
     edge_compliance: f32,
     #[inspector(min = 0., max = 1.)]
     volume_compliance: f32,
+    pub damping: Damping,
+
+    // plasticity, strain past yield_strain moves the rest state toward the current one by creep
+    // per substep, but never further than max_plastic_strain from the original rest state
+    #[inspector(min = 0., max = 1.)]
+    pub yield_strain: f32,
+    #[inspector(min = 0., max = 1.)]
+    pub creep: f32,
+    #[inspector(min = 0., max = 1.)]
+    pub max_plastic_strain: f32,
+    pub plastic: bool,
+    orig_volumn: Vec<f32>,
+    orig_edge_lengths: Vec<f32>,
+
+    // constraints are sorted by graph color, constraints of one color share no particle
+    // so each color can be solved in parallel
+    pub parallel: bool,
+    edge_color_starts: Vec<usize>,
+    tet_color_starts: Vec<usize>,
+    // original index of every sorted tet, paints and meshes number tets the original way
+    tet_order: Vec<usize>,
 
-    temp: Vec<f32>,
-    grads: Vec<f32>,
     grab_id: Option<usize>,
     grab_inv_mass: f32,
 
@@ -59,33 +88,53 @@ pub struct SoftBody {
 
 const VOLUME_ID_ORDER: [[usize; 3]; 4] = [[1, 3, 2], [0, 2, 3], [0, 3, 1], [0, 1, 2]];
 
+// colors smaller than this aren't worth sending to the task pool
+const PARALLEL_MIN_CONSTRAINTS: usize = 256;
+
 impl SoftBody {
     pub fn new(mesh: &TetMesh, edge_compliance: f32, volume_compliance: f32) -> Self {
-        let num_particles = mesh.tet_vertices.len() / 3;
+        let positions = to_vec3a(&mesh.tet_vertices);
+        let num_particles = positions.len();
         let num_tets = mesh.tet_indices.len() / 4;
-        let num_vis_verts = mesh.vertices.len() / 3;
+        let visual_vertices = to_vec3a(&mesh.vertices);
+        let num_vis_verts = visual_vertices.len();
 
         let mut result = Self {
             visual_indices: mesh.indices.clone(),
-            visual_vertices: mesh.vertices.clone(),
+            visual_vertices,
             num_vis_verts: num_vis_verts,
             skinning_info: vec![0.0; 4 * num_vis_verts],
             
             // tet mesh
             num_particles,
             num_tets,
-            positions: mesh.tet_vertices.clone(),
-            prev_positions: mesh.tet_vertices.clone(),
-            velocities: vec![0.0; 3 * num_particles],
+            positions: positions.clone(),
+            prev_positions: positions,
+            velocities: vec![Vec3A::ZERO; num_particles],
             tet_ids: mesh.tet_indices.clone(),
             edge_ids: mesh.tet_edge_ids.clone(),
             rest_volumn: vec![0.0; num_tets],
             edge_lengths: vec![0.0; mesh.tet_edge_ids.len() / 2],
             inv_mass: vec![0.0; num_particles],
+            masses: vec![0.0; num_particles],
+            material: BodyMaterial::default(),
+            edge_scales: vec![1.0; mesh.tet_edge_ids.len() / 2],
+            volume_scales: vec![1.0; num_tets],
+            paint_damping: vec![0.0; num_particles],
+            density_scales: vec![1.0; num_tets],
             edge_compliance,
             volume_compliance,
-            temp: vec![0.0; 4 * 3],
-            grads: vec![0.0; 4 * 3],
+            damping: Damping::default(),
+            yield_strain: 0.1,
+            creep: 0.5,
+            max_plastic_strain: 0.5,
+            plastic: false,
+            orig_volumn: vec![],
+            orig_edge_lengths: vec![],
+            parallel: true,
+            edge_color_starts: vec![],
+            tet_color_starts: vec![],
+            tet_order: vec![],
             grab_id: None,
             grab_inv_mass: 0.,
             radius: 0.0,
@@ -102,9 +151,7 @@ impl SoftBody {
     {
         // create a hash for all vertices of the visual mesh
         let mut hash = SpatialHash::new(0.05, self.num_vis_verts);
-        hash.create(&self.visual_vertices.chunks(3)
-        .map(|v| Vec3::new(v[0], v[1], v[2]))
-        .collect::<Vec<Vec3>>());        
+        hash.create(&self.visual_vertices.iter().map(|&v| Vec3::from(v)).collect::<Vec<Vec3>>());
 
         self.skinning_info.fill(-1.0);		// undefined
 
@@ -113,43 +160,26 @@ impl SoftBody {
         let border = 0.05;
 
         // each tet searches for containing vertices
-        let mut tet_center = [0.0; 3];
-        let mut mat = [0.0; 9];
-        let mut bary = [0.0; 4];
-
         for i in 0..self.num_tets {
+            let p = self.tet_positions(i);
 
             // compute bounding sphere of tet
-            tet_center.fill(0.0);
-            for j in 0..4 {
-                vecAdd(&mut tet_center, 0, &self.positions, self.tet_ids[4 * i + j], 0.25);
-            }
+            let tet_center = (p[0] + p[1] + p[2] + p[3]) * 0.25;
+            let r_max = p.iter().map(|&v| tet_center.distance(v)).fold(0.0, f32::max) + border;
 
-            let mut r_max = 0.0f32;
-            for j in 0..4 {
-                let r2 = vecDistSquared(&tet_center, 0, &self.positions, self.tet_ids[4 * i + j]);
-                r_max = r_max.max(r2.sqrt());
+            hash.query(&tet_center.to_array(), 0, r_max);
+            if hash.query_size == 0 {
+                continue;
             }
 
-            r_max += border;
-
-            hash.query(&tet_center, 0, r_max);
-            if hash.query_ids.len() == 0 {
+            let mat = Mat3A::from_cols(p[0] - p[3], p[1] - p[3], p[2] - p[3]);
+            // a flat tet can't contain anything
+            if mat.determinant() == 0.0 {
                 continue;
             }
+            let mat = mat.inverse();
 
-            let id0 = self.tet_ids[4 * i];
-            let id1 = self.tet_ids[4 * i + 1];
-            let id2 = self.tet_ids[4 * i + 2];
-            let id3 = self.tet_ids[4 * i + 3];
-
-            vecSetDiff(&mut mat, 0, &self.positions, id0, &self.positions, id3, 1.0);
-            vecSetDiff(&mut mat, 1, &self.positions, id1, &self.positions, id3, 1.0);
-            vecSetDiff(&mut mat, 2, &self.positions, id2, &self.positions, id3, 1.0);
-
-            matSetInverse(&mut mat);
-
-            for j in 0..hash.query_ids.len() {
+            for j in 0..hash.query_size {
                 let id = hash.query_ids[j];
 
                 // we already have skinning info
@@ -157,20 +187,15 @@ impl SoftBody {
                     continue;
                 }
 
-                if vecDistSquared(&self.visual_vertices, id, &tet_center, 0) > r_max * r_max {
+                if self.visual_vertices[id].distance_squared(tet_center) > r_max * r_max {
                     continue;
                 }
 
                 // compute barycentric coords for candidate
-                vecSetDiff(&mut bary, 0, &self.visual_vertices, id, &self.positions, id3, 1.0);
-                matSetMult(&mat, &mut bary, 0);
-                bary[3] = 1.0 - bary[0] - bary[1] - bary[2];
+                let bary = mat * (self.visual_vertices[id] - p[3]);
+                let bary = [bary.x, bary.y, bary.z, 1.0 - bary.x - bary.y - bary.z];
 
-                let mut dist = 0.0f32;
-                for k in 0..4 {
-                    dist = dist.max( -bary[k]);
-                }
-                    
+                let dist = bary.iter().fold(0.0f32, |d, &b| d.max(-b));
                 if dist < min_dist[id] {
                     min_dist[id] = dist;
                     self.skinning_info[4 * id] = i as f32;
@@ -182,89 +207,181 @@ impl SoftBody {
         }
     }
 
-  
-    pub fn get_tet_volume(&mut self, nr: usize) -> f32 {
-        let id0 = self.tet_ids[4 * nr];
-        let id1 = self.tet_ids[4 * nr + 1];
-        let id2 = self.tet_ids[4 * nr + 2];
-        let id3 = self.tet_ids[4 * nr + 3];
-        vecSetDiff(
-            &mut self.temp,
-            0,
-            &self.positions,
-            id1,
-            &self.positions,
-            id0,
-            1.0,
-        );
-        vecSetDiff(
-            &mut self.temp,
-            1,
-            &self.positions,
-            id2,
-            &self.positions,
-            id0,
-            1.0,
-        );
-        vecSetDiff(
-            &mut self.temp,
-            2,
-            &self.positions,
-            id3,
-            &self.positions,
-            id0,
-            1.0,
-        );
-
-        // Cant borrow self.temp as mutable and immutable, doing it manually
-        // vecSetCross(&mut self.temp, 3, &self.temp, 0, &self.temp, 1);
-        let ar = 3 * 3;
-        let br = 0 * 3;
-        let cr = 1 * 3;
-        self.temp[ar] =
-            self.temp[br + 1] * self.temp[cr + 2] - self.temp[br + 2] * self.temp[cr + 1];
-        self.temp[ar + 1] =
-            self.temp[br + 2] * self.temp[cr + 0] - self.temp[br + 0] * self.temp[cr + 2];
-        self.temp[ar + 2] =
-            self.temp[br + 0] * self.temp[cr + 1] - self.temp[br + 1] * self.temp[cr + 0];
+    fn tet_positions(&self, nr: usize) -> [Vec3A; 4] {
+        [
+            self.positions[self.tet_ids[4 * nr]],
+            self.positions[self.tet_ids[4 * nr + 1]],
+            self.positions[self.tet_ids[4 * nr + 2]],
+            self.positions[self.tet_ids[4 * nr + 3]],
+        ]
+    }
 
-        return vecDot(&self.temp, 3, &self.temp, 2) / 6.0;
+    pub fn get_tet_volume(&self, nr: usize) -> f32 {
+        tet_volume(self.tet_positions(nr))
     }
 
     pub fn init_physics(&mut self) {
         for i in 0..self.num_tets {
-            let vol = self.get_tet_volume(i);
-            self.rest_volumn[i] = vol;
-            let p_inv_mass = if vol > 0.0 { 1.0 / (vol / 4.0) } else { 0.0 };
-            self.inv_mass[self.tet_ids[4 * i]] += p_inv_mass;
-            self.inv_mass[self.tet_ids[4 * i + 1]] += p_inv_mass;
-            self.inv_mass[self.tet_ids[4 * i + 2]] += p_inv_mass;
-            self.inv_mass[self.tet_ids[4 * i + 3]] += p_inv_mass;
+            self.rest_volumn[i] = self.get_tet_volume(i);
         }
+        self.update_masses();
         for i in 0..self.edge_lengths.len() {
             let id0 = self.edge_ids[2 * i];
             let id1 = self.edge_ids[2 * i + 1];
-            self.edge_lengths[i] =
-                vecDistSquared(&self.positions, id0, &self.positions, id1).sqrt();
+            self.edge_lengths[i] = self.positions[id0].distance(self.positions[id1]);
         }
+        self.color_constraints();
+
+        self.orig_volumn = self.rest_volumn.clone();
+        self.orig_edge_lengths = self.edge_lengths.clone();
     }
 
-    pub fn pre_solve(&mut self, dt: f32, gravity: Vec3) {
+    // reorders edges and tets so each color is contiguous
+    fn color_constraints(&mut self) {
+        let (order, starts) = color_graph(&self.edge_ids, 2, self.num_particles);
+        self.edge_ids = order
+            .iter()
+            .flat_map(|&i| [self.edge_ids[2 * i], self.edge_ids[2 * i + 1]])
+            .collect();
+        self.edge_lengths = order.iter().map(|&i| self.edge_lengths[i]).collect();
+        self.edge_color_starts = starts;
+
+        let (order, starts) = color_graph(&self.tet_ids, 4, self.num_particles);
+        self.tet_ids = order
+            .iter()
+            .flat_map(|&i| {
+                [
+                    self.tet_ids[4 * i],
+                    self.tet_ids[4 * i + 1],
+                    self.tet_ids[4 * i + 2],
+                    self.tet_ids[4 * i + 3],
+                ]
+            })
+            .collect();
+        self.rest_volumn = order.iter().map(|&i| self.rest_volumn[i]).collect();
+        self.tet_color_starts = starts;
+        self.tet_order = order;
+    }
+
+    // a quarter of every tet's rest mass goes to each of its particles
+    fn update_masses(&mut self) {
+        self.masses.fill(0.0);
+        for i in 0..self.num_tets {
+            // inverted tets add nothing
+            let p_mass =
+                self.rest_volumn[i].max(0.0) * self.material.density * self.density_scales[i] / 4.0;
+            for j in 0..4 {
+                self.masses[self.tet_ids[4 * i + j]] += p_mass;
+            }
+        }
+        for (i, (w, &m)) in self.inv_mass.iter_mut().zip(self.masses.iter()).enumerate() {
+            let inv = if m > 0.0 { 1.0 / m } else { 0.0 };
+            if self.grab_id == Some(i) {
+                self.grab_inv_mass = inv;
+            } else {
+                *w = inv;
+            }
+        }
+    }
+
+    // varies compliance, damping and density over the body, the paint is indexed by tet particle
+    // and by original tet number
+    pub fn paint(&mut self, paint: &MaterialPaint) {
+        self.edge_scales = self
+            .edge_ids
+            .chunks_exact(2)
+            .map(|ids| paint.constraint(ids).compliance)
+            .collect();
+        let tets = self
+            .tet_ids
+            .chunks_exact(4)
+            .zip(self.tet_order.iter())
+            .map(|(ids, &nr)| paint.tet(nr, ids))
+            .collect::<Vec<_>>();
+        self.volume_scales = tets.iter().map(|t| t.compliance).collect();
+        self.density_scales = tets.iter().map(|t| t.density).collect();
         for i in 0..self.num_particles {
-            if self.inv_mass[i] == 0.0 {
+            self.paint_damping[i] = paint.vertex(i).damping;
+        }
+        self.update_masses();
+    }
+
+    // rest pose, for building a paint
+    pub fn particle_positions(&self) -> Vec<Vec3> {
+        self.positions.iter().map(|&p| p.into()).collect()
+    }
+
+    pub fn num_colors(&self) -> (usize, usize) {
+        (
+            self.edge_color_starts.len().saturating_sub(1),
+            self.tet_color_starts.len().saturating_sub(1),
+        )
+    }
+
+    pub fn material(&self) -> BodyMaterial {
+        self.material
+    }
+
+    pub fn set_material(&mut self, material: BodyMaterial) {
+        // masses are linear in the density, scaling them leaves grabbed particles pinned
+        let scale = material.density / self.material.density;
+        for m in self.masses.iter_mut() {
+            *m *= scale;
+        }
+        for w in self.inv_mass.iter_mut() {
+            *w /= scale;
+        }
+        self.grab_inv_mass /= scale;
+        self.material = material;
+    }
+
+    // kg
+    pub fn total_mass(&self) -> f32 {
+        self.masses.iter().sum()
+    }
+
+    pub fn center_of_mass(&self) -> Vec3 {
+        let total = self.total_mass();
+        if total == 0.0 {
+            return Vec3::ZERO;
+        }
+        let weighted = self
+            .positions
+            .iter()
+            .zip(self.masses.iter())
+            .map(|(&p, &m)| p * m)
+            .sum::<Vec3A>();
+        (weighted / total).into()
+    }
+
+    pub fn energy(&self, gravity: Vec3) -> Energy {
+        self.positions
+            .iter()
+            .zip(self.velocities.iter())
+            .zip(self.masses.iter())
+            .map(|((&p, &v), &m)| Energy::particle(m, p.into(), v.into(), gravity))
+            .sum()
+    }
+
+    pub fn pre_solve(&mut self, dt: f32, gravity: Vec3) {
+        let gravity = Vec3A::from(gravity) * dt;
+        for (((pos, prev), vel), &w) in self
+            .positions
+            .iter_mut()
+            .zip(self.prev_positions.iter_mut())
+            .zip(self.velocities.iter_mut())
+            .zip(self.inv_mass.iter())
+        {
+            // grabbed particles still need a start position for constraint damping
+            *prev = *pos;
+            if w == 0.0 {
                 continue;
             }
-            //vecAdd(this.vel,i, gravity,0, dt);
-            self.velocities[i * 3] += gravity[0] * dt;
-            self.velocities[i * 3 + 1] += gravity[1] * dt;
-            self.velocities[i * 3 + 2] += gravity[2] * dt;
-
-            vecCopy(&mut self.prev_positions, i, &self.positions, i);
-            vecAdd(&mut self.positions, i, &self.velocities, i, dt);
-            let y = self.positions[3 * i + 1];
-            if y < 0.0 {
-                vecCopy(&mut self.positions, i, &self.prev_positions, i);
-                self.positions[3 * i + 1] = 0.0;
+            *vel += gravity;
+            *pos += *vel * dt;
+            if pos.y < 0.0 {
+                *pos = *prev;
+                pos.y = 0.0;
             }
         }
     }
@@ -275,122 +392,223 @@ impl SoftBody {
     }
 
     pub fn post_solve( &mut self, dt: f32) {
-        
-
-        for i in 0..self.num_particles {
-            if self.inv_mass[i] == 0.0 {
+        let inv_dt = 1.0 / dt;
+        for ((((vel, pos), prev), &w), &damping) in self
+            .velocities
+            .iter_mut()
+            .zip(self.positions.iter())
+            .zip(self.prev_positions.iter())
+            .zip(self.inv_mass.iter())
+            .zip(self.paint_damping.iter())
+        {
+            if w == 0.0 {
                 continue;
             }
-            vecSetDiff(
-                &mut self.velocities,
-                i,
-                &self.positions,
-                i,
-                &self.prev_positions,
-                i,
-                1.0 / dt,
-            );
+            *vel = (*pos - *prev) * inv_dt;
+            if damping > 0.0 {
+                *vel *= (1.0 - damping * dt).max(0.0);
+            }
         }
+        self.damping.apply(
+            &self.positions,
+            &mut self.velocities,
+            &self.masses,
+            &self.inv_mass,
+            dt,
+        );
     }
 
-    fn solve_edges(&mut self, compliance: f32, dt: f32) {
-        let alpha = compliance / dt / dt;
+    // call after post_solve, the constraints have done what they could this substep and
+    // whatever strain is left past the yield point becomes permanent
+    pub fn solve_plasticity(&mut self) {
+        if !self.plastic {
+            return;
+        }
 
         for i in 0..self.edge_lengths.len() {
             let id0 = self.edge_ids[2 * i];
             let id1 = self.edge_ids[2 * i + 1];
-            let w0 = self.inv_mass[id0];
-            let w1 = self.inv_mass[id1];
-            let w = w0 + w1;
-            if w == 0.0 {
-                continue;
-            }
+            let len = self.positions[id0].distance(self.positions[id1]);
+            self.edge_lengths[i] = plastic_flow(
+                self.edge_lengths[i],
+                len,
+                self.orig_edge_lengths[i],
+                self.yield_strain,
+                self.creep,
+                self.max_plastic_strain,
+            );
+        }
 
-            vecSetDiff(
-                &mut self.grads,
-                0,
-                &self.positions,
-                id0,
-                &self.positions,
-                id1,
-                1.0,
+        for i in 0..self.num_tets {
+            let vol = self.get_tet_volume(i);
+            self.rest_volumn[i] = plastic_flow(
+                self.rest_volumn[i],
+                vol,
+                self.orig_volumn[i],
+                self.yield_strain,
+                self.creep,
+                self.max_plastic_strain,
             );
-            let len = vecLengthSquared(&self.grads, 0).sqrt();
-            if len == 0.0 {
+        }
+    }
+
+    fn solve_edges(&mut self, compliance: f32, dt: f32) {
+        let alpha = compliance / dt / dt;
+
+        if !self.parallel {
+            for i in 0..self.edge_lengths.len() {
+                self.solve_edge(i, alpha, dt);
+            }
+            return;
+        }
+
+        for color in 0..self.edge_color_starts.len() - 1 {
+            let start = self.edge_color_starts[color];
+            let end = self.edge_color_starts[color + 1];
+            if end - start < PARALLEL_MIN_CONSTRAINTS {
+                for i in start..end {
+                    self.solve_edge(i, alpha, dt);
+                }
                 continue;
             }
-            vecScale(&mut self.grads, 0, 1.0 / len);
-            let rest_len = self.edge_lengths[i];
-            let c = len - rest_len;
-            let s = -c / (w + alpha);
-            vecAdd(&mut self.positions, id0, &self.grads, 0, s * w0);
-            vecAdd(&mut self.positions, id1, &self.grads, 0, -s * w1);
+
+            let positions = &self.positions;
+            let inv_mass = &self.inv_mass;
+            let edge_ids = &self.edge_ids;
+            let edge_lengths = &self.edge_lengths;
+            let edge_scales = &self.edge_scales;
+            let prev_positions = &self.prev_positions;
+            let damping = self.damping;
+            let corrections = par_ranges(start, end, |range| {
+                range
+                    .filter_map(|i| {
+                        let alpha = alpha * edge_scales[i];
+                        edge_correction(
+                            positions,
+                            prev_positions,
+                            inv_mass,
+                            [edge_ids[2 * i], edge_ids[2 * i + 1]],
+                            edge_lengths[i],
+                            alpha,
+                            damping.gamma(alpha, dt),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+            for (ids, deltas) in corrections.iter().flatten() {
+                for j in 0..2 {
+                    self.positions[ids[j]] += deltas[j];
+                }
+            }
+        }
+    }
+
+    fn solve_edge(&mut self, i: usize, alpha: f32, dt: f32) {
+        let ids = [self.edge_ids[2 * i], self.edge_ids[2 * i + 1]];
+        let alpha = alpha * self.edge_scales[i];
+        if let Some((ids, deltas)) = edge_correction(
+            &self.positions,
+            &self.prev_positions,
+            &self.inv_mass,
+            ids,
+            self.edge_lengths[i],
+            alpha,
+            self.damping.gamma(alpha, dt),
+        ) {
+            self.positions[ids[0]] += deltas[0];
+            self.positions[ids[1]] += deltas[1];
         }
     }
 
     fn solve_volumes(&mut self, compliance: f32, dt: f32) {
         let alpha = compliance / dt / dt;
 
-        for i in 0..self.num_tets {
-            let mut w = 0.0;
-
-            for j in 0..4 {
-                let id0 = self.tet_ids[4 * i + VOLUME_ID_ORDER[j][0]];
-                let id1 = self.tet_ids[4 * i + VOLUME_ID_ORDER[j][1]];
-                let id2 = self.tet_ids[4 * i + VOLUME_ID_ORDER[j][2]];
-
-                vecSetDiff(
-                    &mut self.temp,
-                    0,
-                    &self.positions,
-                    id1,
-                    &self.positions,
-                    id0,
-                    1.0,
-                );
-                vecSetDiff(
-                    &mut self.temp,
-                    1,
-                    &self.positions,
-                    id2,
-                    &self.positions,
-                    id0,
-                    1.0,
-                );
-                vecSetCross(&mut self.grads, j, &self.temp, 0, &self.temp, 1);
-                vecScale(&mut self.grads, j, 1.0 / 6.0);
-
-                w += self.inv_mass[self.tet_ids[4 * i + j]] * vecLengthSquared(&self.grads, j);
+        if !self.parallel {
+            for i in 0..self.num_tets {
+                self.solve_volume(i, alpha, dt);
             }
-            if w == 0.0 {
+            return;
+        }
+
+        for color in 0..self.tet_color_starts.len() - 1 {
+            let start = self.tet_color_starts[color];
+            let end = self.tet_color_starts[color + 1];
+            if end - start < PARALLEL_MIN_CONSTRAINTS {
+                for i in start..end {
+                    self.solve_volume(i, alpha, dt);
+                }
                 continue;
             }
 
-            let vol = self.get_tet_volume(i);
-            let rest_vol = self.rest_volumn[i];
-            let c = vol - rest_vol;
-            let s = -c / (w + alpha);
+            let positions = &self.positions;
+            let inv_mass = &self.inv_mass;
+            let tet_ids = &self.tet_ids;
+            let rest_volumn = &self.rest_volumn;
+            let volume_scales = &self.volume_scales;
+            let prev_positions = &self.prev_positions;
+            let damping = self.damping;
+            let corrections = par_ranges(start, end, |range| {
+                range
+                    .filter_map(|i| {
+                        let ids = [
+                            tet_ids[4 * i],
+                            tet_ids[4 * i + 1],
+                            tet_ids[4 * i + 2],
+                            tet_ids[4 * i + 3],
+                        ];
+                        let alpha = alpha * volume_scales[i];
+                        volume_correction(
+                            positions,
+                            prev_positions,
+                            inv_mass,
+                            ids,
+                            rest_volumn[i],
+                            alpha,
+                            damping.gamma(alpha, dt),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+            for (ids, deltas) in corrections.iter().flatten() {
+                for j in 0..4 {
+                    self.positions[ids[j]] += deltas[j];
+                }
+            }
+        }
+    }
 
+    fn solve_volume(&mut self, i: usize, alpha: f32, dt: f32) {
+        let ids = [
+            self.tet_ids[4 * i],
+            self.tet_ids[4 * i + 1],
+            self.tet_ids[4 * i + 2],
+            self.tet_ids[4 * i + 3],
+        ];
+        let alpha = alpha * self.volume_scales[i];
+        if let Some((ids, deltas)) = volume_correction(
+            &self.positions,
+            &self.prev_positions,
+            &self.inv_mass,
+            ids,
+            self.rest_volumn[i],
+            alpha,
+            self.damping.gamma(alpha, dt),
+        ) {
             for j in 0..4 {
-                let id = self.tet_ids[4 * i + j];
-                vecAdd(
-                    &mut self.positions,
-                    id,
-                    &self.grads,
-                    j,
-                    s * self.inv_mass[id],
-                )
+                self.positions[ids[j]] += deltas[j];
             }
         }
     }
-    
+
     pub fn create_tet_mesh(&self) -> Mesh {
         let mut mesh = Mesh::new(PrimitiveTopology::LineList);
         let indices = self.edge_ids.iter().map(|i| *i as u32).collect::<Vec<u32>>();
         let positions = self
             .positions
-            .chunks_exact(3)
-            .map(|v| [v[0], v[1], v[2]])
+            .iter()
+            .map(|v| v.to_array())
             .collect::<Vec<[f32; 3]>>();
 
         mesh.set_indices(Some(Indices::U32(indices)));
@@ -403,113 +621,44 @@ impl SoftBody {
     pub fn update_transform(&mut self, trans: &mut Transform) {
         
         // find avg position and radius of the mesh
-        let mut avg_pos = Vec3::ZERO;     
-        let len = self.positions.len() / 3;
-        for pos in self.positions.chunks_exact(3) {
-             avg_pos[0] += pos[0];
-             avg_pos[1] += pos[1];
-             avg_pos[2] += pos[2];
-        }
-        avg_pos[0] /= len as f32;
-        avg_pos[1] /= len as f32;
-        avg_pos[2] /= len as f32;
+        let avg_pos = self.positions.iter().copied().sum::<Vec3A>() / self.num_particles as f32;
 
         // find max distance from avg position
-        let mut max_dist = 0.0;
-        for pos in self.positions.chunks_exact(3) {
-            
-            let dist = (pos[0] - avg_pos[0]).powi(2)
-                 + (pos[1] - avg_pos[1]).powi(2)
-                 + (pos[2] - avg_pos[2]).powi(2);
-             if dist > max_dist {
-                 max_dist = dist;
-             }
-        }
+        let max_dist = self
+            .positions
+            .iter()
+            .map(|pos| pos.distance_squared(avg_pos))
+            .fold(0.0, f32::max);
         self.radius = max_dist.sqrt();
-        trans.translation = avg_pos;
-
-    // update positions to be relative to the center of the mesh
-    //     for pos in self.positions.chunks_exact_mut(3) {
-    //          pos[0] -= avg_pos[0];
-    //          pos[1] -= avg_pos[1];
-    //          pos[2] -= avg_pos[2];
-    //     }
-
-    //     for pos in self.prev_positions.chunks_exact_mut(3) {
-    //         pos[0] -= avg_pos[0];
-    //         pos[1] -= avg_pos[1];
-    //         pos[2] -= avg_pos[2];
-    //    }
-
-    //     for pos in self.visual_vertices.chunks_exact_mut(3) {
-    //         pos[0] -= avg_pos[0];
-    //         pos[1] -= avg_pos[1];
-    //         pos[2] -= avg_pos[2];
-    //     }
-
+        trans.translation = avg_pos.into();
     }
 
     pub fn update_tet_mesh(&mut self, trans: &Transform, mesh: &mut Mesh) {
-        
-        let positions = mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION).unwrap();
-        for i in 0..(self.positions.len() / 3) {
-            match positions {
-                VertexAttributeValues::Float32x3(positions) => {
-                    positions[i][0] = self.positions[i * 3] - trans.translation.x;
-                    positions[i][1] = self.positions[i * 3 + 1] - trans.translation.y;
-                    positions[i][2] = self.positions[i * 3 + 2] - trans.translation.z;
+        let offset = Vec3A::from(trans.translation);
+        match mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION).unwrap() {
+            VertexAttributeValues::Float32x3(positions) => {
+                for (dst, &pos) in positions.iter_mut().zip(self.positions.iter()) {
+                    *dst = (pos - offset).to_array();
                 }
-                _ => panic!("Wrong attribute type"),
-            }            
-        }        
+            }
+            _ => panic!("Wrong attribute type"),
+        }
     }
 
     pub fn update_visual_mesh(&mut self, trans: &Transform, mesh: &mut Mesh) {
-        let mut nr = 0;
         for i in 0..self.num_vis_verts {
-            let mut tet_nr = (self.skinning_info[nr] * 4.0) as usize; // TODO: check this
-            nr += 1;
-            // dont think this can happen
-            // if tet_nr < 0 { 
-            //     // dont think this can happen
-            //     nr += 3;
-            //     continue;
-            // }
-            let b0 = self.skinning_info[nr];
-            nr += 1;
-            let b1 = self.skinning_info[nr];
-            nr += 1;
-            let b2 = self.skinning_info[nr];
-            nr += 1;
+            let tet_nr = self.skinning_info[4 * i] as usize;
+            let b0 = self.skinning_info[4 * i + 1];
+            let b1 = self.skinning_info[4 * i + 2];
+            let b2 = self.skinning_info[4 * i + 3];
             let b3 = 1.0 - b0 - b1 - b2;
-            let id0 = self.tet_ids[tet_nr];
-            tet_nr += 1;
-            let id1 = self.tet_ids[tet_nr];
-            tet_nr += 1;
-            let id2 = self.tet_ids[tet_nr];
-            tet_nr += 1;
-            let id3 = self.tet_ids[tet_nr];
-            //tet_nr += 1;
-            vecSetZero(&mut self.visual_vertices, i);
-            vecAdd(&mut self.visual_vertices, i, &self.positions, id0, b0);
-            vecAdd(&mut self.visual_vertices, i, &self.positions, id1, b1);
-            vecAdd(&mut self.visual_vertices, i, &self.positions, id2, b2);
-            vecAdd(&mut self.visual_vertices, i, &self.positions, id3, b3);
-        }
-
-
-        let indices = self.visual_indices.iter().map(|i| *i as u32).collect::<Vec<u32>>();
-        let positions = self
-            .visual_vertices
-            .chunks_exact(3)
-            .map(|v| [v[0] - trans.translation.x, v[1] - trans.translation.y, v[2] - trans.translation.z])
-            .collect::<Vec<[f32; 3]>>();
+            let p = self.tet_positions(tet_nr);
+            self.visual_vertices[i] = p[0] * b0 + p[1] * b1 + p[2] * b2 + p[3] * b3;
+        }
 
-        
-        mesh.set_indices(Some(Indices::U32(indices)));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.duplicate_vertices();
-        mesh.compute_flat_normals();
+        let offset = Vec3A::from(trans.translation);
+        let vertices = &self.visual_vertices;
+        write_flat_mesh(mesh, &self.visual_indices, self.num_vis_verts, |i| vertices[i] - offset);
     }
 
     // returns distance to closest point
@@ -520,21 +669,9 @@ impl SoftBody {
             let mut min_dist = f32::MAX;
             for i in 0..self.tet_ids.len() / 3 {
                 let index = i * 3;
-                let p0 = Vec3::new(
-                    self.positions[self.tet_ids[index] * 3],
-                    self.positions[self.tet_ids[index] * 3 + 1],
-                    self.positions[self.tet_ids[index] * 3 + 2],
-                );
-                let p1 = Vec3::new(
-                    self.positions[self.tet_ids[index + 1] * 3],
-                    self.positions[self.tet_ids[index + 1] * 3 + 1],
-                    self.positions[self.tet_ids[index + 1] * 3 + 2],
-                );
-                let p2 = Vec3::new(
-                    self.positions[self.tet_ids[index + 2] * 3],
-                    self.positions[self.tet_ids[index + 2] * 3 + 1],
-                    self.positions[self.tet_ids[index + 2] * 3 + 2],
-                );
+                let p0 = self.positions[self.tet_ids[index]].into();
+                let p1 = self.positions[self.tet_ids[index + 1]].into();
+                let p2 = self.positions[self.tet_ids[index + 2]].into();
                 if let Some(dist) = ray_triangle_intersect(ray, p0, p1, p2) {
                     if dist < min_dist {
                         min_dist = dist;
@@ -550,11 +687,11 @@ impl SoftBody {
     }
 
     pub fn start_grab(&mut self, pos: Vec3) {
-        let mut p = [pos.x, pos.y, pos.z];
+        let p = Vec3A::from(pos);
         let mut min_d2 = f32::MAX;
         self.grab_id = None;
         for i in 0..self.num_particles {
-            let d2 = vecDistSquared(&mut p, 0, &self.positions, i);
+            let d2 = p.distance_squared(self.positions[i]);
             if d2 < min_d2 {
                 min_d2 = d2;
                 self.grab_id = Some(i);
@@ -564,60 +701,38 @@ impl SoftBody {
         if let Some(index) = self.grab_id {
             self.grab_inv_mass = self.inv_mass[index];
             self.inv_mass[index] = 0.0;
-            vecCopy(&mut self.positions, index, &p, 0);
+            self.positions[index] = p;
         }
     }
 
     pub fn move_grabbed(&mut self, pos: Vec3, _vel: Vec3) {
         if let Some(index) = self.grab_id {
-            let p = [pos.x, pos.y, pos.z];
-            vecCopy(&mut self.positions, index, &p, 0);
+            self.positions[index] = pos.into();
         }
     }
 
     pub fn end_grab(&mut self, _pos: Vec3, vel: Vec3) {
         if let Some(index) = self.grab_id {
             self.inv_mass[index] = self.grab_inv_mass;
-            let v = [vel.x, vel.y, vel.z];
-            vecCopy(&mut self.velocities, index, &v, 0);
+            self.velocities[index] = vel.into();
         }
         self.grab_id = None;
     }
 
     pub fn update_mesh(&mut self, mesh: &mut Mesh) {
-        let indices = self.visual_indices.iter().map(|i| *i as u32).collect::<Vec<u32>>();
-        let positions = self
-            .visual_vertices
-            .chunks_exact(3)
-            .map(|v| [v[0], v[1], v[2]])
-            .collect::<Vec<[f32; 3]>>();
-
-        mesh.set_indices(Some(Indices::U32(indices)));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.duplicate_vertices();
-        mesh.compute_flat_normals();
+        *mesh = Mesh::from(&*self);
     }
 }
 
 impl From<&SoftBody> for Mesh {
     fn from(sb: &SoftBody) -> Self {
-        // let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        // var geometry = new THREE.BufferGeometry();
-        // geometry.setAttribute('position', new THREE.BufferAttribute(this.pos, 3));
-        // geometry.setIndex(tetMesh.edgeIds);
-        // var lineMaterial = new THREE.LineBasicMaterial({color: 0xffffff, linewidth: 2});
-        // this.tetMesh = new THREE.LineSegments(geometry, lineMaterial);
-        // this.tetMesh.visible = true;
-        // scene.add(this.tetMesh);
-        // this.tetMesh.visible = false;
-
         // generate mesh
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
         let indices = sb.visual_indices.iter().map(|i| *i as u32).collect::<Vec<u32>>();
         let positions = sb
             .visual_vertices
-            .chunks_exact(3)
-            .map(|v| [v[0], v[1], v[2]])
+            .iter()
+            .map(|v| v.to_array())
             .collect::<Vec<[f32; 3]>>();
 
         mesh.set_indices(Some(Indices::U32(indices)));
@@ -628,152 +743,313 @@ impl From<&SoftBody> for Mesh {
     }
 }
 
-#[allow(dead_code, non_snake_case)]
-fn vecSetZero(a: &mut [f32], anr: usize) {
-    let ar = anr * 3;
-    a[ar] = 0.0;
-    a[ar + 1] = 0.0;
-    a[ar + 2] = 0.0;
-}
+// greedy coloring, each constraint of `stride` particle ids gets the lowest color none of its
+// particles has yet, returns the constraint order sorted by color and where each color starts
+fn color_graph(ids: &[usize], stride: usize, num_particles: usize) -> (Vec<usize>, Vec<usize>) {
+    let num = ids.len() / stride;
+    let mut colors = vec![0; num];
+    let mut particle_colors: Vec<Vec<usize>> = vec![vec![]; num_particles];
+    let mut num_colors = 0;
+    let mut taken = vec![];
+
+    for c in 0..num {
+        taken.clear();
+        taken.resize(num_colors + 1, false);
+        for k in 0..stride {
+            for &color in particle_colors[ids[stride * c + k]].iter() {
+                taken[color] = true;
+            }
+        }
+        let color = taken.iter().position(|t| !t).unwrap();
+        num_colors = num_colors.max(color + 1);
+        colors[c] = color;
+        for k in 0..stride {
+            particle_colors[ids[stride * c + k]].push(color);
+        }
+    }
 
-#[allow(non_snake_case)]
-fn vecScale(a: &mut Vec<f32>, anr: usize, scale: f32) {
-    let ar = anr * 3;
-    a[ar] *= scale;
-    a[ar + 1] *= scale;
-    a[ar + 2] *= scale;
-}
+    // counting sort by color
+    let mut starts = vec![0; num_colors + 1];
+    for &color in colors.iter() {
+        starts[color + 1] += 1;
+    }
+    for i in 0..num_colors {
+        starts[i + 1] += starts[i];
+    }
+    let mut next = starts.clone();
+    let mut order = vec![0; num];
+    for c in 0..num {
+        order[next[colors[c]]] = c;
+        next[colors[c]] += 1;
+    }
 
-#[allow(non_snake_case)]
-fn vecCopy(a: &mut [f32], anr: usize, b: &[f32], bnr: usize) {
-    let ar = anr * 3;
-    let br = bnr * 3;
-    a[ar] = b[br];
-    a[ar + 1] = b[br + 1];
-    a[ar + 2] = b[br + 2];
+    (order, starts)
 }
 
-#[allow(non_snake_case)]
-fn vecAdd(a: &mut [f32], anr: usize, b: &[f32], bnr: usize, scale: f32) {
-    let ar = anr * 3;
-    let br = bnr * 3;
-    a[ar] += b[br] * scale;
-    a[ar + 1] += b[br + 1] * scale;
-    a[ar + 2] += b[br + 2] * scale;
+// splits start..end over the compute task pool
+fn par_ranges<R: Send + 'static>(
+    start: usize,
+    end: usize,
+    f: impl Fn(std::ops::Range<usize>) -> R + Send + Sync,
+) -> Vec<R> {
+    let pool = ComputeTaskPool::get();
+    let chunk_size = ((end - start) / pool.thread_num().max(1)).max(PARALLEL_MIN_CONSTRAINTS / 4);
+    let f = &f;
+    pool.scope(|scope| {
+        for chunk_start in (start..end).step_by(chunk_size) {
+            let chunk_end = (chunk_start + chunk_size).min(end);
+            scope.spawn(async move { f(chunk_start..chunk_end) });
+        }
+    })
 }
 
-#[allow(non_snake_case)]
-fn vecSetDiff(
-    dst: &mut [f32],
-    dnr: usize,
-    a: &[f32],
-    anr: usize,
-    b: &[f32],
-    bnr: usize,
-    scale: f32,
+// flat shaded triangle list, one mesh vertex per index so each face has its own normal, meshes
+// that already have that layout are written in place so nothing is allocated per frame
+pub fn write_flat_mesh(
+    mesh: &mut Mesh,
+    indices: &[usize],
+    num_points: usize,
+    point: impl Fn(usize) -> Vec3A,
 ) {
-    let dr = dnr * 3;
-    let ar = anr * 3;
-    let br = bnr * 3;
-    dst[dr] = (a[ar] - b[br]) * scale;
-    dst[dr + 1] = (a[ar + 1] - b[br + 1]) * scale;
-    dst[dr + 2] = (a[ar + 2] - b[br + 2]) * scale;
-}
+    let has_normals = matches!(
+        mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+        Some(VertexAttributeValues::Float32x3(_))
+    );
+    if mesh.count_vertices() != indices.len() || !has_normals {
+        // first frame or a mesh from somewhere else, duplicating keeps its other attributes
+        let positions = (0..num_points)
+            .map(|i| point(i).to_array())
+            .collect::<Vec<[f32; 3]>>();
+        mesh.set_indices(Some(Indices::U32(indices.iter().map(|&i| i as u32).collect())));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.duplicate_vertices();
+        mesh.compute_flat_normals();
+        return;
+    }
 
-#[allow(non_snake_case)]
-fn vecLengthSquared(a: &[f32], anr: usize) -> f32 {
-    let ar = anr * 3;
-    let a0 = a[ar];
-    let a1 = a[ar + 1];
-    let a2 = a[ar + 2];
-    return a0 * a0 + a1 * a1 + a2 * a2;
+    if let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+    {
+        for (dst, &id) in positions.iter_mut().zip(indices.iter()) {
+            *dst = point(id).to_array();
+        }
+    }
+    if let Some(VertexAttributeValues::Float32x3(normals)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL)
+    {
+        for (dst, tri) in normals.chunks_exact_mut(3).zip(indices.chunks_exact(3)) {
+            let p0 = point(tri[0]);
+            let n = (point(tri[1]) - p0).cross(point(tri[2]) - p0).normalize_or_zero();
+            dst.fill(n.to_array());
+        }
+    }
 }
 
-#[allow(non_snake_case)]
-fn vecDistSquared(a: &[f32], anr: usize, b: &[f32], bnr: usize) -> f32 {
-    let ar = anr * 3;
-    let br = bnr * 3;
-    let a0 = a[ar] - b[br];
-    let a1 = a[ar + 1] - b[br + 1];
-    let a2 = a[ar + 2] - b[br + 2];
-    return a0 * a0 + a1 * a1 + a2 * a2;
+fn to_vec3a(v: &[f32]) -> Vec<Vec3A> {
+    v.chunks_exact(3).map(Vec3A::from_slice).collect()
 }
 
-#[allow(non_snake_case)]
-fn vecDot(a: &[f32], anr: usize, b: &[f32], bnr: usize) -> f32 {
-    let ar = anr * 3;
-    let br = bnr * 3;
-    return a[ar] * b[br] + a[ar + 1] * b[br + 1] + a[ar + 2] * b[br + 2];
+fn tet_volume(p: [Vec3A; 4]) -> f32 {
+    (p[1] - p[0]).cross(p[2] - p[0]).dot(p[3] - p[0]) / 6.0
 }
 
-#[allow(non_snake_case)]
-fn vecSetCross(a: &mut [f32], anr: usize, b: &[f32], bnr: usize, c: &[f32], cnr: usize) {
-    let ar = anr * 3;
-    let br = bnr * 3;
-    let cr = cnr * 3;
-    a[ar] = b[br + 1] * c[cr + 2] - b[br + 2] * c[cr + 1];
-    a[ar + 1] = b[br + 2] * c[cr + 0] - b[br + 0] * c[cr + 2];
-    a[ar + 2] = b[br + 0] * c[cr + 1] - b[br + 1] * c[cr + 0];
+// returns the position changes instead of applying them, so colors can run in parallel
+// gamma damps the motion along the gradient since the last substep, see Damping::gamma
+fn edge_correction(
+    positions: &[Vec3A],
+    prev_positions: &[Vec3A],
+    inv_mass: &[f32],
+    ids: [usize; 2],
+    rest_len: f32,
+    alpha: f32,
+    gamma: f32,
+) -> Option<([usize; 2], [Vec3A; 2])> {
+    let w0 = inv_mass[ids[0]];
+    let w1 = inv_mass[ids[1]];
+    let w = w0 + w1;
+    if w == 0.0 {
+        return None;
+    }
+    let diff = positions[ids[0]] - positions[ids[1]];
+    let len = diff.length();
+    if len == 0.0 {
+        return None;
+    }
+    let grad = diff / len;
+    let moved = (positions[ids[0]] - prev_positions[ids[0]])
+        - (positions[ids[1]] - prev_positions[ids[1]]);
+    let s = -(len - rest_len + gamma * grad.dot(moved)) / ((1.0 + gamma) * w + alpha);
+    Some((ids, [grad * (s * w0), grad * (-s * w1)]))
 }
 
-#[allow(non_snake_case)]
-fn matGetDeterminant(a: &[f32; 9]) -> f32 {
-    let a11 = a[0];
-    let a12 = a[3];
-    let a13 = a[6];
-
-    let a21 = a[1];
-    let a22 = a[4];
-    let a23 = a[7];
+// same as edge_correction for the tet volume constraint
+fn volume_correction(
+    positions: &[Vec3A],
+    prev_positions: &[Vec3A],
+    inv_mass: &[f32],
+    ids: [usize; 4],
+    rest_vol: f32,
+    alpha: f32,
+    gamma: f32,
+) -> Option<([usize; 4], [Vec3A; 4])> {
+    let p = ids.map(|id| positions[id]);
+    let mut grads = [Vec3A::ZERO; 4];
+    let mut w = 0.0;
+    for j in 0..4 {
+        let p0 = p[VOLUME_ID_ORDER[j][0]];
+        let p1 = p[VOLUME_ID_ORDER[j][1]];
+        let p2 = p[VOLUME_ID_ORDER[j][2]];
+        grads[j] = (p1 - p0).cross(p2 - p0) / 6.0;
+        w += inv_mass[ids[j]] * grads[j].length_squared();
+    }
+    if w == 0.0 {
+        return None;
+    }
 
-    let a31 = a[2];
-    let a32 = a[5];
-    let a33 = a[8];
-    a11*a22*a33 + a12*a23*a31 + a13*a21*a32 - a13*a22*a31 - a12*a21*a33 - a11*a23*a32
+    let moved = (0..4)
+        .map(|j| grads[j].dot(p[j] - prev_positions[ids[j]]))
+        .sum::<f32>();
+    let s = -(tet_volume(p) - rest_vol + gamma * moved) / ((1.0 + gamma) * w + alpha);
+    let mut deltas = [Vec3A::ZERO; 4];
+    for j in 0..4 {
+        deltas[j] = grads[j] * (s * inv_mass[ids[j]]);
+    }
+    Some((ids, deltas))
 }
 
-#[allow(non_snake_case)]
-fn matSetMult(A: &[f32; 9], a: &mut [f32], anr: usize) {
-    // note: use to pass b: &[f32], bnr: usize, but was the same values as a and anr, can cant borrow mut and immutable at the same time
-    let bnr = anr * 3;  //bnr *= 3;     
-    let bx = a[bnr];
-    let by = a[bnr + 1];
-    let bz = a[bnr + 2];
-    vecSetZero(a, anr);
-    vecAdd(a,anr, A,0, bx);
-    vecAdd(a,anr, A,1, by);
-    vecAdd(a,anr, A,2, bz);
+// new rest value after yielding, works for lengths and (signed) volumes
+fn plastic_flow(
+    rest: f32,
+    current: f32,
+    orig: f32,
+    yield_strain: f32,
+    creep: f32,
+    max_plastic_strain: f32,
+) -> f32 {
+    if rest == 0.0 {
+        return rest;
+    }
+    let strain = (current - rest) / rest.abs();
+    if strain.abs() <= yield_strain {
+        return rest;
+    }
+    let excess = strain - yield_strain * strain.signum();
+    let rest = rest + excess * creep * rest.abs();
+
+    let limit = max_plastic_strain * orig.abs();
+    rest.clamp(orig - limit, orig + limit)
 }
 
-#[allow(non_snake_case)]
-fn matSetInverse(a: &mut [f32; 9]) {
-    let det = matGetDeterminant(a);
-    if det == 0.0 {
-        for i in 0..9 {
-            a[i] = 0.0; // this was 'anr + i', think it was a bug and just defaulted to 0
-            return;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::tasks::TaskPool;
+
+    // n³ cubes split into 6 tets each around the main diagonal, big enough that the larger
+    // colors go through the task pool
+    fn grid_tet_mesh(n: usize, spacing: f32) -> TetMesh {
+        let id = |x: usize, y: usize, z: usize| (x * (n + 1) + y) * (n + 1) + z;
+
+        let mut tet_vertices = vec![];
+        for x in 0..=n {
+            for y in 0..=n {
+                for z in 0..=n {
+                    tet_vertices.extend([x as f32, y as f32 + 1.0, z as f32].map(|c| c * spacing));
+                }
+            }
+        }
+
+        let mut tet_indices = vec![];
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n {
+                    let corner = |i: usize| id(x + (i & 1), y + ((i >> 1) & 1), z + (i >> 2));
+                    for path in [[1, 3], [1, 5], [2, 3], [2, 6], [4, 5], [4, 6]] {
+                        tet_indices.extend([corner(0), corner(path[0]), corner(path[1]), corner(7)]);
+                    }
+                }
+            }
+        }
+
+        let mut edges = tet_indices
+            .chunks_exact(4)
+            .flat_map(|t| {
+                [(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)]
+                    .map(|(a, b)| (t[a].min(t[b]), t[a].max(t[b])))
+            })
+            .collect::<Vec<_>>();
+        edges.sort();
+        edges.dedup();
+
+        TetMesh {
+            vertices: tet_vertices.clone(),
+            indices: vec![],
+            tet_vertices,
+            tet_indices,
+            tet_edge_ids: edges.iter().flat_map(|&(a, b)| [a, b]).collect(),
+            tet_vertex_colors: vec![],
         }
     }
-    let invDet = 1.0 / det;
-    let a11 = a[0];
-    let a12 = a[3]; 
-    let a13 = a[6];
-    
-    let a21 = a[1];
-    let a22 = a[4];
-    let a23 = a[7];
-
-    let a31 = a[2];
-    let a32 = a[5];
-    let a33 = a[8];
-
-    a[0] =  (a22 * a33 - a23 * a32) * invDet; 
-    a[3] = -(a12 * a33 - a13 * a32) * invDet;
-    a[6] =  (a12 * a23 - a13 * a22) * invDet;
-    a[1] = -(a21 * a33 - a23 * a31) * invDet;
-    a[4] =  (a11 * a33 - a13 * a31) * invDet;
-    a[7] = -(a11 * a23 - a13 * a21) * invDet;
-    a[2] =  (a21 * a32 - a22 * a31) * invDet;
-    a[5] = -(a11 * a32 - a12 * a31) * invDet;
-    a[8] =  (a11 * a22 - a12 * a21) * invDet;
-}
\ No newline at end of file
+
+    // squashed along x so every constraint has work to do
+    fn squashed_body(parallel: bool) -> SoftBody {
+        let mut sb = SoftBody::new(&grid_tet_mesh(10, 0.1), 0.0, 0.0);
+        for p in sb.positions.iter_mut() {
+            p.x *= 0.7;
+        }
+        sb.parallel = parallel;
+        sb
+    }
+
+    // the squashed body with its constraints put back in mesh order, the order the sequential
+    // solver used before coloring, so the reference doesn't share the colored order
+    fn uncolored_body() -> SoftBody {
+        let mesh = grid_tet_mesh(10, 0.1);
+        let mut sb = squashed_body(false);
+        sb.edge_ids = mesh.tet_edge_ids.clone();
+        sb.tet_ids = mesh.tet_indices.clone();
+        let rest = SoftBody::new(&mesh, 0.0, 0.0);
+        let rest = &rest.positions;
+        sb.edge_lengths = (0..sb.edge_lengths.len())
+            .map(|i| rest[sb.edge_ids[2 * i]].distance(rest[sb.edge_ids[2 * i + 1]]))
+            .collect();
+        sb.rest_volumn = (0..sb.num_tets)
+            .map(|i| tet_volume([0, 1, 2, 3].map(|j| rest[sb.tet_ids[4 * i + j]])))
+            .collect();
+        sb
+    }
+
+    fn max_deviation(a: &SoftBody, b: &SoftBody) -> f32 {
+        a.positions
+            .iter()
+            .zip(b.positions.iter())
+            .map(|(a, b)| a.distance(*b))
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn colored_parallel_solve_matches_sequential() {
+        ComputeTaskPool::init(TaskPool::default);
+
+        let mut reference = uncolored_body();
+        let mut sequential = squashed_body(false);
+        let mut parallel = squashed_body(true);
+        assert!(parallel.edge_color_starts.windows(2).any(|c| c[1] - c[0] >= PARALLEL_MIN_CONSTRAINTS));
+
+        // only the projection, so the bodies settle instead of bouncing off what they gained
+        let dt = 1.0 / 60.0 / 10.0;
+        for _ in 0..200 {
+            for sb in [&mut reference, &mut sequential, &mut parallel] {
+                sb.solve(dt);
+            }
+        }
+
+        // same order, the task pool alone shouldn't change anything
+        let deviation = max_deviation(&sequential, &parallel);
+        assert!(deviation < 1.0e-4, "colored order max deviation {}", deviation);
+        // a different order is a different gauss seidel iterate, but both should settle within
+        // a few millimeters of each other on the 1 m grid
+        let deviation = max_deviation(&reference, &parallel);
+        assert!(deviation < 1.0e-2, "mesh order max deviation {}", deviation);
+    }
+}
@@ -1,24 +1,39 @@
 use super::Keep;
+use crate::energy::{
+    ANGULAR_MOMENTUM, KINETIC_ENERGY, LINEAR_MOMENTUM, POTENTIAL_ENERGY, TOTAL_ENERGY,
+};
 use bevy::{
-    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics, FrameTimeDiagnosticsPlugin},
     prelude::*,
 };
 
+// time spent in the substep loop, simulate_softbody measures it
+pub const SOLVE_TIME: DiagnosticId = DiagnosticId::from_u128(158218410738316286915327127003185401893);
+
 pub struct TextOverlayPlugin;
 
 impl Plugin for TextOverlayPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(FrameTimeDiagnosticsPlugin::default())
+            .add_startup_system(setup_diagnostics)
             .add_startup_system(setup_overlay)
-            .add_system(update_fps);
+            .add_system(update_fps)
+            .add_system(update_energy);
     }
 }
 
 #[derive(Component)]
 struct FpsText;
 
+#[derive(Component)]
+struct EnergyText;
+
 const UI_SIZE: f32 = 20.0;
 
+fn setup_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(SOLVE_TIME, "solve_time", 20).with_suffix("ms"));
+}
+
 fn setup_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
     let ui_font = asset_server.load("fonts/FiraSans-Bold.ttf");
 
@@ -52,6 +67,22 @@ fn setup_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
                             color: Color::GREEN,
                         },
                     },
+                    TextSection {
+                        value: "  Solve: ".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::WHITE,
+                        },
+                    },
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::WHITE,
+                        },
+                    },
                 ],
                 ..Default::default()
             },
@@ -61,6 +92,62 @@ fn setup_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
         Keep,
         FpsText,
     ));
+
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(30.),
+                    ..Default::default()
+                },
+                align_self: AlignSelf::FlexEnd,
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: "Energy: ".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::WHITE,
+                        },
+                    },
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::WHITE,
+                        },
+                    },
+                    TextSection {
+                        value: "  Momentum: ".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::WHITE,
+                        },
+                    },
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::WHITE,
+                        },
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Name::new("ui Energy"),
+        Keep,
+        EnergyText,
+    ));
 }
 
 fn update_fps(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, With<FpsText>>) {
@@ -77,5 +164,32 @@ fn update_fps(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, With<Fp
                 };
             }
         }
+        if let Some(solve) = diagnostics.get(SOLVE_TIME) {
+            if let Some(average) = solve.average() {
+                text.sections[3].value = format!("{:.2} ms", average);
+            }
+        }
+    }
+}
+
+fn update_energy(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, With<EnergyText>>) {
+    let average = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.average())
+            .unwrap_or_default()
+    };
+    for mut text in query.iter_mut() {
+        text.sections[1].value = format!(
+            "{:.2} J (kinetic {:.2}, potential {:.2})",
+            average(TOTAL_ENERGY),
+            average(KINETIC_ENERGY),
+            average(POTENTIAL_ENERGY),
+        );
+        text.sections[3].value = format!(
+            "linear {:.3}, angular {:.3}",
+            average(LINEAR_MOMENTUM),
+            average(ANGULAR_MOMENTUM),
+        );
     }
 }
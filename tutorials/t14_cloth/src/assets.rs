@@ -47,3 +47,100 @@ impl AssetLoader for TetMeshLoader {
         &["tet.json"]
     }
 }
+
+// local material variation painted over a body, compliance and density multiply the body's own
+// values, damping is in 1/s
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaintSample {
+    pub compliance: f32,
+    pub damping: f32,
+    pub density: f32,
+}
+
+impl Default for PaintSample {
+    fn default() -> Self {
+        Self {
+            compliance: 1.0,
+            damping: 0.0,
+            density: 1.0,
+        }
+    }
+}
+
+impl PaintSample {
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            compliance: self.compliance + (other.compliance - self.compliance) * t,
+            damping: self.damping + (other.damping - self.damping) * t,
+            density: self.density + (other.density - self.density) * t,
+        }
+    }
+
+    pub fn mean(samples: &[Self]) -> Self {
+        let n = samples.len().max(1) as f32;
+        Self {
+            compliance: samples.iter().map(|s| s.compliance).sum::<f32>() / n,
+            damping: samples.iter().map(|s| s.damping).sum::<f32>() / n,
+            density: samples.iter().map(|s| s.density).sum::<f32>() / n,
+        }
+    }
+}
+
+// one sample per particle and optionally one per tet, a tet without its own sample uses the
+// mean of its particles, missing entries are the default sample
+#[derive(Debug, Clone, Default)]
+pub struct MaterialPaint {
+    pub vertices: Vec<PaintSample>,
+    pub tets: Vec<PaintSample>,
+}
+
+impl MaterialPaint {
+    // red, green and blue blend compliance, damping and density from low to high
+    pub fn from_vertex_colors(colors: &[[f32; 4]], low: PaintSample, high: PaintSample) -> Self {
+        let vertices = colors
+            .iter()
+            .map(|c| PaintSample {
+                compliance: low.lerp(high, c[0]).compliance,
+                damping: low.lerp(high, c[1]).damping,
+                density: low.lerp(high, c[2]).density,
+            })
+            .collect();
+        Self {
+            vertices,
+            tets: vec![],
+        }
+    }
+
+    // inner at center blending to outer at radius and beyond
+    pub fn radial(
+        points: &[Vec3],
+        center: Vec3,
+        radius: f32,
+        inner: PaintSample,
+        outer: PaintSample,
+    ) -> Self {
+        let vertices = points
+            .iter()
+            .map(|p| inner.lerp(outer, (p.distance(center) / radius).min(1.0)))
+            .collect();
+        Self {
+            vertices,
+            tets: vec![],
+        }
+    }
+
+    pub fn vertex(&self, i: usize) -> PaintSample {
+        self.vertices.get(i).copied().unwrap_or_default()
+    }
+
+    pub fn constraint(&self, ids: &[usize]) -> PaintSample {
+        PaintSample::mean(&ids.iter().map(|&i| self.vertex(i)).collect::<Vec<_>>())
+    }
+
+    pub fn tet(&self, nr: usize, ids: &[usize]) -> PaintSample {
+        self.tets
+            .get(nr)
+            .copied()
+            .unwrap_or_else(|| self.constraint(ids))
+    }
+}
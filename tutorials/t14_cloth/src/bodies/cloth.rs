@@ -1,6 +1,8 @@
 use bevy::{
+    math::Vec3A,
     prelude::*,
     reflect::TypeUuid,
+    utils::HashMap,
     render::{
         mesh::{Indices, VertexAttributeValues},
         render_resource::PrimitiveTopology,
@@ -9,8 +11,12 @@ use bevy::{
 use bevy_inspector_egui::prelude::*;
 
 use crate::{
+    assets::MaterialPaint,
+    bodies::{BodyMaterial, Damping},
+    ccd::{edge_edge_toi, point_triangle_toi},
+    energy::Energy,
     intersect::{ray_sphere_intersect, ray_triangle_intersect},
-    math::*,
+    spatial_hash::SpatialHash,
 };
 
 #[derive(Reflect, Default, InspectorOptions, TypeUuid)]
@@ -19,31 +25,84 @@ pub struct Cloth {
     num_particles: usize,
     num_triangles: usize,
     indices: Vec<usize>,
-    positions: Vec<f32>,
-    prev_positions: Vec<f32>,
-    rest_positions: Vec<f32>,
-    velocities: Vec<f32>,
+    positions: Vec<Vec3A>,
+    prev_positions: Vec<Vec3A>,
+    rest_positions: Vec<Vec3A>,
+    velocities: Vec<Vec3A>,
     inv_mass: Vec<f32>,
+    masses: Vec<f32>,
+    pinned: Vec<bool>,
+    material: BodyMaterial,
+
+    // painted variation, compliance scales per constraint, damping and density per particle
+    stretching_scales: Vec<f32>,
+    bending_scales: Vec<f32>,
+    stitch_scales: Vec<f32>,
+    paint_damping: Vec<f32>,
+    density_scales: Vec<f32>,
 
     #[inspector(min = 0., max = 100.)]
     bending_compliance: f32,
     #[inspector(min = 0., max = 1.)]
     stretching_compliance: f32,
+    #[inspector(min = 0., max = 1.)]
+    stitch_compliance: f32,
+    pub damping: Damping,
 
     bending_ids: Vec<usize>,
     bending_lengths: Vec<f32>,
     stretching_ids: Vec<usize>,
     stretching_lengths: Vec<f32>,
+    stitch_ids: Vec<usize>,
 
-    temp: Vec<f32>,
-    grads: Vec<f32>,
+    // pressure, only for closed surfaces
+    closed: bool,
+    #[inspector(min = 0., max = 10.)]
+    pub pressure: f32,
+    #[inspector(min = 0., max = 1.)]
+    pub volume_compliance: f32,
+    rest_volume: f32,
+    volume_grads: Vec<Vec3A>,
 
     grab_id: Option<usize>,
     grab_inv_mass: f32,
 
+    // rendering, welding only joins particles, vertices on a uv seam keep one render vertex per
+    // side that maps back to the shared particle
+    render_ids: Vec<usize>,
+    render_indices: Vec<usize>,
+    uvs: Vec<[f32; 2]>,
+    // welded vertex colors of the panels, empty when none of them had any
+    colors: Vec<[f32; 4]>,
+    pub double_sided: bool,
+    normals: Vec<Vec3A>,
+
+    // continuous collision
+    #[inspector(min = 0., max = 0.1)]
+    pub thickness: f32,
+    #[reflect(ignore)]
+    tri_hash: SpatialHash,
+    ccd_tri_radius: f32,
+
     pub radius: f32, // for raycasting
 }
 
+pub struct ClothPanel<'a> {
+    pub mesh: &'a Mesh,
+    pub offset: Transform,
+}
+
+// sews vertex a[i] of panel_a to vertex b[i] of panel_b
+pub struct Seam {
+    pub panel_a: usize,
+    pub a: Vec<usize>,
+    pub panel_b: usize,
+    pub b: Vec<usize>,
+}
+
+// vertices closer than this are merged into one particle
+const WELD_DISTANCE: f32 = 1e-5;
+
 #[derive(Clone, Copy)]
 struct Edge {
     id0: usize,
@@ -53,31 +112,116 @@ struct Edge {
 impl Cloth {
 
     pub fn new(mesh: &Mesh, bending_compliance: f32, offset: &Transform, pin_indices: &[usize]) -> Self {
-        let vertices = mesh
-            .attribute(Mesh::ATTRIBUTE_POSITION)
-            .expect("Cloth mesh requires ATTRIBUTE_POSITION");
-        let positions = match vertices {
-            VertexAttributeValues::Float32x3(positions) => {
-                let x = positions.clone().to_vec();
-                x
+        let pins = pin_indices.iter().map(|&i| (0, i)).collect::<Vec<_>>();
+        Self::sewn(
+            &[ClothPanel {
+                mesh,
+                offset: *offset,
+            }],
+            &[],
+            bending_compliance,
+            &pins,
+        )
+    }
+
+    // builds one cloth out of any number of triangle meshes, duplicate vertices are welded
+    // (within and across panels) and seams add stitch constraints that pull the panels together,
+    // pins are (panel, vertex) pairs in the panel's own vertex numbering
+    pub fn sewn(
+        panels: &[ClothPanel],
+        seams: &[Seam],
+        bending_compliance: f32,
+        pin_indices: &[(usize, usize)],
+    ) -> Self {
+        let mut positions = vec![];
+        let mut uvs = vec![];
+        let mut colors = vec![];
+        let mut has_colors = false;
+        let mut indices = vec![];
+        let mut panel_starts = vec![];
+
+        for panel in panels.iter() {
+            let start = positions.len();
+            panel_starts.push(start);
+
+            let vertices = panel
+                .mesh
+                .attribute(Mesh::ATTRIBUTE_POSITION)
+                .expect("Cloth mesh requires ATTRIBUTE_POSITION");
+            let panel_positions = match vertices {
+                VertexAttributeValues::Float32x3(positions) => positions
+                    .iter()
+                    .map(|&v| Vec3A::from(panel.offset.transform_point(Vec3::from(v))))
+                    .collect::<Vec<_>>(),
+                _ => panic!("Wrong attribute type"),
+            };
+
+            let panel_uvs = match panel.mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+                Some(VertexAttributeValues::Float32x2(uvs)) => uvs.clone(),
+                _ => grid_uvs(&panel_positions),
+            };
+
+            match panel.mesh.indices().expect("Cloth mesh requires indices") {
+                Indices::U16(v) => indices.extend(v.iter().map(|&i| start + i as usize)),
+                Indices::U32(v) => indices.extend(v.iter().map(|&i| start + i as usize)),
+            };
+
+            positions.extend(panel_positions);
+            uvs.extend(panel_uvs);
+
+            match panel.mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+                Some(VertexAttributeValues::Float32x4(panel_colors)) => {
+                    has_colors = true;
+                    colors.extend_from_slice(panel_colors);
+                }
+                _ => colors.resize(positions.len(), [1.0; 4]),
             }
-            _ => panic!("Wrong attribute type"),
         }
-        .iter()
-        .map(|v| {
-            offset
-                .transform_point(Vec3::new(v[0], v[1], v[2]))
-                .to_array()
-        })
-        .flatten()
-        .collect::<Vec<_>>();
 
-        let indices = match mesh.indices().expect("Cloth mesh requires indices") {
-            Indices::U16(v) => v.iter().map(|&i| i as usize).collect::<Vec<_>>(),
-            Indices::U32(v) => v.iter().map(|&i| i as usize).collect::<Vec<_>>(),
+        let (remap, firsts) = weld(&positions);
+        let positions = firsts.iter().map(|&i| positions[i]).collect::<Vec<_>>();
+        let colors = if has_colors {
+            firsts.iter().map(|&i| colors[i]).collect::<Vec<_>>()
+        } else {
+            vec![]
         };
 
-        let num_particles = positions.len() / 3;
+        // welded vertices with the same uv share a render vertex
+        let mut render_map = HashMap::new();
+        let mut render_ids = vec![];
+        let mut render_uvs = vec![];
+        let render_remap = remap
+            .iter()
+            .zip(uvs.iter())
+            .map(|(&id, uv)| {
+                *render_map
+                    .entry((id, uv[0].to_bits(), uv[1].to_bits()))
+                    .or_insert_with(|| {
+                        render_ids.push(id);
+                        render_uvs.push(*uv);
+                        render_ids.len() - 1
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        // drop triangles that collapsed while welding
+        let tris = indices
+            .chunks_exact(3)
+            .filter(|tri| {
+                let [a, b, c] = [remap[tri[0]], remap[tri[1]], remap[tri[2]]];
+                a != b && b != c && c != a
+            })
+            .collect::<Vec<_>>();
+        let indices = tris
+            .iter()
+            .flat_map(|tri| tri.iter().map(|&i| remap[i]))
+            .collect::<Vec<_>>();
+        let render_indices = tris
+            .iter()
+            .flat_map(|tri| tri.iter().map(|&i| render_remap[i]))
+            .collect::<Vec<_>>();
+
+        let num_particles = positions.len();
         let num_triangles = indices.len() / 3;
         let mut result = Self {
             num_particles,
@@ -86,60 +230,104 @@ impl Cloth {
             positions: positions.clone(),
             prev_positions: positions.clone(),
             rest_positions: positions.clone(),
-            velocities: vec![0.0; 3 * num_particles],
+            velocities: vec![Vec3A::ZERO; num_particles],
             inv_mass: vec![0.0; num_particles],
+            masses: vec![0.0; num_particles],
+            pinned: vec![false; num_particles],
+            material: BodyMaterial::default(),
+            stretching_scales: vec![],
+            bending_scales: vec![],
+            stitch_scales: vec![],
+            paint_damping: vec![0.0; num_particles],
+            density_scales: vec![1.0; num_particles],
             bending_compliance,
             stretching_compliance: 0.01,
-            temp: vec![0.0; 4 * 3],
-            grads: vec![0.0; 4 * 3],
+            stitch_compliance: 0.001,
+            damping: Damping::default(),
             grab_id: None,
             grab_inv_mass: 0.,
+            render_ids,
+            render_indices,
+            uvs: render_uvs,
+            colors,
+            double_sided: true,
+            normals: vec![Vec3A::ZERO; num_particles],
+            thickness: 0.01,
+            tri_hash: SpatialHash::new(1.0, num_triangles),
+            ccd_tri_radius: 0.0,
             radius: 0.0,
 
             stretching_ids: vec![],
             stretching_lengths: vec![],
             bending_ids: vec![],
             bending_lengths: vec![],
+            stitch_ids: vec![],
+            closed: false,
+            pressure: 0.0,
+            volume_compliance: 0.0,
+            rest_volume: 0.0,
+            volume_grads: vec![Vec3A::ZERO; num_particles],
         };
 
         let neighors = result.find_tri_neighbors();
-        let num_tris = result.indices.len() / 3;
+        result.closed = num_triangles > 0 && neighors.iter().all(|n| n.is_some());
         let mut edge_ids = vec![];
         let mut tri_pair_ids = vec![];
 
-        for i in 0..num_tris {
+        for i in 0..num_triangles {
             for j in 0..3 {
                 let id0 = result.indices[3 * i + j];
                 let id1 = result.indices[3 * i + (j + 1) % 3];
 
-                // each edge only once
-                let n = neighors[3 * i + j];
-                if n < 0 || id0 < id1 {
-                    edge_ids.push(id0);
-                    edge_ids.push(id1);
+                // each edge only once, open and non-manifold edges have no neighbor
+                // and show up once per triangle, they get deduped below
+                edge_ids.push((id0.min(id1), id0.max(id1)));
+
+                // tri pair, once from the lower numbered triangle
+                if let Some(n) = neighors[3 * i + j] {
+                    if i < n / 3 {
+                        // opposite ids
+                        let ni = n / 3;
+                        let nj = n % 3;
+                        let id2 = result.indices[3 * i + (j + 2) % 3];
+                        let id3 = result.indices[3 * ni + (nj + 2) % 3];
+                        tri_pair_ids.push(id0);
+                        tri_pair_ids.push(id1);
+                        tri_pair_ids.push(id2);
+                        tri_pair_ids.push(id3);
+                    }
                 }
-                // tri pair
-                if n >= 0 {
-                    // opposite ids
-                    let ni = (n / 3) as usize;
-                    let nj = (n % 3) as usize;
-                    let id2 = result.indices[3 * i + (j + 2) % 3];
-                    let id3 = result.indices[3 * ni + (nj + 2) % 3];
-                    tri_pair_ids.push(id0);
-                    tri_pair_ids.push(id1);
-                    tri_pair_ids.push(id2);
-                    tri_pair_ids.push(id3);
+            }
+        }
+        edge_ids.sort_unstable();
+        edge_ids.dedup();
+
+        // seams, welding may already have joined both sides
+        for seam in seams.iter() {
+            for (&a, &b) in seam.a.iter().zip(seam.b.iter()) {
+                let id0 = remap[panel_starts[seam.panel_a] + a];
+                let id1 = remap[panel_starts[seam.panel_b] + b];
+                if id0 != id1 {
+                    result.stitch_ids.push(id0);
+                    result.stitch_ids.push(id1);
                 }
             }
         }
 
-        result.stretching_ids = edge_ids;
+        result.stretching_ids = edge_ids.iter().flat_map(|&(a, b)| [a, b]).collect();
         result.bending_ids = tri_pair_ids;
         result.stretching_lengths = vec![0.0; result.stretching_ids.len() / 2];
+        result.bending_lengths = vec![0.0; result.bending_ids.len() / 4];
+        result.stretching_scales = vec![1.0; result.stretching_lengths.len()];
+        result.bending_scales = vec![1.0; result.bending_lengths.len()];
+        result.stitch_scales = vec![1.0; result.stitch_ids.len() / 2];
         result.init_physics();
 
-        result.pin_indices(pin_indices);
-        // 
+        let pins = pin_indices
+            .iter()
+            .map(|&(panel, i)| remap[panel_starts[panel] + i])
+            .collect::<Vec<_>>();
+        result.pin_indices(&pins);
         result
     }
 
@@ -151,61 +339,160 @@ impl Cloth {
     }
 
     pub fn init_physics(&mut self) {
-        let num_tris = self.indices.len() / 3;
-        let mut e0 = [0f32; 3];
-        let mut e1 = [0f32; 3];
-        let mut c = [0f32; 3];
-
-        for i in 0..num_tris {
-            let id0 = self.indices[3 * i];
-            let id1 = self.indices[3 * i + 1];
-            let id2 = self.indices[3 * i + 2];
-            vecSetDiff(&mut e0, 0, &self.positions, id1, &self.positions, id0, 1.0);
-            vecSetDiff(&mut e1, 0, &self.positions, id2, &self.positions, id0, 1.0);
-            vecSetCross(&mut c, 0, &e0, 0, &e1, 0);
-            let a = 0.5 * vecLengthSquared(&c, 0).sqrt();
-            let p_inv_mass = if a > 0.0 { 1.0 / (a * 3.0) } else { 0.0 };
-            self.inv_mass[id0] += p_inv_mass;
-            self.inv_mass[id1] += p_inv_mass;
-            self.inv_mass[id2] += p_inv_mass;
-        }
+        self.update_masses();
 
         for i in 0..self.stretching_lengths.len() {
             let id0 = self.stretching_ids[2 * i];
             let id1 = self.stretching_ids[2 * i + 1];
-            self.stretching_lengths[i] =
-                vecDistSquared(&self.positions, id0, &self.positions, id1).sqrt();
+            self.stretching_lengths[i] = self.positions[id0].distance(self.positions[id1]);
         }
 
         for i in 0..self.bending_lengths.len() {
             let id0 = self.bending_ids[4 * i + 2];
             let id1 = self.bending_ids[4 * i + 3];
-            self.bending_lengths[i] =
-                vecDistSquared(&self.positions, id0, &self.positions, id1).sqrt();
+            self.bending_lengths[i] = self.positions[id0].distance(self.positions[id1]);
+        }
+
+        self.rest_volume = self.volume();
+    }
+
+    // a third of every triangle's area goes to each of its particles, pinned and grabbed
+    // particles stay pinned
+    fn update_masses(&mut self) {
+        self.masses.fill(0.0);
+        for tri in self.indices.chunks_exact(3) {
+            let e0 = self.positions[tri[1]] - self.positions[tri[0]];
+            let e1 = self.positions[tri[2]] - self.positions[tri[0]];
+            let a = 0.5 * e0.cross(e1).length();
+            for &id in tri.iter() {
+                self.masses[id] += a * self.material.areal_density * self.density_scales[id] / 3.0;
+            }
+        }
+
+        let particles = self.inv_mass.iter_mut().zip(&self.masses).zip(&self.pinned);
+        for (i, ((inv_mass, &m), &pinned)) in particles.enumerate() {
+            let w = if pinned || m <= 0.0 { 0.0 } else { 1.0 / m };
+            if self.grab_id == Some(i) {
+                self.grab_inv_mass = w;
+            } else {
+                *inv_mass = w;
+            }
+        }
+    }
+
+    // varies compliance, damping and density over the cloth, the paint is indexed by particle
+    pub fn paint(&mut self, paint: &MaterialPaint) {
+        self.stretching_scales = self
+            .stretching_ids
+            .chunks_exact(2)
+            .map(|ids| paint.constraint(ids).compliance)
+            .collect();
+        self.bending_scales = self
+            .bending_ids
+            .chunks_exact(4)
+            .map(|ids| paint.constraint(&ids[2..]).compliance)
+            .collect();
+        self.stitch_scales = self
+            .stitch_ids
+            .chunks_exact(2)
+            .map(|ids| paint.constraint(ids).compliance)
+            .collect();
+        for i in 0..self.num_particles {
+            let sample = paint.vertex(i);
+            self.paint_damping[i] = sample.damping;
+            self.density_scales[i] = sample.density;
         }
+        self.update_masses();
+    }
+
+    // in particle order, for MaterialPaint::from_vertex_colors
+    pub fn vertex_colors(&self) -> Option<&[[f32; 4]]> {
+        if self.colors.is_empty() {
+            None
+        } else {
+            Some(&self.colors)
+        }
+    }
+
+    pub fn material(&self) -> BodyMaterial {
+        self.material
+    }
+
+    pub fn set_material(&mut self, material: BodyMaterial) {
+        self.material = material;
+        self.update_masses();
+    }
+
+    // kg, pinned particles included
+    pub fn total_mass(&self) -> f32 {
+        self.masses.iter().sum()
+    }
+
+    pub fn center_of_mass(&self) -> Vec3 {
+        let total = self.total_mass();
+        if total == 0.0 {
+            return Vec3::ZERO;
+        }
+        let weighted = self
+            .positions
+            .iter()
+            .zip(self.masses.iter())
+            .map(|(&p, &m)| p * m)
+            .sum::<Vec3A>();
+        (weighted / total).into()
+    }
+
+    pub fn energy(&self, gravity: Vec3) -> Energy {
+        self.positions
+            .iter()
+            .zip(self.velocities.iter())
+            .zip(self.masses.iter())
+            .map(|((&p, &v), &m)| Energy::particle(m, p.into(), v.into(), gravity))
+            .sum()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    // signed volume enclosed by the triangles, positive when they wind counter clockwise seen
+    // from outside
+    pub fn volume(&self) -> f32 {
+        let mut vol = 0.0;
+        for tri in self.indices.chunks_exact(3) {
+            let p0 = self.positions[tri[0]];
+            let p1 = self.positions[tri[1]];
+            let p2 = self.positions[tri[2]];
+            vol += p0.cross(p1).dot(p2);
+        }
+        vol / 6.0
     }
 
     pub fn pin_indices(&mut self, indices: &[usize]) {
         for i in 0..indices.len() {            
             self.inv_mass[indices[i]] = 0.0;                            
+            self.pinned[indices[i]] = true;
         }
     }
     pub fn pre_solve(&mut self, dt: f32, gravity: Vec3) {
-        for i in 0..self.num_particles {
-            if self.inv_mass[i] == 0.0 {
+        let gravity = Vec3A::from(gravity) * dt;
+        for (((pos, prev), vel), &w) in self
+            .positions
+            .iter_mut()
+            .zip(self.prev_positions.iter_mut())
+            .zip(self.velocities.iter_mut())
+            .zip(self.inv_mass.iter())
+        {
+            // pinned and grabbed particles still need a start position for ccd sweeps
+            *prev = *pos;
+            if w == 0.0 {
                 continue;
             }
-            //vecAdd(this.vel,i, gravity,0, dt);
-            self.velocities[i * 3] += gravity[0] * dt;
-            self.velocities[i * 3 + 1] += gravity[1] * dt;
-            self.velocities[i * 3 + 2] += gravity[2] * dt;
-
-            vecCopy(&mut self.prev_positions, i, &self.positions, i);
-            vecAdd(&mut self.positions, i, &self.velocities, i, dt);
-            let y = self.positions[3 * i + 1];
-            if y < 0.0 {
-                vecCopy(&mut self.positions, i, &self.prev_positions, i);
-                self.positions[3 * i + 1] = 0.0;
+            *vel += gravity;
+            *pos += *vel * dt;
+            if pos.y < 0.0 {
+                *pos = *prev;
+                pos.y = 0.0;
             }
         }
     }
@@ -213,23 +500,179 @@ impl Cloth {
     pub fn solve(&mut self, dt: f32) {
         self.solve_stretching(dt);
         self.solve_bending(dt);
+        self.solve_stitches(dt);
+        self.solve_pressure(dt);
     }
 
     pub fn post_solve(&mut self, dt: f32) {
+        let inv_dt = 1.0 / dt;
+        for ((((vel, pos), prev), &w), &damping) in self
+            .velocities
+            .iter_mut()
+            .zip(self.positions.iter())
+            .zip(self.prev_positions.iter())
+            .zip(self.inv_mass.iter())
+            .zip(self.paint_damping.iter())
+        {
+            if w == 0.0 {
+                continue;
+            }
+            *vel = (*pos - *prev) * inv_dt;
+            if damping > 0.0 {
+                *vel *= (1.0 - damping * dt).max(0.0);
+            }
+        }
+        self.damping.apply(
+            &self.positions,
+            &mut self.velocities,
+            &self.masses,
+            &self.inv_mass,
+            dt,
+        );
+    }
+
+    // sweeps every particle against every nearby triangle and every edge against nearby edges
+    // over the last substep, colliding pairs are moved back to their time of impact, call after
+    // solve and before post_solve so velocities pick up the stop
+    pub fn solve_ccd(&mut self) {
+        self.update_ccd_hash();
+
+        // particle - triangle
         for i in 0..self.num_particles {
             if self.inv_mass[i] == 0.0 {
                 continue;
             }
-            vecSetDiff(
-                &mut self.velocities,
-                i,
-                &self.positions,
-                i,
-                &self.prev_positions,
-                i,
-                1.0 / dt,
-            );
+            let p = self.sweep(i);
+            let max_dist = p[0].distance(p[1]) + self.ccd_tri_radius + self.thickness;
+            self.tri_hash.query(&p[1].to_array(), 0, max_dist);
+
+            for k in 0..self.tri_hash.query_size {
+                let tri = self.tri_hash.query_ids[k];
+                let id0 = self.indices[3 * tri];
+                let id1 = self.indices[3 * tri + 1];
+                let id2 = self.indices[3 * tri + 2];
+                if i == id0 || i == id1 || i == id2 {
+                    continue;
+                }
+
+                if let Some(t) = point_triangle_toi(
+                    self.sweep(i),
+                    self.sweep(id0),
+                    self.sweep(id1),
+                    self.sweep(id2),
+                    self.thickness,
+                ) {
+                    for id in [i, id0, id1, id2] {
+                        self.roll_back(id, t);
+                    }
+                }
+            }
+        }
+
+        // edge - edge
+        for tri in 0..self.num_triangles {
+            for j in 0..3 {
+                let id0 = self.indices[3 * tri + j];
+                let id1 = self.indices[3 * tri + (j + 1) % 3];
+                let e0 = self.sweep(id0);
+                let e1 = self.sweep(id1);
+                let center = (e0[1] + e1[1]) * 0.5;
+                let edge_radius = [e0[0], e0[1], e1[0], e1[1]]
+                    .iter()
+                    .map(|p| p.distance(center))
+                    .fold(0.0, f32::max);
+                let max_dist = edge_radius + self.ccd_tri_radius + self.thickness;
+                self.tri_hash.query(&center.to_array(), 0, max_dist);
+
+                for k in 0..self.tri_hash.query_size {
+                    let other = self.tri_hash.query_ids[k];
+                    // each triangle pair once, shared edges show up twice which is harmless
+                    if other <= tri {
+                        continue;
+                    }
+                    for l in 0..3 {
+                        let id2 = self.indices[3 * other + l];
+                        let id3 = self.indices[3 * other + (l + 1) % 3];
+                        if id2 == id0 || id2 == id1 || id3 == id0 || id3 == id1 {
+                            continue;
+                        }
+
+                        if let Some(t) = edge_edge_toi(
+                            self.sweep(id0),
+                            self.sweep(id1),
+                            self.sweep(id2),
+                            self.sweep(id3),
+                            self.thickness,
+                        ) {
+                            for id in [id0, id1, id2, id3] {
+                                self.roll_back(id, t);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // earliest time of impact of a foreign particle moving from p[0] to p[1] with the cloth,
+    // the cloth is treated as a moving obstacle, solve_ccd must have been called this substep
+    pub fn sweep_point(&mut self, p: [Vec3; 2]) -> Option<f32> {
+        let max_dist = p[0].distance(p[1]) + self.ccd_tri_radius + self.thickness;
+        self.tri_hash.query(&p[1].to_array(), 0, max_dist);
+
+        let mut toi: Option<f32> = None;
+        for k in 0..self.tri_hash.query_size {
+            let tri = self.tri_hash.query_ids[k];
+            let a = self.sweep(self.indices[3 * tri]);
+            let b = self.sweep(self.indices[3 * tri + 1]);
+            let c = self.sweep(self.indices[3 * tri + 2]);
+            if let Some(t) = point_triangle_toi(p, a, b, c, self.thickness) {
+                toi = Some(toi.map_or(t, |min| min.min(t)));
+            }
+        }
+        toi
+    }
+
+    fn update_ccd_hash(&mut self) {
+        let max_disp = self
+            .positions
+            .iter()
+            .zip(self.prev_positions.iter())
+            .map(|(p, prev)| p.distance(*prev))
+            .fold(0.0, f32::max);
+
+        // bounding sphere around the swept triangle, centered at its end position
+        let mut centers = Vec::with_capacity(self.num_triangles);
+        let mut tri_radius = 0.0f32;
+        for tri in 0..self.num_triangles {
+            let sweeps = [
+                self.sweep(self.indices[3 * tri]),
+                self.sweep(self.indices[3 * tri + 1]),
+                self.sweep(self.indices[3 * tri + 2]),
+            ];
+            let center = (sweeps[0][1] + sweeps[1][1] + sweeps[2][1]) / 3.0;
+            for s in sweeps.iter() {
+                tri_radius = tri_radius.max(s[0].distance(center));
+                tri_radius = tri_radius.max(s[1].distance(center));
+            }
+            centers.push(center);
+        }
+        self.ccd_tri_radius = tri_radius;
+
+        // large enough that a query only touches a couple cells along each axis
+        self.tri_hash.spacing = (2.0 * (max_disp + tri_radius + self.thickness)).max(0.001);
+        self.tri_hash.create(&centers);
+    }
+
+    fn sweep(&self, i: usize) -> [Vec3; 2] {
+        [self.prev_positions[i].into(), self.positions[i].into()]
+    }
+
+    fn roll_back(&mut self, i: usize, t: f32) {
+        if self.inv_mass[i] == 0.0 {
+            return;
         }
+        self.positions[i] = self.prev_positions[i].lerp(self.positions[i], t);
     }
 
     fn solve_stretching(&mut self, dt: f32) {
@@ -238,32 +681,78 @@ impl Cloth {
         for i in 0..self.stretching_lengths.len() {
             let id0 = self.stretching_ids[2 * i];
             let id1 = self.stretching_ids[2 * i + 1];
-            let w0 = self.inv_mass[id0];
-            let w1 = self.inv_mass[id1];
-            let w = w0 + w1;
-            if w == 0.0 {
-                continue;
-            }
+            let rest_len = self.stretching_lengths[i];
+            let alpha = alpha * self.stretching_scales[i];
+            solve_distance(
+                &mut self.positions,
+                &self.prev_positions,
+                &self.inv_mass,
+                [id0, id1],
+                rest_len,
+                alpha,
+                self.damping.gamma(alpha, dt),
+            );
+        }
+    }
+
+    // zero rest length distance constraints between the two sides of a seam
+    fn solve_stitches(&mut self, dt: f32) {
+        let alpha = self.stitch_compliance / dt / dt;
 
-            vecSetDiff(
-                &mut self.grads,
-                0,
-                &self.positions,
-                id0,
-                &self.positions,
-                id1,
-                1.0,
+        for i in 0..self.stitch_ids.len() / 2 {
+            let id0 = self.stitch_ids[2 * i];
+            let id1 = self.stitch_ids[2 * i + 1];
+            let alpha = alpha * self.stitch_scales[i];
+            solve_distance(
+                &mut self.positions,
+                &self.prev_positions,
+                &self.inv_mass,
+                [id0, id1],
+                0.0,
+                alpha,
+                self.damping.gamma(alpha, dt),
             );
-            let len = vecLengthSquared(&self.grads, 0).sqrt();
-            if len == 0.0 {
-                continue;
+        }
+    }
+
+    // one global constraint keeping the enclosed volume at pressure * rest volume, the gradient
+    // for a particle is the sum of the area weighted normals of its triangles
+    fn solve_pressure(&mut self, dt: f32) {
+        if self.pressure <= 0.0 || !self.closed {
+            return;
+        }
+        let alpha = self.volume_compliance / dt / dt;
+
+        self.volume_grads.fill(Vec3A::ZERO);
+        for tri in self.indices.chunks_exact(3) {
+            let p0 = self.positions[tri[0]];
+            let p1 = self.positions[tri[1]];
+            let p2 = self.positions[tri[2]];
+            let grads = [p1.cross(p2), p2.cross(p0), p0.cross(p1)];
+            for j in 0..3 {
+                self.volume_grads[tri[j]] += grads[j] / 6.0;
             }
-            vecScale(&mut self.grads, 0, 1.0 / len);
-            let rest_len = self.stretching_lengths[i];
-            let c = len - rest_len;
-            let s = -c / (w + alpha);
-            vecAdd(&mut self.positions, id0, &self.grads, 0, s * w0);
-            vecAdd(&mut self.positions, id1, &self.grads, 0, -s * w1);
+        }
+
+        let w = self
+            .volume_grads
+            .iter()
+            .zip(self.inv_mass.iter())
+            .map(|(g, &w)| w * g.length_squared())
+            .sum::<f32>();
+        if w == 0.0 {
+            return;
+        }
+
+        let c = self.volume() - self.pressure * self.rest_volume;
+        let s = -c / (w + alpha);
+        for ((pos, grad), &w) in self
+            .positions
+            .iter_mut()
+            .zip(self.volume_grads.iter())
+            .zip(self.inv_mass.iter())
+        {
+            *pos += *grad * (s * w);
         }
     }
 
@@ -273,99 +762,136 @@ impl Cloth {
         for i in 0..self.bending_lengths.len() {
             let id0 = self.bending_ids[4 * i + 2];
             let id1 = self.bending_ids[4 * i + 3];
-            let w0 = self.inv_mass[id0];
-            let w1 = self.inv_mass[id1];
-            let w = w0 + w1;
-            if w == 0.0 {
-                continue;
-            }
-            vecSetDiff(
-                &mut self.grads,
-                0,
-                &self.positions,
-                id0,
-                &self.positions,
-                id1,
-                1.0,
-            );
-            let len = vecLengthSquared(&self.grads, 0).sqrt();
-            if len == 0.0 {
-                continue;
-            }
-            vecScale(&mut self.grads, 0, 1.0 / len);
             let rest_len = self.bending_lengths[i];
-            let c = len - rest_len;
-            let s = -c / (w + alpha);
-            vecAdd(&mut self.positions, id0, &self.grads, 0, s * w0);
-            vecAdd(&mut self.positions, id1, &self.grads, 0, -s * w1);
+            let alpha = alpha * self.bending_scales[i];
+            solve_distance(
+                &mut self.positions,
+                &self.prev_positions,
+                &self.inv_mass,
+                [id0, id1],
+                rest_len,
+                alpha,
+                self.damping.gamma(alpha, dt),
+            );
         }
     }
 
     // moves position changes to local space and updates transform position, call before update meshes
     pub fn update_transform(&mut self, trans: &mut Transform) {
         // find avg position and radius of the mesh
-        let mut avg_pos = Vec3::ZERO;
-        let len = self.positions.len() / 3;
-        for pos in self.positions.chunks_exact(3) {
-            avg_pos[0] += pos[0];
-            avg_pos[1] += pos[1];
-            avg_pos[2] += pos[2];
-        }
-        avg_pos[0] /= len as f32;
-        avg_pos[1] /= len as f32;
-        avg_pos[2] /= len as f32;
+        let avg_pos = self.positions.iter().copied().sum::<Vec3A>() / self.num_particles as f32;
 
         // find max distance from avg position
-        let mut max_dist = 0.0;
-        for pos in self.positions.chunks_exact(3) {
-            let dist = (pos[0] - avg_pos[0]).powi(2)
-                + (pos[1] - avg_pos[1]).powi(2)
-                + (pos[2] - avg_pos[2]).powi(2);
-            if dist > max_dist {
-                max_dist = dist;
+        let max_dist = self
+            .positions
+            .iter()
+            .map(|pos| pos.distance_squared(avg_pos))
+            .fold(0.0, f32::max);
+        self.radius = max_dist.sqrt();
+        trans.translation = avg_pos.into();
+    }
+
+    // writes positions and normals into the mesh buffers when the mesh already has this cloth's
+    // layout, only falls back to building the mesh when it doesn't (first frame, double_sided
+    // toggled)
+    pub fn update_visual_mesh(&mut self, trans: &Transform, mesh: &mut Mesh) {
+        let n = self.render_ids.len();
+        let num_verts = if self.double_sided { 2 * n } else { n };
+        let has_normals = matches!(
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+            Some(VertexAttributeValues::Float32x3(_))
+        );
+        if mesh.count_vertices() != num_verts || !has_normals {
+            self.write_mesh(mesh, trans.translation);
+            return;
+        }
+
+        let offset = Vec3A::from(trans.translation);
+        if let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+        {
+            for (i, dst) in positions.iter_mut().enumerate() {
+                *dst = (self.positions[self.render_ids[i % n]] - offset).to_array();
             }
         }
-        self.radius = max_dist.sqrt();
-        trans.translation = avg_pos;
 
-        // update positions to be relative to the center of the mesh
-        //     for pos in self.positions.chunks_exact_mut(3) {
-        //          pos[0] -= avg_pos[0];
-        //          pos[1] -= avg_pos[1];
-        //          pos[2] -= avg_pos[2];
-        //     }
+        let mut normals = std::mem::take(&mut self.normals);
+        self.accumulate_normals(&mut normals);
+        if let Some(VertexAttributeValues::Float32x3(dst)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL)
+        {
+            // the back copy gets the flipped normal
+            for (i, dst) in dst.iter_mut().enumerate() {
+                let normal = normals[self.render_ids[i % n]].normalize_or_zero();
+                *dst = if i < n { normal } else { -normal }.to_array();
+            }
+        }
+        self.normals = normals;
+    }
 
-        //     for pos in self.prev_positions.chunks_exact_mut(3) {
-        //         pos[0] -= avg_pos[0];
-        //         pos[1] -= avg_pos[1];
-        //         pos[2] -= avg_pos[2];
-        //    }
+    // area weighted average of the face normals, shared vertices stay shared so shading is smooth
+    pub fn vertex_normals(&self) -> Vec<[f32; 3]> {
+        let mut normals = vec![];
+        self.accumulate_normals(&mut normals);
+        normals
+            .iter()
+            .map(|n| n.normalize_or_zero().to_array())
+            .collect()
+    }
 
-        //     for pos in self.visual_vertices.chunks_exact_mut(3) {
-        //         pos[0] -= avg_pos[0];
-        //         pos[1] -= avg_pos[1];
-        //         pos[2] -= avg_pos[2];
-        //     }
+    // sums the (unnormalized) face normals into each particle
+    fn accumulate_normals(&self, normals: &mut Vec<Vec3A>) {
+        normals.clear();
+        normals.resize(self.num_particles, Vec3A::ZERO);
+        for tri in self.indices.chunks_exact(3) {
+            let p0 = self.positions[tri[0]];
+            let n = (self.positions[tri[1]] - p0).cross(self.positions[tri[2]] - p0);
+            normals[tri[0]] += n;
+            normals[tri[1]] += n;
+            normals[tri[2]] += n;
+        }
     }
 
-    pub fn update_visual_mesh(&mut self, trans: &Transform, mesh: &mut Mesh) {
-        let indices = self.indices.iter().map(|i| *i as u32).collect::<Vec<u32>>();
-        let positions = self
-            .positions
-            .chunks_exact(3)
-            .map(|v| {
-                [
-                    v[0] - trans.translation.x,
-                    v[1] - trans.translation.y,
-                    v[2] - trans.translation.z,
-                ]
-            })
+    // positions are written relative to offset, with double_sided a copy of every vertex is
+    // appended with flipped normals and winding so the back face gets lit too
+    fn write_mesh(&self, mesh: &mut Mesh, offset: Vec3) {
+        let particle_normals = self.vertex_normals();
+        let normals = self
+            .render_ids
+            .iter()
+            .map(|&id| particle_normals[id])
             .collect::<Vec<[f32; 3]>>();
+        let offset = Vec3A::from(offset);
+        let mut positions = self
+            .render_ids
+            .iter()
+            .map(|&id| (self.positions[id] - offset).to_array())
+            .collect::<Vec<[f32; 3]>>();
+        let mut indices = self.render_indices.iter().map(|i| *i as u32).collect::<Vec<u32>>();
+        let mut uvs = self.uvs.clone();
+
+        let normals = if self.double_sided {
+            let n = self.render_ids.len() as u32;
+            positions.extend_from_within(..);
+            uvs.extend_from_within(..);
+            for tri in self.render_indices.chunks_exact(3) {
+                indices.push(tri[0] as u32 + n);
+                indices.push(tri[2] as u32 + n);
+                indices.push(tri[1] as u32 + n);
+            }
+            normals
+                .iter()
+                .copied()
+                .chain(normals.iter().map(|n| [-n[0], -n[1], -n[2]]))
+                .collect::<Vec<[f32; 3]>>()
+        } else {
+            normals
+        };
 
         mesh.set_indices(Some(Indices::U32(indices)));
         mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.duplicate_vertices();
-        mesh.compute_flat_normals();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
     }
 
     // returns distance to closest point
@@ -375,21 +901,9 @@ impl Cloth {
             let mut min_dist = f32::MAX;
             for i in 0..self.indices.len() / 3 {
                 let index = i * 3;
-                let p0 = Vec3::new(
-                    self.positions[self.indices[index] * 3],
-                    self.positions[self.indices[index] * 3 + 1],
-                    self.positions[self.indices[index] * 3 + 2],
-                );
-                let p1 = Vec3::new(
-                    self.positions[self.indices[index + 1] * 3],
-                    self.positions[self.indices[index + 1] * 3 + 1],
-                    self.positions[self.indices[index + 1] * 3 + 2],
-                );
-                let p2 = Vec3::new(
-                    self.positions[self.indices[index + 2] * 3],
-                    self.positions[self.indices[index + 2] * 3 + 1],
-                    self.positions[self.indices[index + 2] * 3 + 2],
-                );
+                let p0 = self.positions[self.indices[index]].into();
+                let p1 = self.positions[self.indices[index + 1]].into();
+                let p2 = self.positions[self.indices[index + 2]].into();
                 if let Some(dist) = ray_triangle_intersect(ray, p0, p1, p2) {
                     if dist < min_dist {
                         min_dist = dist;
@@ -405,11 +919,11 @@ impl Cloth {
     }
 
     pub fn start_grab(&mut self, pos: Vec3) {
-        let mut p = [pos.x, pos.y, pos.z];
+        let p = Vec3A::from(pos);
         let mut min_d2 = f32::MAX;
         self.grab_id = None;
         for i in 0..self.num_particles {
-            let d2 = vecDistSquared(&mut p, 0, &self.positions, i);
+            let d2 = p.distance_squared(self.positions[i]);
             if d2 < min_d2 {
                 min_d2 = d2;
                 self.grab_id = Some(i);
@@ -419,41 +933,31 @@ impl Cloth {
         if let Some(index) = self.grab_id {
             self.grab_inv_mass = self.inv_mass[index];
             self.inv_mass[index] = 0.0;
-            vecCopy(&mut self.positions, index, &p, 0);
+            self.positions[index] = p;
         }
     }
 
     pub fn move_grabbed(&mut self, pos: Vec3, _vel: Vec3) {
         if let Some(index) = self.grab_id {
-            let p = [pos.x, pos.y, pos.z];
-            vecCopy(&mut self.positions, index, &p, 0);
+            self.positions[index] = pos.into();
         }
     }
 
     pub fn end_grab(&mut self, _pos: Vec3, vel: Vec3) {
         if let Some(index) = self.grab_id {
             self.inv_mass[index] = self.grab_inv_mass;
-            let v = [vel.x, vel.y, vel.z];
-            vecCopy(&mut self.velocities, index, &v, 0);
+            self.velocities[index] = vel.into();
         }
         self.grab_id = None;
     }
 
     pub fn update_mesh(&mut self, mesh: &mut Mesh) {
-        let indices = self.indices.iter().map(|i| *i as u32).collect::<Vec<u32>>();
-        let positions = self
-            .positions
-            .chunks_exact(3)
-            .map(|v| [v[0], v[1], v[2]])
-            .collect::<Vec<[f32; 3]>>();
-
-        mesh.set_indices(Some(Indices::U32(indices)));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.duplicate_vertices();
-        mesh.compute_flat_normals();
+        self.write_mesh(mesh, Vec3::ZERO);
     }
 
-    fn find_tri_neighbors(&self) -> Vec<usize> {
+    // neighbor edge of every triangle edge, open edges and edges shared by more than two
+    // triangles (non-manifold) have none
+    fn find_tri_neighbors(&self) -> Vec<Option<usize>> {
         // create common edges
         let mut edges: Vec<Edge> = vec![];
 
@@ -470,34 +974,35 @@ impl Cloth {
         }
 
         // sort so common edges are next to each other
+        edges.sort_by_key(|e| (e.id0, e.id1));
 
-        edges.sort_by(|a, b| -> core::cmp::Ordering {
-            return if (a.id0 < b.id0) || (a.id0 == b.id0 && a.id1 < b.id1) {
-                core::cmp::Ordering::Less
-            } else {
-                core::cmp::Ordering::Greater
-            };
-        });
         // find matchign edges
-
-        let mut neighbors = vec![0usize; 3 * self.num_triangles];
-        //NOTE:  Was -1 for all as open edges are -1, but we want to be able to index into the array
+        let mut neighbors = vec![None; 3 * self.num_triangles];
+        let mut non_manifold = 0;
 
         let mut nr = 0;
-
         while nr < edges.len() {
             let e0 = edges[nr];
-            nr += 1;
-            if nr < edges.len() {
-                let e1 = edges[nr];
-                if e0.id0 == e1.id0 && e0.id1 == e1.id1 {
-                    neighbors[e0.edge_nr] = e1.edge_nr;
-                    neighbors[e1.edge_nr] = e0.edge_nr;
+            let mut end = nr + 1;
+            while end < edges.len() && edges[end].id0 == e0.id0 && edges[end].id1 == e0.id1 {
+                end += 1;
+            }
+            match end - nr {
+                1 => {}
+                2 => {
+                    let e1 = edges[nr + 1];
+                    neighbors[e0.edge_nr] = Some(e1.edge_nr);
+                    neighbors[e1.edge_nr] = Some(e0.edge_nr);
                 }
-                nr += 1;
+                _ => non_manifold += 1,
             }
+            nr = end;
+        }
+
+        if non_manifold > 0 {
+            warn!("Cloth mesh has {} non-manifold edges, no bending across them", non_manifold);
         }
-        return neighbors;
+        neighbors
     }
 }
 
@@ -505,17 +1010,155 @@ impl From<&Cloth> for Mesh {
     fn from(sb: &Cloth) -> Self {
         // generate mesh
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        let indices = sb.indices.iter().map(|i| *i as u32).collect::<Vec<u32>>();
-        let positions = sb
-            .positions
-            .chunks_exact(3)
-            .map(|v| [v[0], v[1], v[2]])
-            .collect::<Vec<[f32; 3]>>();
-
-        mesh.set_indices(Some(Indices::U32(indices)));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.duplicate_vertices();
-        mesh.compute_flat_normals();
+        sb.write_mesh(&mut mesh, Vec3::ZERO);
         mesh
     }
 }
+
+// merges vertices within WELD_DISTANCE, returns the old to new index map and for every new
+// vertex the first old vertex that went into it, that one keeps its color
+fn weld(positions: &[Vec3A]) -> (Vec<usize>, Vec<usize>) {
+    let mut cells: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    let mut remap = Vec::with_capacity(positions.len());
+    let mut firsts: Vec<usize> = vec![];
+
+    for (i, &p) in positions.iter().enumerate() {
+        let cell = (p / WELD_DISTANCE).floor();
+        let (cx, cy, cz) = (cell.x as i64, cell.y as i64, cell.z as i64);
+
+        // a close pair can straddle a cell border, so compare against the representatives of
+        // all neighbouring cells instead of trusting the cell key alone
+        let mut found = None;
+        'search: for x in cx - 1..=cx + 1 {
+            for y in cy - 1..=cy + 1 {
+                for z in cz - 1..=cz + 1 {
+                    let Some(ids) = cells.get(&(x, y, z)) else {
+                        continue;
+                    };
+                    for &id in ids {
+                        if positions[firsts[id]].distance_squared(p)
+                            <= WELD_DISTANCE * WELD_DISTANCE
+                        {
+                            found = Some(id);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+
+        let id = found.unwrap_or_else(|| {
+            firsts.push(i);
+            let id = firsts.len() - 1;
+            cells.entry((cx, cy, cz)).or_default().push(id);
+            id
+        });
+        remap.push(id);
+    }
+
+    (remap, firsts)
+}
+
+// planar projection onto the two largest axes of the bounding box, for meshes without uvs
+fn grid_uvs(positions: &[Vec3A]) -> Vec<[f32; 2]> {
+    let mut min = Vec3A::splat(f32::MAX);
+    let mut max = Vec3A::splat(f32::MIN);
+    for &p in positions.iter() {
+        min = min.min(p);
+        max = max.max(p);
+    }
+    let size = max - min;
+
+    // drop the thinnest axis
+    let (u, v) = if size.x <= size.y && size.x <= size.z {
+        (2, 1)
+    } else if size.y <= size.z {
+        (0, 2)
+    } else {
+        (0, 1)
+    };
+
+    positions
+        .iter()
+        .map(|p| {
+            let su = if size[u] > 0.0 { size[u] } else { 1.0 };
+            let sv = if size[v] > 0.0 { size[v] } else { 1.0 };
+            [(p[u] - min[u]) / su, (p[v] - min[v]) / sv]
+        })
+        .collect()
+}
+
+// xpbd distance constraint between two particles, shared by stretching, bending and stitches,
+// gamma damps the motion along the constraint since the last substep, see Damping::gamma
+fn solve_distance(
+    positions: &mut [Vec3A],
+    prev_positions: &[Vec3A],
+    inv_mass: &[f32],
+    [id0, id1]: [usize; 2],
+    rest_len: f32,
+    alpha: f32,
+    gamma: f32,
+) {
+    let w0 = inv_mass[id0];
+    let w1 = inv_mass[id1];
+    let w = w0 + w1;
+    if w == 0.0 {
+        return;
+    }
+    let diff = positions[id0] - positions[id1];
+    let len = diff.length();
+    if len == 0.0 {
+        return;
+    }
+    let grad = diff / len;
+    let moved = (positions[id0] - prev_positions[id0]) - (positions[id1] - prev_positions[id1]);
+    let s = -(len - rest_len + gamma * grad.dot(moved)) / ((1.0 + gamma) * w + alpha);
+    positions[id0] += grad * (s * w0);
+    positions[id1] += grad * (-s * w1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // pins have to survive a massless material, there's no mass left to tell them apart by
+    #[test]
+    fn set_material_keeps_pins() {
+        let mesh = Mesh::from(shape::Plane {
+            size: 2.0,
+            subdivisions: 1,
+        });
+        let mut cloth = Cloth::new(&mesh, 0.0, &Transform::IDENTITY, &[0]);
+        cloth.set_material(BodyMaterial {
+            density: 0.0,
+            areal_density: 0.0,
+        });
+        assert!(cloth.inv_mass.iter().all(|w| w.is_finite()));
+
+        cloth.set_material(BodyMaterial::COTTON);
+        assert_eq!(cloth.inv_mass[0], 0.0);
+        assert!(cloth.inv_mass[1..].iter().all(|&w| w.is_finite() && w > 0.0));
+        assert!((cloth.total_mass() - 4.0 * BodyMaterial::COTTON.areal_density).abs() < 1e-4);
+    }
+
+    // the sphere's seam column and poles are welded into shared particles but keep their uvs
+    #[test]
+    fn welding_keeps_uv_seams() {
+        let mesh = Mesh::from(shape::UVSphere {
+            radius: 1.0,
+            sectors: 8,
+            stacks: 4,
+        });
+        let mut cloth = Cloth::new(&mesh, 0.0, &Transform::IDENTITY, &[]);
+        assert!(cloth.num_particles < mesh.count_vertices());
+
+        cloth.double_sided = false;
+        let mut written = Mesh::new(PrimitiveTopology::TriangleList);
+        cloth.update_mesh(&mut written);
+        assert_eq!(written.count_vertices(), mesh.count_vertices());
+        assert_eq!(
+            written.attribute(Mesh::ATTRIBUTE_UV_0).unwrap().get_bytes(),
+            mesh.attribute(Mesh::ATTRIBUTE_UV_0).unwrap().get_bytes()
+        );
+    }
+}
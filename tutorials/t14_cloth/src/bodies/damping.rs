@@ -0,0 +1,116 @@
+use bevy::{
+    math::{Mat3A, Vec3A},
+    prelude::*,
+};
+use bevy_inspector_egui::prelude::*;
+
+// velocity damping on top of what the integrator loses numerically, the models can be mixed,
+// all zero is undamped
+#[derive(Reflect, FromReflect, InspectorOptions, Clone, Copy, Debug, Default, PartialEq)]
+#[reflect(InspectorOptions)]
+pub struct Damping {
+    // 1/s, slows every particle down, the body's motion as a whole included
+    #[inspector(min = 0., max = 10.)]
+    pub linear: f32,
+    // 1/s, only damps velocity relative to the body's rigid motion so it still falls and spins
+    #[inspector(min = 0., max = 100.)]
+    pub deformation: f32,
+    // s, xpbd constraint damping, acts along constraint gradients and only on compliant
+    // constraints
+    #[inspector(min = 0., max = 1.)]
+    pub beta: f32,
+}
+
+impl Damping {
+    // gamma of a constraint with the time scaled compliance alpha = compliance / dt²
+    pub fn gamma(&self, alpha: f32, dt: f32) -> f32 {
+        alpha * self.beta * dt
+    }
+
+    // call after the velocities were derived from the positions, particles with zero inverse
+    // mass are left alone
+    pub fn apply(
+        &self,
+        positions: &[Vec3A],
+        velocities: &mut [Vec3A],
+        masses: &[f32],
+        inv_mass: &[f32],
+        dt: f32,
+    ) {
+        if self.linear > 0.0 {
+            let scale = (1.0 - self.linear * dt).max(0.0);
+            for (vel, &w) in velocities.iter_mut().zip(inv_mass.iter()) {
+                if w > 0.0 {
+                    *vel *= scale;
+                }
+            }
+        }
+
+        if self.deformation > 0.0 {
+            damp_deformation(
+                positions,
+                velocities,
+                masses,
+                inv_mass,
+                (self.deformation * dt).min(1.0),
+            );
+        }
+    }
+}
+
+// moves velocities toward the rigid motion v_cm + omega x r of the whole body by k
+fn damp_deformation(
+    positions: &[Vec3A],
+    velocities: &mut [Vec3A],
+    masses: &[f32],
+    inv_mass: &[f32],
+    k: f32,
+) {
+    let total = masses.iter().sum::<f32>();
+    if total == 0.0 {
+        return;
+    }
+
+    let mut x_cm = Vec3A::ZERO;
+    let mut v_cm = Vec3A::ZERO;
+    for ((&p, &v), &m) in positions.iter().zip(velocities.iter()).zip(masses.iter()) {
+        x_cm += p * m;
+        v_cm += v * m;
+    }
+    x_cm /= total;
+    v_cm /= total;
+
+    // angular momentum and inertia tensor about the center of mass
+    let mut l = Vec3A::ZERO;
+    let mut inertia = Mat3A::ZERO;
+    for ((&p, &v), &m) in positions.iter().zip(velocities.iter()).zip(masses.iter()) {
+        let r = p - x_cm;
+        l += r.cross(v) * m;
+        inertia += (Mat3A::from_diagonal(Vec3::splat(r.length_squared()))
+            - outer_product(r, r))
+            * m;
+    }
+    // masses can be tiny so no epsilon here, collinear bodies get no rotation
+    let omega = if inertia.determinant() != 0.0 {
+        inertia.inverse() * l
+    } else {
+        Vec3A::ZERO
+    };
+    let omega = if omega.is_finite() { omega } else { Vec3A::ZERO };
+
+    for ((&p, vel), &w) in positions
+        .iter()
+        .zip(velocities.iter_mut())
+        .zip(inv_mass.iter())
+    {
+        if w == 0.0 {
+            continue;
+        }
+        let rigid = v_cm + omega.cross(p - x_cm);
+        *vel += (rigid - *vel) * k;
+    }
+}
+
+fn outer_product(a: Vec3A, b: Vec3A) -> Mat3A {
+    Mat3A::from_cols(a * b.x, a * b.y, a * b.z)
+}
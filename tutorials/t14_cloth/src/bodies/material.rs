@@ -0,0 +1,33 @@
+use bevy::prelude::*;
+
+// what a body is made of, soft bodies get density * tet volume / 4 per particle and cloth
+// gets areal_density * triangle area / 3, the default is the unit density the demos are tuned for
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, PartialEq)]
+pub struct BodyMaterial {
+    // kg / m³
+    pub density: f32,
+    // kg / m², only used by cloth
+    pub areal_density: f32,
+}
+
+impl Default for BodyMaterial {
+    fn default() -> Self {
+        Self {
+            density: 1.0,
+            areal_density: 1.0,
+        }
+    }
+}
+
+impl BodyMaterial {
+    pub const RUBBER: Self = Self {
+        density: 1100.0,
+        // 0.2 mm sheet, balloons
+        areal_density: 0.22,
+    };
+
+    pub const COTTON: Self = Self {
+        density: 1500.0,
+        areal_density: 0.15,
+    };
+}
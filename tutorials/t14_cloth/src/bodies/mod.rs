@@ -1,10 +1,16 @@
 mod ball;
 mod softbody;
 mod cloth;
+mod rope;
+mod material;
+mod damping;
 
 pub use ball::*;
 pub use softbody::*;
 pub use cloth::*;
+pub use rope::*;
+pub use material::*;
+pub use damping::*;
 
 use bevy::prelude::*;
 
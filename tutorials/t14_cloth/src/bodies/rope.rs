@@ -0,0 +1,401 @@
+use bevy::{
+    math::Vec3A,
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_resource::PrimitiveTopology,
+    },
+};
+use bevy_inspector_egui::prelude::*;
+
+use crate::{
+    bodies::{BodyMaterial, Damping},
+    energy::Energy,
+};
+
+// a chain of particles kept at their rest distances, for cables, hoses and bridges, rendered as
+// a tube around the particles
+#[derive(Reflect, Default, InspectorOptions, TypeUuid)]
+#[uuid = "e2c19309-4c59-4802-a145-94e156c3f7fa"]
+pub struct Rope {
+    num_particles: usize,
+    positions: Vec<Vec3A>,
+    prev_positions: Vec<Vec3A>,
+    velocities: Vec<Vec3A>,
+    inv_mass: Vec<f32>,
+    masses: Vec<f32>,
+    material: BodyMaterial,
+    rest_lengths: Vec<f32>,
+
+    #[inspector(min = 0., max = 1.)]
+    pub stretching_compliance: f32,
+    // not real rod bending, a distance constraint pulls every inner particle onto the midpoint
+    // of its neighbours, so how stiff it feels depends on the segment length and there is no
+    // twist, a rope never coils up on its own
+    pub bending: bool,
+    #[inspector(min = 0., max = 1.)]
+    pub bending_compliance: f32,
+    pub damping: Damping,
+
+    // m, the tube's radius, particles keep this far from the ground and from balls
+    #[inspector(min = 0.001, max = 0.2)]
+    pub radius: f32,
+    #[inspector(min = 3, max = 32)]
+    pub sides: u32,
+}
+
+// ends of a rope pinned to other entities, the offsets are in the entity's local space, every
+// rope needs one even if nothing is attached
+#[derive(Component, Default)]
+pub struct RopeAttachments {
+    pub start: Option<(Entity, Vec3)>,
+    pub end: Option<(Entity, Vec3)>,
+}
+
+impl Rope {
+    // one particle per point, the rest lengths are the distances between them
+    pub fn new(points: &[Vec3], radius: f32) -> Self {
+        let num_particles = points.len();
+        let positions = points.iter().map(|&p| Vec3A::from(p)).collect::<Vec<_>>();
+        let mut rope = Self {
+            num_particles,
+            prev_positions: positions.clone(),
+            positions,
+            velocities: vec![Vec3A::ZERO; num_particles],
+            inv_mass: vec![0.0; num_particles],
+            masses: vec![0.0; num_particles],
+            material: BodyMaterial::default(),
+            rest_lengths: vec![0.0; num_particles.saturating_sub(1)],
+            stretching_compliance: 0.0,
+            bending: false,
+            bending_compliance: 0.01,
+            damping: Damping::default(),
+            radius,
+            sides: 8,
+        };
+        rope.init_physics();
+        rope
+    }
+
+    // points hanging from start to end, the middle sags down by sag like a chain between posts
+    pub fn hanging_points(start: Vec3, end: Vec3, sag: f32, segments: usize) -> Vec<Vec3> {
+        (0..=segments)
+            .map(|i| {
+                let t = i as f32 / segments as f32;
+                start.lerp(end, t) - Vec3::Y * (4.0 * sag * t * (1.0 - t))
+            })
+            .collect()
+    }
+
+    pub fn init_physics(&mut self) {
+        for (i, len) in self.rest_lengths.iter_mut().enumerate() {
+            *len = self.positions[i].distance(self.positions[i + 1]);
+        }
+        self.update_masses();
+    }
+
+    // half of every segment's cylinder goes to each of its particles, pinned particles stay
+    // pinned
+    fn update_masses(&mut self) {
+        let pinned = (0..self.num_particles)
+            .map(|i| self.masses[i] > 0.0 && self.inv_mass[i] == 0.0)
+            .collect::<Vec<_>>();
+
+        let area = std::f32::consts::PI * self.radius * self.radius;
+        self.masses.fill(0.0);
+        for (i, &len) in self.rest_lengths.iter().enumerate() {
+            let m = 0.5 * area * len * self.material.density;
+            self.masses[i] += m;
+            self.masses[i + 1] += m;
+        }
+
+        for ((w, &m), pinned) in self.inv_mass.iter_mut().zip(self.masses.iter()).zip(pinned) {
+            *w = if m > 0.0 && !pinned { 1.0 / m } else { 0.0 };
+        }
+    }
+
+    pub fn set_material(&mut self, material: BodyMaterial) {
+        self.material = material;
+        self.update_masses();
+    }
+
+    pub fn energy(&self, gravity: Vec3) -> Energy {
+        self.positions
+            .iter()
+            .zip(self.velocities.iter())
+            .zip(self.masses.iter())
+            .map(|((&p, &v), &m)| Energy::particle(m, p.into(), v.into(), gravity))
+            .sum()
+    }
+
+    pub fn end_indices(&self) -> (usize, usize) {
+        (0, self.num_particles.saturating_sub(1))
+    }
+
+    pub fn position(&self, index: usize) -> Vec3 {
+        self.positions[index].into()
+    }
+
+    // fixes a particle at pos, call every substep to drag it along with whatever it's attached to
+    pub fn pin(&mut self, index: usize, pos: Vec3) {
+        if index >= self.num_particles {
+            return;
+        }
+        self.inv_mass[index] = 0.0;
+        self.positions[index] = pos.into();
+        self.velocities[index] = Vec3A::ZERO;
+    }
+
+    pub fn pre_solve(&mut self, dt: f32, gravity: Vec3) {
+        let gravity = Vec3A::from(gravity) * dt;
+        for (((pos, prev), vel), &w) in self
+            .positions
+            .iter_mut()
+            .zip(self.prev_positions.iter_mut())
+            .zip(self.velocities.iter_mut())
+            .zip(self.inv_mass.iter())
+        {
+            *prev = *pos;
+            if w == 0.0 {
+                continue;
+            }
+            *vel += gravity;
+            *pos += *vel * dt;
+            if pos.y < self.radius {
+                *pos = *prev;
+                pos.y = self.radius;
+            }
+        }
+    }
+
+    pub fn solve(&mut self, dt: f32) {
+        self.solve_stretching(dt);
+        if self.bending {
+            self.solve_bending(dt);
+        }
+    }
+
+    // pushes particles out of the spheres, the spheres don't move
+    pub fn solve_collisions(&mut self, spheres: &[(Vec3, f32)]) {
+        for (pos, &w) in self.positions.iter_mut().zip(self.inv_mass.iter()) {
+            if w == 0.0 {
+                continue;
+            }
+            for &(center, radius) in spheres.iter() {
+                let center = Vec3A::from(center);
+                let min_dist = radius + self.radius;
+                let diff = *pos - center;
+                let dist = diff.length();
+                if dist == 0.0 || dist >= min_dist {
+                    continue;
+                }
+                *pos = center + diff * (min_dist / dist);
+            }
+        }
+    }
+
+    pub fn post_solve(&mut self, dt: f32) {
+        let inv_dt = 1.0 / dt;
+        for (((vel, pos), prev), &w) in self
+            .velocities
+            .iter_mut()
+            .zip(self.positions.iter())
+            .zip(self.prev_positions.iter())
+            .zip(self.inv_mass.iter())
+        {
+            if w == 0.0 {
+                continue;
+            }
+            *vel = (*pos - *prev) * inv_dt;
+        }
+        self.damping.apply(
+            &self.positions,
+            &mut self.velocities,
+            &self.masses,
+            &self.inv_mass,
+            dt,
+        );
+    }
+
+    fn solve_stretching(&mut self, dt: f32) {
+        let alpha = self.stretching_compliance / dt / dt;
+        let gamma = self.damping.gamma(alpha, dt);
+
+        for i in 0..self.rest_lengths.len() {
+            let (id0, id1) = (i, i + 1);
+            let w0 = self.inv_mass[id0];
+            let w1 = self.inv_mass[id1];
+            let w = w0 + w1;
+            if w == 0.0 {
+                continue;
+            }
+            let diff = self.positions[id0] - self.positions[id1];
+            let len = diff.length();
+            if len == 0.0 {
+                continue;
+            }
+            let grad = diff / len;
+            let moved = (self.positions[id0] - self.prev_positions[id0])
+                - (self.positions[id1] - self.prev_positions[id1]);
+            let s = -(len - self.rest_lengths[i] + gamma * grad.dot(moved))
+                / ((1.0 + gamma) * w + alpha);
+            self.positions[id0] += grad * (s * w0);
+            self.positions[id1] += grad * (-s * w1);
+        }
+    }
+
+    // C = |p1 - (p0 + p2) / 2|, the distance from the middle particle to the midpoint of its
+    // neighbours, zero only when the three are straight and evenly spaced, the gradient is n
+    // for the middle and -n / 2 for the neighbours
+    fn solve_bending(&mut self, dt: f32) {
+        let alpha = self.bending_compliance / dt / dt;
+        let gamma = self.damping.gamma(alpha, dt);
+
+        for i in 1..self.num_particles.saturating_sub(1) {
+            let (id0, id1, id2) = (i - 1, i, i + 1);
+            let w0 = self.inv_mass[id0];
+            let w1 = self.inv_mass[id1];
+            let w2 = self.inv_mass[id2];
+            let w = w1 + 0.25 * (w0 + w2);
+            if w == 0.0 {
+                continue;
+            }
+            let d = self.positions[id1] - 0.5 * (self.positions[id0] + self.positions[id2]);
+            let len = d.length();
+            if len == 0.0 {
+                continue;
+            }
+            let n = d / len;
+            let moved = |id: usize| self.positions[id] - self.prev_positions[id];
+            let moved = moved(id1) - 0.5 * (moved(id0) + moved(id2));
+            let s = -(len + gamma * n.dot(moved)) / ((1.0 + gamma) * w + alpha);
+            self.positions[id1] += n * (s * w1);
+            self.positions[id0] += n * (-0.5 * s * w0);
+            self.positions[id2] += n * (-0.5 * s * w2);
+        }
+    }
+
+    // moves position changes to local space and updates transform position, call before update meshes
+    pub fn update_transform(&mut self, trans: &mut Transform) {
+        if self.num_particles == 0 {
+            return;
+        }
+        let avg_pos = self.positions.iter().copied().sum::<Vec3A>() / self.num_particles as f32;
+        trans.translation = avg_pos.into();
+    }
+
+    // writes positions and normals into the mesh buffers when the mesh already has this rope's
+    // layout, only falls back to building the mesh when it doesn't (first frame, sides changed)
+    pub fn update_visual_mesh(&self, trans: &Transform, mesh: &mut Mesh) {
+        let has_normals = matches!(
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+            Some(VertexAttributeValues::Float32x3(_))
+        );
+        if mesh.count_vertices() != self.num_particles * (self.sides() + 1) || !has_normals {
+            self.write_mesh(mesh, trans.translation);
+            return;
+        }
+
+        if let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+        {
+            self.for_each_ring_vertex(trans.translation, |v, pos, _| {
+                positions[v] = pos.to_array();
+            });
+        }
+        if let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL)
+        {
+            self.for_each_ring_vertex(trans.translation, |v, _, normal| {
+                normals[v] = normal.to_array();
+            });
+        }
+    }
+
+    fn sides(&self) -> usize {
+        self.sides.max(3) as usize
+    }
+
+    // a ring of vertices around every particle, the rings are turned along the rope by parallel
+    // transport so the tube doesn't twist, positions are relative to offset
+    fn for_each_ring_vertex(&self, offset: Vec3, mut f: impl FnMut(usize, Vec3A, Vec3)) {
+        let n = self.num_particles;
+        let sides = self.sides();
+        let offset = Vec3A::from(offset);
+
+        let mut prev_tangent = Vec3::ZERO;
+        let mut normal = Vec3::ZERO;
+        for i in 0..n {
+            let p = self.positions[i];
+            let tangent = Vec3::from(
+                self.positions[(i + 1).min(n - 1)] - self.positions[i.saturating_sub(1)],
+            )
+            .normalize_or_zero();
+            normal = if i == 0 || normal == Vec3::ZERO {
+                tangent.any_orthonormal_vector()
+            } else {
+                let turned = Quat::from_rotation_arc(prev_tangent, tangent) * normal;
+                // keep it perpendicular, the rotation drifts
+                (turned - tangent * turned.dot(tangent)).normalize_or_zero()
+            };
+            prev_tangent = tangent;
+            let binormal = tangent.cross(normal);
+
+            // the first and last vertex of a ring overlap so the texture can wrap around
+            for k in 0..=sides {
+                let angle = std::f32::consts::TAU * k as f32 / sides as f32;
+                let dir = normal * angle.cos() + binormal * angle.sin();
+                f(
+                    i * (sides + 1) + k,
+                    p - offset + Vec3A::from(dir) * self.radius,
+                    dir,
+                );
+            }
+        }
+    }
+
+    // builds the whole tube, the ends are left open
+    fn write_mesh(&self, mesh: &mut Mesh, offset: Vec3) {
+        let n = self.num_particles;
+        let sides = self.sides();
+        let mut positions = Vec::with_capacity(n * (sides + 1));
+        let mut normals = Vec::with_capacity(n * (sides + 1));
+        self.for_each_ring_vertex(offset, |_, pos, normal| {
+            positions.push(pos.to_array());
+            normals.push(normal.to_array());
+        });
+
+        let mut uvs = Vec::with_capacity(n * (sides + 1));
+        for i in 0..n {
+            for k in 0..=sides {
+                uvs.push([k as f32 / sides as f32, i as f32 / (n - 1).max(1) as f32]);
+            }
+        }
+
+        let ring = (sides + 1) as u32;
+        let mut indices = Vec::with_capacity(n.saturating_sub(1) * sides * 6);
+        for i in 0..n.saturating_sub(1) as u32 {
+            for k in 0..sides as u32 {
+                let a = i * ring + k;
+                let b = a + 1;
+                let c = a + ring;
+                let d = c + 1;
+                indices.extend_from_slice(&[a, b, c, b, d, c]);
+            }
+        }
+
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    }
+}
+
+impl From<&Rope> for Mesh {
+    fn from(rope: &Rope) -> Self {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        rope.write_mesh(&mut mesh, Vec3::ZERO);
+        mesh
+    }
+}
@@ -1,4 +1,5 @@
 use bevy::{
+    math::{Mat3A, Vec3A},
     prelude::*,
     render::{mesh::{Indices, VertexAttributeValues}, render_resource::PrimitiveTopology}, reflect::TypeUuid,
 };
@@ -6,8 +7,9 @@ use bevy_inspector_egui::prelude::*;
 
 use crate::{
     intersect::{ray_sphere_intersect, ray_triangle_intersect},
-    assets::TetMesh, spatial_hash::SpatialHash,
-    math::*,
+    assets::{MaterialPaint, TetMesh}, spatial_hash::SpatialHash,
+    bodies::{BodyMaterial, Cloth, Damping},
+    energy::Energy,
 };
 
 
@@ -17,28 +19,35 @@ pub struct SoftBody {
     // visual mesh
     #[inspector()]
     visual_indices: Vec<usize>,
-    visual_vertices: Vec<f32>,
+    visual_vertices: Vec<Vec3A>,
     num_vis_verts: usize,
     skinning_info: Vec<f32>,
 
-    // tet mesh
+    // tet mesh, particles are Vec3A like the rest of the solver math
     num_particles: usize,
     num_tets: usize,
-    positions: Vec<f32>,
-    prev_positions: Vec<f32>,
-    velocities: Vec<f32>,
+    positions: Vec<Vec3A>,
+    prev_positions: Vec<Vec3A>,
+    velocities: Vec<Vec3A>,
     tet_ids: Vec<usize>,
     edge_ids: Vec<usize>,
     rest_volumn: Vec<f32>,
     edge_lengths: Vec<f32>,
     inv_mass: Vec<f32>,
+    masses: Vec<f32>,
+    material: BodyMaterial,
+
+    // painted variation, compliance scales per constraint, damping per particle, density per tet
+    edge_scales: Vec<f32>,
+    volume_scales: Vec<f32>,
+    paint_damping: Vec<f32>,
+    density_scales: Vec<f32>,
     #[inspector(min = 0., max = 100.)]
     edge_compliance: f32,
     #[inspector(min = 0., max = 1.)]
     volume_compliance: f32,
+    pub damping: Damping,
 
-    temp: Vec<f32>,
-    grads: Vec<f32>,
     grab_id: Option<usize>,
     grab_inv_mass: f32,
 
@@ -49,31 +58,38 @@ const VOLUME_ID_ORDER: [[usize; 3]; 4] = [[1, 3, 2], [0, 2, 3], [0, 3, 1], [0, 1
 
 impl SoftBody {
     pub fn new(mesh: &TetMesh, edge_compliance: f32, volume_compliance: f32) -> Self {
-        let num_particles = mesh.tet_vertices.len() / 3;
+        let positions = to_vec3a(&mesh.tet_vertices);
+        let num_particles = positions.len();
         let num_tets = mesh.tet_indices.len() / 4;
-        let num_vis_verts = mesh.vertices.len() / 3;
+        let visual_vertices = to_vec3a(&mesh.vertices);
+        let num_vis_verts = visual_vertices.len();
 
         let mut result = Self {
             visual_indices: mesh.indices.clone(),
-            visual_vertices: mesh.vertices.clone(),
+            visual_vertices,
             num_vis_verts: num_vis_verts,
             skinning_info: vec![0.0; 4 * num_vis_verts],
             
             // tet mesh
             num_particles,
             num_tets,
-            positions: mesh.tet_vertices.clone(),
-            prev_positions: mesh.tet_vertices.clone(),
-            velocities: vec![0.0; 3 * num_particles],
+            positions: positions.clone(),
+            prev_positions: positions,
+            velocities: vec![Vec3A::ZERO; num_particles],
             tet_ids: mesh.tet_indices.clone(),
             edge_ids: mesh.tet_edge_ids.clone(),
             rest_volumn: vec![0.0; num_tets],
             edge_lengths: vec![0.0; mesh.tet_edge_ids.len() / 2],
             inv_mass: vec![0.0; num_particles],
+            masses: vec![0.0; num_particles],
+            material: BodyMaterial::default(),
+            edge_scales: vec![1.0; mesh.tet_edge_ids.len() / 2],
+            volume_scales: vec![1.0; num_tets],
+            paint_damping: vec![0.0; num_particles],
+            density_scales: vec![1.0; num_tets],
             edge_compliance,
             volume_compliance,
-            temp: vec![0.0; 4 * 3],
-            grads: vec![0.0; 4 * 3],
+            damping: Damping::default(),
             grab_id: None,
             grab_inv_mass: 0.,
             radius: 0.0,
@@ -90,9 +106,7 @@ impl SoftBody {
     {
         // create a hash for all vertices of the visual mesh
         let mut hash = SpatialHash::new(0.05, self.num_vis_verts);
-        hash.create(&self.visual_vertices.chunks(3)
-        .map(|v| Vec3::new(v[0], v[1], v[2]))
-        .collect::<Vec<Vec3>>());        
+        hash.create(&self.visual_vertices.iter().map(|&v| Vec3::from(v)).collect::<Vec<Vec3>>());
 
         self.skinning_info.fill(-1.0);		// undefined
 
@@ -101,43 +115,26 @@ impl SoftBody {
         let border = 0.05;
 
         // each tet searches for containing vertices
-        let mut tet_center = [0.0; 3];
-        let mut mat = [0.0; 9];
-        let mut bary = [0.0; 4];
-
         for i in 0..self.num_tets {
+            let p = self.tet_positions(i);
 
             // compute bounding sphere of tet
-            tet_center.fill(0.0);
-            for j in 0..4 {
-                vecAdd(&mut tet_center, 0, &self.positions, self.tet_ids[4 * i + j], 0.25);
-            }
+            let tet_center = (p[0] + p[1] + p[2] + p[3]) * 0.25;
+            let r_max = p.iter().map(|&v| tet_center.distance(v)).fold(0.0, f32::max) + border;
 
-            let mut r_max = 0.0f32;
-            for j in 0..4 {
-                let r2 = vecDistSquared(&tet_center, 0, &self.positions, self.tet_ids[4 * i + j]);
-                r_max = r_max.max(r2.sqrt());
+            hash.query(&tet_center.to_array(), 0, r_max);
+            if hash.query_size == 0 {
+                continue;
             }
 
-            r_max += border;
-
-            hash.query(&tet_center, 0, r_max);
-            if hash.query_ids.len() == 0 {
+            let mat = Mat3A::from_cols(p[0] - p[3], p[1] - p[3], p[2] - p[3]);
+            // a flat tet can't contain anything
+            if mat.determinant() == 0.0 {
                 continue;
             }
+            let mat = mat.inverse();
 
-            let id0 = self.tet_ids[4 * i];
-            let id1 = self.tet_ids[4 * i + 1];
-            let id2 = self.tet_ids[4 * i + 2];
-            let id3 = self.tet_ids[4 * i + 3];
-
-            vecSetDiff(&mut mat, 0, &self.positions, id0, &self.positions, id3, 1.0);
-            vecSetDiff(&mut mat, 1, &self.positions, id1, &self.positions, id3, 1.0);
-            vecSetDiff(&mut mat, 2, &self.positions, id2, &self.positions, id3, 1.0);
-
-            matSetInverse(&mut mat);
-
-            for j in 0..hash.query_ids.len() {
+            for j in 0..hash.query_size {
                 let id = hash.query_ids[j];
 
                 // we already have skinning info
@@ -145,20 +142,15 @@ impl SoftBody {
                     continue;
                 }
 
-                if vecDistSquared(&self.visual_vertices, id, &tet_center, 0) > r_max * r_max {
+                if self.visual_vertices[id].distance_squared(tet_center) > r_max * r_max {
                     continue;
                 }
 
                 // compute barycentric coords for candidate
-                vecSetDiff(&mut bary, 0, &self.visual_vertices, id, &self.positions, id3, 1.0);
-                matSetMult(&mat, &mut bary, 0);
-                bary[3] = 1.0 - bary[0] - bary[1] - bary[2];
+                let bary = mat * (self.visual_vertices[id] - p[3]);
+                let bary = [bary.x, bary.y, bary.z, 1.0 - bary.x - bary.y - bary.z];
 
-                let mut dist = 0.0f32;
-                for k in 0..4 {
-                    dist = dist.max( -bary[k]);
-                }
-                    
+                let dist = bary.iter().fold(0.0f32, |d, &b| d.max(-b));
                 if dist < min_dist[id] {
                     min_dist[id] = dist;
                     self.skinning_info[4 * id] = i as f32;
@@ -170,89 +162,134 @@ impl SoftBody {
         }
     }
 
-  
-    pub fn get_tet_volume(&mut self, nr: usize) -> f32 {
-        let id0 = self.tet_ids[4 * nr];
-        let id1 = self.tet_ids[4 * nr + 1];
-        let id2 = self.tet_ids[4 * nr + 2];
-        let id3 = self.tet_ids[4 * nr + 3];
-        vecSetDiff(
-            &mut self.temp,
-            0,
-            &self.positions,
-            id1,
-            &self.positions,
-            id0,
-            1.0,
-        );
-        vecSetDiff(
-            &mut self.temp,
-            1,
-            &self.positions,
-            id2,
-            &self.positions,
-            id0,
-            1.0,
-        );
-        vecSetDiff(
-            &mut self.temp,
-            2,
-            &self.positions,
-            id3,
-            &self.positions,
-            id0,
-            1.0,
-        );
-
-        // Cant borrow self.temp as mutable and immutable, doing it manually
-        // vecSetCross(&mut self.temp, 3, &self.temp, 0, &self.temp, 1);
-        let ar = 3 * 3;
-        let br = 0 * 3;
-        let cr = 1 * 3;
-        self.temp[ar] =
-            self.temp[br + 1] * self.temp[cr + 2] - self.temp[br + 2] * self.temp[cr + 1];
-        self.temp[ar + 1] =
-            self.temp[br + 2] * self.temp[cr + 0] - self.temp[br + 0] * self.temp[cr + 2];
-        self.temp[ar + 2] =
-            self.temp[br + 0] * self.temp[cr + 1] - self.temp[br + 1] * self.temp[cr + 0];
+    fn tet_positions(&self, nr: usize) -> [Vec3A; 4] {
+        [
+            self.positions[self.tet_ids[4 * nr]],
+            self.positions[self.tet_ids[4 * nr + 1]],
+            self.positions[self.tet_ids[4 * nr + 2]],
+            self.positions[self.tet_ids[4 * nr + 3]],
+        ]
+    }
 
-        return vecDot(&self.temp, 3, &self.temp, 2) / 6.0;
+    pub fn get_tet_volume(&self, nr: usize) -> f32 {
+        tet_volume(self.tet_positions(nr))
     }
 
     pub fn init_physics(&mut self) {
         for i in 0..self.num_tets {
-            let vol = self.get_tet_volume(i);
-            self.rest_volumn[i] = vol;
-            let p_inv_mass = if vol > 0.0 { 1.0 / (vol / 4.0) } else { 0.0 };
-            self.inv_mass[self.tet_ids[4 * i]] += p_inv_mass;
-            self.inv_mass[self.tet_ids[4 * i + 1]] += p_inv_mass;
-            self.inv_mass[self.tet_ids[4 * i + 2]] += p_inv_mass;
-            self.inv_mass[self.tet_ids[4 * i + 3]] += p_inv_mass;
+            self.rest_volumn[i] = self.get_tet_volume(i);
         }
+        self.update_masses();
         for i in 0..self.edge_lengths.len() {
             let id0 = self.edge_ids[2 * i];
             let id1 = self.edge_ids[2 * i + 1];
-            self.edge_lengths[i] =
-                vecDistSquared(&self.positions, id0, &self.positions, id1).sqrt();
+            self.edge_lengths[i] = self.positions[id0].distance(self.positions[id1]);
         }
     }
 
-    pub fn pre_solve(&mut self, dt: f32, gravity: Vec3) {
+    // a quarter of every tet's rest mass goes to each of its particles
+    fn update_masses(&mut self) {
+        self.masses.fill(0.0);
+        for i in 0..self.num_tets {
+            // inverted tets add nothing
+            let p_mass =
+                self.rest_volumn[i].max(0.0) * self.material.density * self.density_scales[i] / 4.0;
+            for j in 0..4 {
+                self.masses[self.tet_ids[4 * i + j]] += p_mass;
+            }
+        }
+        for (i, (w, &m)) in self.inv_mass.iter_mut().zip(self.masses.iter()).enumerate() {
+            let inv = if m > 0.0 { 1.0 / m } else { 0.0 };
+            if self.grab_id == Some(i) {
+                self.grab_inv_mass = inv;
+            } else {
+                *w = inv;
+            }
+        }
+    }
+
+    // varies compliance, damping and density over the body, the paint is indexed by tet particle
+    pub fn paint(&mut self, paint: &MaterialPaint) {
+        self.edge_scales = self
+            .edge_ids
+            .chunks_exact(2)
+            .map(|ids| paint.constraint(ids).compliance)
+            .collect();
+        let tets = self
+            .tet_ids
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(nr, ids)| paint.tet(nr, ids))
+            .collect::<Vec<_>>();
+        self.volume_scales = tets.iter().map(|t| t.compliance).collect();
+        self.density_scales = tets.iter().map(|t| t.density).collect();
         for i in 0..self.num_particles {
-            if self.inv_mass[i] == 0.0 {
+            self.paint_damping[i] = paint.vertex(i).damping;
+        }
+        self.update_masses();
+    }
+
+    // rest pose, for building a paint
+    pub fn particle_positions(&self) -> Vec<Vec3> {
+        self.positions.iter().map(|&p| p.into()).collect()
+    }
+
+    pub fn material(&self) -> BodyMaterial {
+        self.material
+    }
+
+    pub fn set_material(&mut self, material: BodyMaterial) {
+        self.material = material;
+        self.update_masses();
+    }
+
+    // kg
+    pub fn total_mass(&self) -> f32 {
+        self.masses.iter().sum()
+    }
+
+    pub fn center_of_mass(&self) -> Vec3 {
+        let total = self.total_mass();
+        if total == 0.0 {
+            return Vec3::ZERO;
+        }
+        let weighted = self
+            .positions
+            .iter()
+            .zip(self.masses.iter())
+            .map(|(&p, &m)| p * m)
+            .sum::<Vec3A>();
+        (weighted / total).into()
+    }
+
+    pub fn energy(&self, gravity: Vec3) -> Energy {
+        self.positions
+            .iter()
+            .zip(self.velocities.iter())
+            .zip(self.masses.iter())
+            .map(|((&p, &v), &m)| Energy::particle(m, p.into(), v.into(), gravity))
+            .sum()
+    }
+
+    pub fn pre_solve(&mut self, dt: f32, gravity: Vec3) {
+        let gravity = Vec3A::from(gravity) * dt;
+        for (((pos, prev), vel), &w) in self
+            .positions
+            .iter_mut()
+            .zip(self.prev_positions.iter_mut())
+            .zip(self.velocities.iter_mut())
+            .zip(self.inv_mass.iter())
+        {
+            // grabbed particles still need a start position for constraint damping
+            *prev = *pos;
+            if w == 0.0 {
                 continue;
             }
-            //vecAdd(this.vel,i, gravity,0, dt);
-            self.velocities[i * 3] += gravity[0] * dt;
-            self.velocities[i * 3 + 1] += gravity[1] * dt;
-            self.velocities[i * 3 + 2] += gravity[2] * dt;
-
-            vecCopy(&mut self.prev_positions, i, &self.positions, i);
-            vecAdd(&mut self.positions, i, &self.velocities, i, dt);
-            let y = self.positions[3 * i + 1];
-            if y < 0.0 {
-                vecCopy(&mut self.positions, i, &self.prev_positions, i);
-                self.positions[3 * i + 1] = 0.0;
+            *vel += gravity;
+            *pos += *vel * dt;
+            if pos.y < 0.0 {
+                *pos = *prev;
+                pos.y = 0.0;
             }
         }
     }
@@ -263,56 +300,54 @@ impl SoftBody {
     }
 
     pub fn post_solve( &mut self, dt: f32) {
-        
-
-        for i in 0..self.num_particles {
-            if self.inv_mass[i] == 0.0 {
+        let inv_dt = 1.0 / dt;
+        for ((((vel, pos), prev), &w), &damping) in self
+            .velocities
+            .iter_mut()
+            .zip(self.positions.iter())
+            .zip(self.prev_positions.iter())
+            .zip(self.inv_mass.iter())
+            .zip(self.paint_damping.iter())
+        {
+            if w == 0.0 {
                 continue;
             }
-            vecSetDiff(
-                &mut self.velocities,
-                i,
-                &self.positions,
-                i,
-                &self.prev_positions,
-                i,
-                1.0 / dt,
-            );
+            *vel = (*pos - *prev) * inv_dt;
+            if damping > 0.0 {
+                *vel *= (1.0 - damping * dt).max(0.0);
+            }
         }
+        self.damping.apply(
+            &self.positions,
+            &mut self.velocities,
+            &self.masses,
+            &self.inv_mass,
+            dt,
+        );
     }
 
     fn solve_edges(&mut self, compliance: f32, dt: f32) {
         let alpha = compliance / dt / dt;
 
         for i in 0..self.edge_lengths.len() {
-            let id0 = self.edge_ids[2 * i];
-            let id1 = self.edge_ids[2 * i + 1];
-            let w0 = self.inv_mass[id0];
-            let w1 = self.inv_mass[id1];
-            let w = w0 + w1;
-            if w == 0.0 {
-                continue;
-            }
+            self.solve_edge(i, alpha, dt);
+        }
+    }
 
-            vecSetDiff(
-                &mut self.grads,
-                0,
-                &self.positions,
-                id0,
-                &self.positions,
-                id1,
-                1.0,
-            );
-            let len = vecLengthSquared(&self.grads, 0).sqrt();
-            if len == 0.0 {
-                continue;
-            }
-            vecScale(&mut self.grads, 0, 1.0 / len);
-            let rest_len = self.edge_lengths[i];
-            let c = len - rest_len;
-            let s = -c / (w + alpha);
-            vecAdd(&mut self.positions, id0, &self.grads, 0, s * w0);
-            vecAdd(&mut self.positions, id1, &self.grads, 0, -s * w1);
+    fn solve_edge(&mut self, i: usize, alpha: f32, dt: f32) {
+        let ids = [self.edge_ids[2 * i], self.edge_ids[2 * i + 1]];
+        let alpha = alpha * self.edge_scales[i];
+        if let Some((ids, deltas)) = edge_correction(
+            &self.positions,
+            &self.prev_positions,
+            &self.inv_mass,
+            ids,
+            self.edge_lengths[i],
+            alpha,
+            self.damping.gamma(alpha, dt),
+        ) {
+            self.positions[ids[0]] += deltas[0];
+            self.positions[ids[1]] += deltas[1];
         }
     }
 
@@ -320,65 +355,53 @@ impl SoftBody {
         let alpha = compliance / dt / dt;
 
         for i in 0..self.num_tets {
-            let mut w = 0.0;
+            self.solve_volume(i, alpha, dt);
+        }
+    }
 
+    fn solve_volume(&mut self, i: usize, alpha: f32, dt: f32) {
+        let ids = [
+            self.tet_ids[4 * i],
+            self.tet_ids[4 * i + 1],
+            self.tet_ids[4 * i + 2],
+            self.tet_ids[4 * i + 3],
+        ];
+        let alpha = alpha * self.volume_scales[i];
+        if let Some((ids, deltas)) = volume_correction(
+            &self.positions,
+            &self.prev_positions,
+            &self.inv_mass,
+            ids,
+            self.rest_volumn[i],
+            alpha,
+            self.damping.gamma(alpha, dt),
+        ) {
             for j in 0..4 {
-                let id0 = self.tet_ids[4 * i + VOLUME_ID_ORDER[j][0]];
-                let id1 = self.tet_ids[4 * i + VOLUME_ID_ORDER[j][1]];
-                let id2 = self.tet_ids[4 * i + VOLUME_ID_ORDER[j][2]];
-
-                vecSetDiff(
-                    &mut self.temp,
-                    0,
-                    &self.positions,
-                    id1,
-                    &self.positions,
-                    id0,
-                    1.0,
-                );
-                vecSetDiff(
-                    &mut self.temp,
-                    1,
-                    &self.positions,
-                    id2,
-                    &self.positions,
-                    id0,
-                    1.0,
-                );
-                vecSetCross(&mut self.grads, j, &self.temp, 0, &self.temp, 1);
-                vecScale(&mut self.grads, j, 1.0 / 6.0);
-
-                w += self.inv_mass[self.tet_ids[4 * i + j]] * vecLengthSquared(&self.grads, j);
+                self.positions[ids[j]] += deltas[j];
             }
-            if w == 0.0 {
+        }
+    }
+
+    // stops particles at the cloth instead of tunneling through it, call after Cloth::solve_ccd
+    pub fn solve_ccd(&mut self, cloth: &mut Cloth) {
+        for i in 0..self.num_particles {
+            if self.inv_mass[i] == 0.0 {
                 continue;
             }
-
-            let vol = self.get_tet_volume(i);
-            let rest_vol = self.rest_volumn[i];
-            let c = vol - rest_vol;
-            let s = -c / (w + alpha);
-
-            for j in 0..4 {
-                let id = self.tet_ids[4 * i + j];
-                vecAdd(
-                    &mut self.positions,
-                    id,
-                    &self.grads,
-                    j,
-                    s * self.inv_mass[id],
-                )
+            let p = [self.prev_positions[i].into(), self.positions[i].into()];
+            if let Some(t) = cloth.sweep_point(p) {
+                self.positions[i] = self.prev_positions[i].lerp(self.positions[i], t);
             }
         }
     }
-    
+
     pub fn create_tet_mesh(&self) -> Mesh {
         let mut mesh = Mesh::new(PrimitiveTopology::LineList);
         let indices = self.edge_ids.iter().map(|i| *i as u32).collect::<Vec<u32>>();
         let positions = self
             .positions
-            .chunks_exact(3)
-            .map(|v| [v[0], v[1], v[2]])
+            .iter()
+            .map(|v| v.to_array())
             .collect::<Vec<[f32; 3]>>();
 
         mesh.set_indices(Some(Indices::U32(indices)));
@@ -391,113 +414,44 @@ impl SoftBody {
     pub fn update_transform(&mut self, trans: &mut Transform) {
         
         // find avg position and radius of the mesh
-        let mut avg_pos = Vec3::ZERO;     
-        let len = self.positions.len() / 3;
-        for pos in self.positions.chunks_exact(3) {
-             avg_pos[0] += pos[0];
-             avg_pos[1] += pos[1];
-             avg_pos[2] += pos[2];
-        }
-        avg_pos[0] /= len as f32;
-        avg_pos[1] /= len as f32;
-        avg_pos[2] /= len as f32;
+        let avg_pos = self.positions.iter().copied().sum::<Vec3A>() / self.num_particles as f32;
 
         // find max distance from avg position
-        let mut max_dist = 0.0;
-        for pos in self.positions.chunks_exact(3) {
-            
-            let dist = (pos[0] - avg_pos[0]).powi(2)
-                 + (pos[1] - avg_pos[1]).powi(2)
-                 + (pos[2] - avg_pos[2]).powi(2);
-             if dist > max_dist {
-                 max_dist = dist;
-             }
-        }
+        let max_dist = self
+            .positions
+            .iter()
+            .map(|pos| pos.distance_squared(avg_pos))
+            .fold(0.0, f32::max);
         self.radius = max_dist.sqrt();
-        trans.translation = avg_pos;
-
-    // update positions to be relative to the center of the mesh
-    //     for pos in self.positions.chunks_exact_mut(3) {
-    //          pos[0] -= avg_pos[0];
-    //          pos[1] -= avg_pos[1];
-    //          pos[2] -= avg_pos[2];
-    //     }
-
-    //     for pos in self.prev_positions.chunks_exact_mut(3) {
-    //         pos[0] -= avg_pos[0];
-    //         pos[1] -= avg_pos[1];
-    //         pos[2] -= avg_pos[2];
-    //    }
-
-    //     for pos in self.visual_vertices.chunks_exact_mut(3) {
-    //         pos[0] -= avg_pos[0];
-    //         pos[1] -= avg_pos[1];
-    //         pos[2] -= avg_pos[2];
-    //     }
-
+        trans.translation = avg_pos.into();
     }
 
     pub fn update_tet_mesh(&mut self, trans: &Transform, mesh: &mut Mesh) {
-        
-        let positions = mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION).unwrap();
-        for i in 0..(self.positions.len() / 3) {
-            match positions {
-                VertexAttributeValues::Float32x3(positions) => {
-                    positions[i][0] = self.positions[i * 3] - trans.translation.x;
-                    positions[i][1] = self.positions[i * 3 + 1] - trans.translation.y;
-                    positions[i][2] = self.positions[i * 3 + 2] - trans.translation.z;
+        let offset = Vec3A::from(trans.translation);
+        match mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION).unwrap() {
+            VertexAttributeValues::Float32x3(positions) => {
+                for (dst, &pos) in positions.iter_mut().zip(self.positions.iter()) {
+                    *dst = (pos - offset).to_array();
                 }
-                _ => panic!("Wrong attribute type"),
-            }            
-        }        
+            }
+            _ => panic!("Wrong attribute type"),
+        }
     }
 
     pub fn update_visual_mesh(&mut self, trans: &Transform, mesh: &mut Mesh) {
-        let mut nr = 0;
         for i in 0..self.num_vis_verts {
-            let mut tet_nr = (self.skinning_info[nr] * 4.0) as usize; // TODO: check this
-            nr += 1;
-            // dont think this can happen
-            // if tet_nr < 0 { 
-            //     // dont think this can happen
-            //     nr += 3;
-            //     continue;
-            // }
-            let b0 = self.skinning_info[nr];
-            nr += 1;
-            let b1 = self.skinning_info[nr];
-            nr += 1;
-            let b2 = self.skinning_info[nr];
-            nr += 1;
+            let tet_nr = self.skinning_info[4 * i] as usize;
+            let b0 = self.skinning_info[4 * i + 1];
+            let b1 = self.skinning_info[4 * i + 2];
+            let b2 = self.skinning_info[4 * i + 3];
             let b3 = 1.0 - b0 - b1 - b2;
-            let id0 = self.tet_ids[tet_nr];
-            tet_nr += 1;
-            let id1 = self.tet_ids[tet_nr];
-            tet_nr += 1;
-            let id2 = self.tet_ids[tet_nr];
-            tet_nr += 1;
-            let id3 = self.tet_ids[tet_nr];
-            //tet_nr += 1;
-            vecSetZero(&mut self.visual_vertices, i);
-            vecAdd(&mut self.visual_vertices, i, &self.positions, id0, b0);
-            vecAdd(&mut self.visual_vertices, i, &self.positions, id1, b1);
-            vecAdd(&mut self.visual_vertices, i, &self.positions, id2, b2);
-            vecAdd(&mut self.visual_vertices, i, &self.positions, id3, b3);
+            let p = self.tet_positions(tet_nr);
+            self.visual_vertices[i] = p[0] * b0 + p[1] * b1 + p[2] * b2 + p[3] * b3;
         }
 
-
-        let indices = self.visual_indices.iter().map(|i| *i as u32).collect::<Vec<u32>>();
-        let positions = self
-            .visual_vertices
-            .chunks_exact(3)
-            .map(|v| [v[0] - trans.translation.x, v[1] - trans.translation.y, v[2] - trans.translation.z])
-            .collect::<Vec<[f32; 3]>>();
-
-        
-        mesh.set_indices(Some(Indices::U32(indices)));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.duplicate_vertices();
-        mesh.compute_flat_normals();
+        let offset = Vec3A::from(trans.translation);
+        let vertices = &self.visual_vertices;
+        write_flat_mesh(mesh, &self.visual_indices, self.num_vis_verts, |i| vertices[i] - offset);
     }
 
     // returns distance to closest point
@@ -508,21 +462,9 @@ impl SoftBody {
             let mut min_dist = f32::MAX;
             for i in 0..self.tet_ids.len() / 3 {
                 let index = i * 3;
-                let p0 = Vec3::new(
-                    self.positions[self.tet_ids[index] * 3],
-                    self.positions[self.tet_ids[index] * 3 + 1],
-                    self.positions[self.tet_ids[index] * 3 + 2],
-                );
-                let p1 = Vec3::new(
-                    self.positions[self.tet_ids[index + 1] * 3],
-                    self.positions[self.tet_ids[index + 1] * 3 + 1],
-                    self.positions[self.tet_ids[index + 1] * 3 + 2],
-                );
-                let p2 = Vec3::new(
-                    self.positions[self.tet_ids[index + 2] * 3],
-                    self.positions[self.tet_ids[index + 2] * 3 + 1],
-                    self.positions[self.tet_ids[index + 2] * 3 + 2],
-                );
+                let p0 = self.positions[self.tet_ids[index]].into();
+                let p1 = self.positions[self.tet_ids[index + 1]].into();
+                let p2 = self.positions[self.tet_ids[index + 2]].into();
                 if let Some(dist) = ray_triangle_intersect(ray, p0, p1, p2) {
                     if dist < min_dist {
                         min_dist = dist;
@@ -538,11 +480,11 @@ impl SoftBody {
     }
 
     pub fn start_grab(&mut self, pos: Vec3) {
-        let mut p = [pos.x, pos.y, pos.z];
+        let p = Vec3A::from(pos);
         let mut min_d2 = f32::MAX;
         self.grab_id = None;
         for i in 0..self.num_particles {
-            let d2 = vecDistSquared(&mut p, 0, &self.positions, i);
+            let d2 = p.distance_squared(self.positions[i]);
             if d2 < min_d2 {
                 min_d2 = d2;
                 self.grab_id = Some(i);
@@ -552,60 +494,38 @@ impl SoftBody {
         if let Some(index) = self.grab_id {
             self.grab_inv_mass = self.inv_mass[index];
             self.inv_mass[index] = 0.0;
-            vecCopy(&mut self.positions, index, &p, 0);
+            self.positions[index] = p;
         }
     }
 
     pub fn move_grabbed(&mut self, pos: Vec3, _vel: Vec3) {
         if let Some(index) = self.grab_id {
-            let p = [pos.x, pos.y, pos.z];
-            vecCopy(&mut self.positions, index, &p, 0);
+            self.positions[index] = pos.into();
         }
     }
 
     pub fn end_grab(&mut self, _pos: Vec3, vel: Vec3) {
         if let Some(index) = self.grab_id {
             self.inv_mass[index] = self.grab_inv_mass;
-            let v = [vel.x, vel.y, vel.z];
-            vecCopy(&mut self.velocities, index, &v, 0);
+            self.velocities[index] = vel.into();
         }
         self.grab_id = None;
     }
 
     pub fn update_mesh(&mut self, mesh: &mut Mesh) {
-        let indices = self.visual_indices.iter().map(|i| *i as u32).collect::<Vec<u32>>();
-        let positions = self
-            .visual_vertices
-            .chunks_exact(3)
-            .map(|v| [v[0], v[1], v[2]])
-            .collect::<Vec<[f32; 3]>>();
-
-        mesh.set_indices(Some(Indices::U32(indices)));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.duplicate_vertices();
-        mesh.compute_flat_normals();
+        *mesh = Mesh::from(&*self);
     }
 }
 
 impl From<&SoftBody> for Mesh {
     fn from(sb: &SoftBody) -> Self {
-        // let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        // var geometry = new THREE.BufferGeometry();
-        // geometry.setAttribute('position', new THREE.BufferAttribute(this.pos, 3));
-        // geometry.setIndex(tetMesh.edgeIds);
-        // var lineMaterial = new THREE.LineBasicMaterial({color: 0xffffff, linewidth: 2});
-        // this.tetMesh = new THREE.LineSegments(geometry, lineMaterial);
-        // this.tetMesh.visible = true;
-        // scene.add(this.tetMesh);
-        // this.tetMesh.visible = false;
-
         // generate mesh
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
         let indices = sb.visual_indices.iter().map(|i| *i as u32).collect::<Vec<u32>>();
         let positions = sb
             .visual_vertices
-            .chunks_exact(3)
-            .map(|v| [v[0], v[1], v[2]])
+            .iter()
+            .map(|v| v.to_array())
             .collect::<Vec<[f32; 3]>>();
 
         mesh.set_indices(Some(Indices::U32(indices)));
@@ -616,3 +536,151 @@ impl From<&SoftBody> for Mesh {
     }
 }
 
+// flat shaded triangle list, one mesh vertex per index so each face has its own normal, meshes
+// that already have that layout are written in place so nothing is allocated per frame
+pub fn write_flat_mesh(
+    mesh: &mut Mesh,
+    indices: &[usize],
+    num_points: usize,
+    point: impl Fn(usize) -> Vec3A,
+) {
+    let has_normals = matches!(
+        mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+        Some(VertexAttributeValues::Float32x3(_))
+    );
+    if mesh.count_vertices() != indices.len() || !has_normals {
+        // first frame or a mesh from somewhere else, duplicating keeps its other attributes
+        let positions = (0..num_points)
+            .map(|i| point(i).to_array())
+            .collect::<Vec<[f32; 3]>>();
+        mesh.set_indices(Some(Indices::U32(indices.iter().map(|&i| i as u32).collect())));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.duplicate_vertices();
+        mesh.compute_flat_normals();
+        return;
+    }
+
+    if let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+    {
+        for (dst, &id) in positions.iter_mut().zip(indices.iter()) {
+            *dst = point(id).to_array();
+        }
+    }
+    if let Some(VertexAttributeValues::Float32x3(normals)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL)
+    {
+        for (dst, tri) in normals.chunks_exact_mut(3).zip(indices.chunks_exact(3)) {
+            let p0 = point(tri[0]);
+            let n = (point(tri[1]) - p0).cross(point(tri[2]) - p0).normalize_or_zero();
+            dst.fill(n.to_array());
+        }
+    }
+}
+
+fn to_vec3a(v: &[f32]) -> Vec<Vec3A> {
+    v.chunks_exact(3).map(Vec3A::from_slice).collect()
+}
+
+fn tet_volume(p: [Vec3A; 4]) -> f32 {
+    (p[1] - p[0]).cross(p[2] - p[0]).dot(p[3] - p[0]) / 6.0
+}
+
+// returns the position changes instead of applying them
+// gamma damps the motion along the gradient since the last substep, see Damping::gamma
+fn edge_correction(
+    positions: &[Vec3A],
+    prev_positions: &[Vec3A],
+    inv_mass: &[f32],
+    ids: [usize; 2],
+    rest_len: f32,
+    alpha: f32,
+    gamma: f32,
+) -> Option<([usize; 2], [Vec3A; 2])> {
+    let w0 = inv_mass[ids[0]];
+    let w1 = inv_mass[ids[1]];
+    let w = w0 + w1;
+    if w == 0.0 {
+        return None;
+    }
+    let diff = positions[ids[0]] - positions[ids[1]];
+    let len = diff.length();
+    if len == 0.0 {
+        return None;
+    }
+    let grad = diff / len;
+    let moved = (positions[ids[0]] - prev_positions[ids[0]])
+        - (positions[ids[1]] - prev_positions[ids[1]]);
+    let s = -(len - rest_len + gamma * grad.dot(moved)) / ((1.0 + gamma) * w + alpha);
+    Some((ids, [grad * (s * w0), grad * (-s * w1)]))
+}
+
+// same as edge_correction for the tet volume constraint
+fn volume_correction(
+    positions: &[Vec3A],
+    prev_positions: &[Vec3A],
+    inv_mass: &[f32],
+    ids: [usize; 4],
+    rest_vol: f32,
+    alpha: f32,
+    gamma: f32,
+) -> Option<([usize; 4], [Vec3A; 4])> {
+    let p = ids.map(|id| positions[id]);
+    let mut grads = [Vec3A::ZERO; 4];
+    let mut w = 0.0;
+    for j in 0..4 {
+        let p0 = p[VOLUME_ID_ORDER[j][0]];
+        let p1 = p[VOLUME_ID_ORDER[j][1]];
+        let p2 = p[VOLUME_ID_ORDER[j][2]];
+        grads[j] = (p1 - p0).cross(p2 - p0) / 6.0;
+        w += inv_mass[ids[j]] * grads[j].length_squared();
+    }
+    if w == 0.0 {
+        return None;
+    }
+
+    let moved = (0..4)
+        .map(|j| grads[j].dot(p[j] - prev_positions[ids[j]]))
+        .sum::<f32>();
+    let s = -(tet_volume(p) - rest_vol + gamma * moved) / ((1.0 + gamma) * w + alpha);
+    let mut deltas = [Vec3A::ZERO; 4];
+    for j in 0..4 {
+        deltas[j] = grads[j] * (s * inv_mass[ids[j]]);
+    }
+    Some((ids, deltas))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a single tet dropped through a cloth square in one substep
+    #[test]
+    fn solve_ccd_stops_particles_at_cloth() {
+        let mesh = Mesh::from(shape::Plane {
+            size: 2.0,
+            subdivisions: 1,
+        });
+        let mut cloth = Cloth::new(&mesh, 0.0, &Transform::IDENTITY, &[]);
+        cloth.solve_ccd();
+
+        let tet_vertices = vec![0.0, 0.1, 0.0, 0.1, 0.1, 0.0, 0.0, 0.2, 0.0, 0.0, 0.1, 0.1];
+        let tet = TetMesh {
+            vertices: tet_vertices.clone(),
+            indices: vec![0, 1, 2],
+            tet_vertices,
+            tet_indices: vec![0, 1, 2, 3],
+            tet_edge_ids: vec![0, 1, 0, 2, 0, 3, 1, 2, 1, 3, 2, 3],
+        };
+        let mut sb = SoftBody::new(&tet, 0.0, 0.0);
+        sb.prev_positions = sb.positions.clone();
+        for p in sb.positions.iter_mut() {
+            p.y -= 1.0;
+        }
+
+        sb.solve_ccd(&mut cloth);
+        for p in sb.positions.iter() {
+            assert!(p.y >= 0.0, "tunneled to {}", p.y);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use bevy::prelude::*;
+
+// Continuous collision detection, every point moves linearly from sweep[0] (start of the substep)
+// to sweep[1] (end of the substep), toi is returned as a fraction of the substep
+
+const MAX_ADVANCE_STEPS: usize = 32;
+
+pub fn closest_point_on_triangle(p: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    // Real-Time Collision Detection, Ericson 5.1.5
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = p - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = p - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    let denom = 1.0 / (va + vb + vc);
+    if !denom.is_finite() {
+        // degenerate triangle
+        return a;
+    }
+    a + ab * (vb * denom) + ac * (vc * denom)
+}
+
+pub fn closest_points_on_segments(p0: Vec3, p1: Vec3, q0: Vec3, q1: Vec3) -> (Vec3, Vec3) {
+    // Real-Time Collision Detection, Ericson 5.1.9
+    let d1 = p1 - p0;
+    let d2 = q1 - q0;
+    let r = p0 - q0;
+    let a = d1.dot(d1);
+    let e = d2.dot(d2);
+    let f = d2.dot(r);
+
+    if a <= f32::EPSILON && e <= f32::EPSILON {
+        return (p0, q0);
+    }
+
+    let (s, t) = if a <= f32::EPSILON {
+        (0.0, (f / e).clamp(0.0, 1.0))
+    } else {
+        let c = d1.dot(r);
+        if e <= f32::EPSILON {
+            ((-c / a).clamp(0.0, 1.0), 0.0)
+        } else {
+            let b = d1.dot(d2);
+            let denom = a * e - b * b;
+            let s = if denom != 0.0 {
+                ((b * f - c * e) / denom).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let t = (b * s + f) / e;
+            if t < 0.0 {
+                ((-c / a).clamp(0.0, 1.0), 0.0)
+            } else if t > 1.0 {
+                (((b - c) / a).clamp(0.0, 1.0), 1.0)
+            } else {
+                (s, t)
+            }
+        }
+    };
+
+    (p0 + d1 * s, q0 + d2 * t)
+}
+
+fn lerp(sweep: [Vec3; 2], t: f32) -> Vec3 {
+    sweep[0].lerp(sweep[1], t)
+}
+
+fn max_move(sweeps: &[[Vec3; 2]]) -> f32 {
+    sweeps
+        .iter()
+        .map(|s| s[0].distance(s[1]))
+        .fold(0.0, f32::max)
+}
+
+// conservative advancement, dist changes at most max_rel_move over the whole substep so stepping
+// by the current gap can never skip past the contact
+fn conservative_toi(dist_at: impl Fn(f32) -> f32, max_rel_move: f32, thickness: f32) -> Option<f32> {
+    if max_rel_move <= 0.0 {
+        return None;
+    }
+
+    let d0 = dist_at(0.0);
+    if d0 <= thickness {
+        // already in contact, the pair is held where it started unless the gap is opening,
+        // letting it close in a little every substep would tunnel eventually
+        if d0 <= f32::EPSILON {
+            // no gap left to tell which side it's on
+            return Some(0.0);
+        }
+        // the distance is unsigned, so look before the pair could have crossed
+        let t = (0.5 * d0 / max_rel_move).min(1.0);
+        return if dist_at(t) < d0 { Some(0.0) } else { None };
+    }
+    let target = thickness;
+
+    let mut t = 0.0;
+    let mut d = d0;
+    for _ in 0..MAX_ADVANCE_STEPS {
+        t += (d - target) / max_rel_move;
+        if t >= 1.0 {
+            return None;
+        }
+        d = dist_at(t);
+        if d <= target {
+            return Some(t);
+        }
+    }
+    // didn't converge, still safe since we never stepped past the contact
+    Some(t)
+}
+
+pub fn point_triangle_toi(
+    p: [Vec3; 2],
+    a: [Vec3; 2],
+    b: [Vec3; 2],
+    c: [Vec3; 2],
+    thickness: f32,
+) -> Option<f32> {
+    let max_rel_move = max_move(&[p]) + max_move(&[a, b, c]);
+    conservative_toi(
+        |t| {
+            let pt = lerp(p, t);
+            pt.distance(closest_point_on_triangle(pt, lerp(a, t), lerp(b, t), lerp(c, t)))
+        },
+        max_rel_move,
+        thickness,
+    )
+}
+
+pub fn edge_edge_toi(
+    p0: [Vec3; 2],
+    p1: [Vec3; 2],
+    q0: [Vec3; 2],
+    q1: [Vec3; 2],
+    thickness: f32,
+) -> Option<f32> {
+    let max_rel_move = max_move(&[p0, p1]) + max_move(&[q0, q1]);
+    conservative_toi(
+        |t| {
+            let (a, b) =
+                closest_points_on_segments(lerp(p0, t), lerp(p1, t), lerp(q0, t), lerp(q1, t));
+            a.distance(b)
+        },
+        max_rel_move,
+        thickness,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THICKNESS: f32 = 0.01;
+
+    fn triangle() -> [[Vec3; 2]; 3] {
+        [
+            Vec3::new(-1.0, 0.0, -1.0),
+            Vec3::new(1.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ]
+        .map(|p| [p, p])
+    }
+
+    #[test]
+    fn point_through_triangle_stops_at_thickness() {
+        let [a, b, c] = triangle();
+        let p = [Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0)];
+        let t = point_triangle_toi(p, a, b, c, THICKNESS).expect("tunneled");
+        let y = lerp(p, t).y;
+        assert!((0.0..=THICKNESS * 1.01).contains(&y), "stopped at {}", y);
+    }
+
+    #[test]
+    fn point_missing_triangle_is_free() {
+        let [a, b, c] = triangle();
+        let p = [Vec3::new(2.0, 1.0, 0.0), Vec3::new(2.0, -1.0, 0.0)];
+        assert_eq!(point_triangle_toi(p, a, b, c, THICKNESS), None);
+    }
+
+    #[test]
+    fn touching_point_is_held_until_it_separates() {
+        let [a, b, c] = triangle();
+        let start = Vec3::new(0.0, 0.5 * THICKNESS, 0.0);
+        let closing = [start, start - Vec3::Y * 0.1];
+        assert_eq!(point_triangle_toi(closing, a, b, c, THICKNESS), Some(0.0));
+        let separating = [start, start + Vec3::Y * 0.1];
+        assert_eq!(point_triangle_toi(separating, a, b, c, THICKNESS), None);
+    }
+}
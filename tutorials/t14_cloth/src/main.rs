@@ -1,8 +1,9 @@
 mod assets;
 mod bodies;
 mod camera_grabber;
+mod ccd;
+mod energy;
 mod intersect;
-mod math;
 mod resources;
 mod spatial_hash;
 mod state;
@@ -11,6 +12,7 @@ mod text_overlay;
 use assets::*;
 use bodies::*;
 use camera_grabber::*;
+use energy::*;
 use resources::*;
 use state::*;
 use text_overlay::*;
@@ -18,6 +20,7 @@ use text_overlay::*;
 use bevy_atmosphere::prelude::*;
 
 use bevy::{
+    ecs::system::SystemParam,
     pbr::{
         wireframe::{Wireframe, WireframePlugin},
         NotShadowCaster, CascadeShadowConfigBuilder, NotShadowReceiver,
@@ -35,18 +38,28 @@ fn main() {
         .add_plugin(MeshAssetsPlugin)
         .add_plugin(StatePlugin)
         .add_plugin(TextOverlayPlugin)
+        .add_plugin(EnergyDiagnosticsPlugin)
         .add_plugin(CameraGrabberPlugin)
         .add_plugin(AtmospherePlugin)
         .add_plugin(WireframePlugin)
         .add_asset::<SoftBody>()
         .add_asset::<Cloth>()
+        .add_asset::<Rope>()
         //.insert_resource(ClearColor(Color::BLACK))
         .init_resource::<DragonAssets>()
         .init_resource::<Config>()
         .add_startup_system(setup)
         .add_system(spawn_cloth.in_schedule(OnEnter(AppState::Playing)))
+        .add_system(spawn_sack.in_schedule(OnEnter(AppState::Playing)))
+        .add_system(spawn_balloon.in_schedule(OnEnter(AppState::Playing)))
+        .add_system(spawn_ropes.in_schedule(OnEnter(AppState::Playing)))
         //.add_system(spawn_dragon.in_schedule(OnEnter(AppState::Playing)))
         .add_system(simulate.in_set(OnUpdate(AppState::Playing)))
+        .add_system(
+            simulate_ropes
+                .in_set(OnUpdate(AppState::Playing))
+                .after(simulate),
+        )
         //.add_system(fix_added_softbody.in_set(OnUpdate(AppState::Playing)).before(simulate_softbody))
         // debug
         .add_system(spawn_debug_children.in_schedule(OnEnter(DebugState::On)))
@@ -57,6 +70,7 @@ fn main() {
         )
         .add_system(remove_debug_children.in_schedule(OnExit(DebugState::On)))
         .register_type::<Config>()
+        .register_type::<Damping>()
         .register_type::<Ball>()
         .register_type::<Velocity>()
         .register_type::<SoftBody>()
@@ -162,18 +176,33 @@ fn setup(
 
 fn spawn_cloth(
     mut commands: Commands,
+    asset_server: Res<AssetServer>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut cloth: ResMut<Assets<Cloth>>,
+    config: Res<Config>,
 ) {
     info!("Spawning cloth");
 
     let subdivisions = 20;
-    let mesh = Mesh::from(shape::Plane {
+    let mut mesh = Mesh::from(shape::Plane {
         size: 1.0,
         subdivisions,
         ..default()
     });
+
+    // paint a heavy, floppy and damped hem, the gradient runs away from the pinned edge
+    let hem = mesh
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+        .and_then(|a| a.as_float3())
+        .unwrap()
+        .iter()
+        .map(|p| {
+            let t = p[2] + 0.5;
+            [t, t, t, 1.0]
+        })
+        .collect::<Vec<_>>();
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, hem);
     
     let offset = Transform {  
         translation: Vec3::new(0., 2.0, 0.),
@@ -185,17 +214,28 @@ fn spawn_cloth(
     let x_vertex_count = subdivisions + 2;    
     let corner_index = ((z_vertex_count - 1) * (x_vertex_count - 1)) as usize;
 
-    let c = Cloth::new( &mesh, 0.9, &offset, &[0,  corner_index] );
+    let mut c = Cloth::new( &mesh, 0.9, &offset, &[0,  corner_index] );
+    c.set_material(BodyMaterial::COTTON);
+    c.damping = config.damping;
+    let paint = MaterialPaint::from_vertex_colors(
+        c.vertex_colors().unwrap(),
+        PaintSample::default(),
+        PaintSample {
+            compliance: 10.0,
+            damping: 1.0,
+            density: 2.0,
+        },
+    );
+    c.paint(&paint);
 
     commands.spawn((
         PbrBundle {
             // mesh will be replaced every frame
-            mesh: meshes.add(mesh),
+            mesh: meshes.add(Mesh::from(&c)),
             material: materials.add(StandardMaterial {
                 base_color: Color::ORANGE,
-                perceptual_roughness: 0.5,
-                cull_mode: None,
-                metallic: 0.5,
+                base_color_texture: Some(asset_server.load("checker_red.png")),
+                perceptual_roughness: 0.9,
                 ..default()
             }),
             transform: Transform::from_xyz(0., 2.0, 0.),
@@ -208,6 +248,235 @@ fn spawn_cloth(
     ));
 }
 
+// two panels sewn together along their borders
+fn spawn_sack(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cloth: ResMut<Assets<Cloth>>,
+    config: Res<Config>,
+) {
+    info!("Spawning sack");
+
+    let subdivisions = 10;
+    let mesh = Mesh::from(shape::Plane {
+        size: 0.6,
+        subdivisions,
+    });
+
+    // shape::Plane lays vertices out row by row
+    let vertex_count = (subdivisions + 2) as usize;
+    let border = (0..vertex_count * vertex_count)
+        .filter(|i| {
+            let (x, z) = (i % vertex_count, i / vertex_count);
+            x == 0 || z == 0 || x == vertex_count - 1 || z == vertex_count - 1
+        })
+        .collect::<Vec<_>>();
+
+    let front = ClothPanel {
+        mesh: &mesh,
+        offset: Transform::from_xyz(1.5, 1.5, 0.1)
+            .with_rotation(Quat::from_rotation_x(FRAC_PI_2)),
+    };
+    let back = ClothPanel {
+        mesh: &mesh,
+        offset: Transform::from_xyz(1.5, 1.5, -0.1)
+            .with_rotation(Quat::from_rotation_x(-FRAC_PI_2)),
+    };
+    let seam = Seam {
+        panel_a: 0,
+        a: border.clone(),
+        panel_b: 1,
+        b: border
+            .iter()
+            .map(|i| {
+                // the back panel is flipped over, mirror the rows
+                let (x, z) = (i % vertex_count, i / vertex_count);
+                (vertex_count - 1 - z) * vertex_count + x
+            })
+            .collect(),
+    };
+
+    let mut c = Cloth::sewn(&[front, back], &[seam], 0.9, &[]);
+    c.set_material(BodyMaterial::COTTON);
+    c.damping = config.damping;
+
+    commands.spawn((
+        PbrBundle {
+            // mesh will be replaced every frame
+            mesh: meshes.add(Mesh::from(&c)),
+            material: materials.add(StandardMaterial {
+                base_color: Color::TEAL,
+                perceptual_roughness: 0.9,
+                ..default()
+            }),
+            transform: Transform::from_xyz(1.5, 1.5, 0.),
+            ..default()
+        },
+        cloth.add(c),
+        NotShadowReceiver,
+        Name::new("Sack"),
+    ));
+}
+
+// closed sphere held up by the pressure constraint
+fn spawn_balloon(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cloth: ResMut<Assets<Cloth>>,
+    config: Res<Config>,
+) {
+    info!("Spawning balloon");
+
+    let mesh = Mesh::try_from(shape::Icosphere {
+        radius: 0.3,
+        subdivisions: 3,
+    })
+    .unwrap();
+
+    let mut c = Cloth::new(&mesh, 0.9, &Transform::from_xyz(-1.5, 1.5, 0.), &[]);
+    c.pressure = 1.5;
+    c.double_sided = false;
+    c.set_material(BodyMaterial::RUBBER);
+    c.damping = config.damping;
+
+    commands.spawn((
+        PbrBundle {
+            // mesh will be replaced every frame
+            mesh: meshes.add(Mesh::from(&c)),
+            material: materials.add(StandardMaterial {
+                base_color: Color::RED,
+                perceptual_roughness: 0.3,
+                ..default()
+            }),
+            transform: Transform::from_xyz(-1.5, 1.5, 0.),
+            ..default()
+        },
+        cloth.add(c),
+        NotShadowReceiver,
+        Name::new("Balloon"),
+    ));
+}
+
+// a hose hanging from a hook to a ball you can grab, draped over a second ball, and a cable
+// swinging from the same hook
+fn spawn_ropes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut ropes: ResMut<Assets<Rope>>,
+    config: Res<Config>,
+) {
+    info!("Spawning ropes");
+
+    let hook_pos = Vec3::new(0.8, 2.2, 1.0);
+    let hook = commands
+        .spawn((
+            PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Cube { size: 0.08 })),
+                material: materials.add(Color::DARK_GRAY.into()),
+                transform: Transform::from_translation(hook_pos),
+                ..default()
+            },
+            Name::new("Hook"),
+        ))
+        .id();
+
+    let handle_radius = 0.1;
+    let handle_pos = Vec3::new(2.2, 1.6, 1.0);
+    let handle = commands
+        .spawn((
+            PbrBundle {
+                mesh: meshes.add(
+                    Mesh::try_from(shape::Icosphere {
+                        radius: handle_radius,
+                        subdivisions: 3,
+                    })
+                    .unwrap(),
+                ),
+                material: materials.add(Color::YELLOW.into()),
+                transform: Transform::from_translation(handle_pos),
+                ..default()
+            },
+            Ball(handle_radius),
+            Velocity::default(),
+            Name::new("Rope Handle"),
+        ))
+        .id();
+
+    // the hose starts out sagging through it and gets pushed out over the top
+    let obstacle_radius = 0.25;
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(
+                Mesh::try_from(shape::Icosphere {
+                    radius: obstacle_radius,
+                    subdivisions: 3,
+                })
+                .unwrap(),
+            ),
+            material: materials.add(Color::SILVER.into()),
+            transform: Transform::from_xyz(1.5, 1.2, 1.0),
+            ..default()
+        },
+        Ball(obstacle_radius),
+        Velocity::default(),
+        Name::new("Rope Obstacle"),
+    ));
+
+    let mut hose = Rope::new(&Rope::hanging_points(hook_pos, handle_pos, 0.6, 40), 0.025);
+    hose.bending = true;
+    hose.set_material(BodyMaterial::RUBBER);
+    hose.damping = config.damping;
+
+    commands.spawn((
+        PbrBundle {
+            // mesh will be replaced every frame
+            mesh: meshes.add(Mesh::from(&hose)),
+            material: materials.add(StandardMaterial {
+                base_color: Color::GREEN,
+                perceptual_roughness: 0.4,
+                ..default()
+            }),
+            ..default()
+        },
+        ropes.add(hose),
+        RopeAttachments {
+            start: Some((hook, Vec3::ZERO)),
+            // on the side facing the hook so the hose doesn't start inside the ball
+            end: Some((handle, Vec3::new(-handle_radius, 0., 0.))),
+        },
+        Name::new("Hose"),
+    ));
+
+    // starts out straight and sideways so it swings down
+    let mut cable = Rope::new(
+        &Rope::hanging_points(hook_pos, hook_pos - Vec3::new(1.0, 0., 0.), 0.0, 30),
+        0.01,
+    );
+    cable.damping = config.damping;
+
+    commands.spawn((
+        PbrBundle {
+            // mesh will be replaced every frame
+            mesh: meshes.add(Mesh::from(&cable)),
+            material: materials.add(StandardMaterial {
+                base_color: Color::BLACK,
+                perceptual_roughness: 0.6,
+                ..default()
+            }),
+            ..default()
+        },
+        ropes.add(cable),
+        RopeAttachments {
+            start: Some((hook, Vec3::ZERO)),
+            end: None,
+        },
+        Name::new("Cable"),
+    ));
+}
+
 #[allow(dead_code)]
 fn spawn_dragon(
     mut commands: Commands,
@@ -216,11 +485,29 @@ fn spawn_dragon(
     dragon_assets: Res<DragonAssets>,
     mut tet_meshes: ResMut<Assets<TetMesh>>,
     mut softbodies: ResMut<Assets<SoftBody>>,
+    config: Res<Config>,
 ) {
     info!("Spawning dragon");
 
     let dragon = tet_meshes.get_mut(&dragon_assets.tet_mesh).unwrap();
-    let sb = SoftBody::new(dragon, 20., 0.0);
+    let mut sb = SoftBody::new(dragon, 20., 0.0);
+    sb.damping = config.damping;
+    // squishy belly, stiff limbs
+    let paint = MaterialPaint::radial(
+        &sb.particle_positions(),
+        sb.center_of_mass(),
+        0.3,
+        PaintSample {
+            compliance: 4.0,
+            damping: 2.0,
+            density: 1.0,
+        },
+        PaintSample {
+            compliance: 0.25,
+            ..default()
+        },
+    );
+    sb.paint(&paint);
     let mesh_handle = meshes.add(Mesh::from(&sb));
     let sb_handle = softbodies.add(sb);
 
@@ -256,6 +543,8 @@ fn simulate(
     mut meshes: ResMut<Assets<Mesh>>,
     mut softbodies: ResMut<Assets<SoftBody>>,
     mut cloths: ResMut<Assets<Cloth>>,
+    mut last_damping: Local<Option<Damping>>,
+    mut step_energy: ResMut<StepEnergy>,
 ) {
     let sdt = time.delta_seconds() / config.sub_steps as f32;
 
@@ -264,7 +553,21 @@ fn simulate(
         return;
     }
 
-    for _step in 0..config.sub_steps {
+    // bodies get the config damping when spawned, only edits are pushed to existing ones so
+    // a body can still be given its own
+    if last_damping.replace(config.damping).is_some_and(|last| last != config.damping) {
+        for (mut _trans, sb_handle, _mesh_handle) in query_softbody.iter_mut() {
+            let sb = softbodies.get_mut(sb_handle).unwrap();
+            sb.damping = config.damping;
+        }
+
+        for (mut _trans, cloth_handle, _mesh_handle) in query_cloth.iter_mut() {
+            let cloth = cloths.get_mut(cloth_handle).unwrap();
+            cloth.damping = config.damping;
+        }
+    }
+
+    for step in 0..config.sub_steps as usize {
         for (mut _trans, sb_handle, _mesh_handle) in query_softbody.iter_mut() {
             let sb = softbodies.get_mut(sb_handle).unwrap();
             sb.pre_solve(sdt, config.gravity);
@@ -285,6 +588,21 @@ fn simulate(
             cloth.solve(sdt);
         }
 
+        if config.ccd {
+            for (mut _trans, cloth_handle, _mesh_handle) in query_cloth.iter_mut() {
+                let cloth = cloths.get_mut(cloth_handle).unwrap();
+                cloth.solve_ccd();
+            }
+
+            for (mut _trans, sb_handle, _mesh_handle) in query_softbody.iter_mut() {
+                let sb = softbodies.get_mut(sb_handle).unwrap();
+                for (_trans, cloth_handle, _mesh_handle) in query_cloth.iter() {
+                    let cloth = cloths.get_mut(cloth_handle).unwrap();
+                    sb.solve_ccd(cloth);
+                }
+            }
+        }
+
         for (mut _trans, sb_handle, _mesh_handle) in query_softbody.iter_mut() {
             let sb = softbodies.get_mut(sb_handle).unwrap();
             sb.post_solve(sdt);
@@ -294,6 +612,19 @@ fn simulate(
             let cloth = cloths.get_mut(cloth_handle).unwrap();
             cloth.post_solve(sdt);
         }
+
+        let energy = query_softbody
+            .iter()
+            .filter_map(|(_, handle, _)| softbodies.get(handle))
+            .map(|sb| sb.energy(config.gravity))
+            .chain(
+                query_cloth
+                    .iter()
+                    .filter_map(|(_, handle, _)| cloths.get(handle))
+                    .map(|cloth| cloth.energy(config.gravity)),
+            )
+            .sum();
+        step_energy.add(step, energy);
     }
 
     // update mesh, kind of hacky
@@ -316,6 +647,87 @@ fn simulate(
     }
 }
 
+type RopeData = (
+    &'static mut Transform,
+    &'static Handle<Rope>,
+    &'static Handle<Mesh>,
+    &'static RopeAttachments,
+);
+
+// the ropes with the asset store they live in
+#[derive(SystemParam)]
+struct Ropes<'w, 's> {
+    query: Query<'w, 's, RopeData>,
+    ropes: ResMut<'w, Assets<Rope>>,
+}
+
+// after the soft bodies and cloth, the ropes only collide with the balls
+fn simulate_ropes(
+    mut ropes: Ropes,
+    // what the ends hang from and the balls they hit
+    query_others: Query<(&Transform, Option<&Ball>), Without<Handle<Rope>>>,
+    time: Res<Time>,
+    config: Res<Config>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut last_damping: Local<Option<Damping>>,
+    mut step_energy: ResMut<StepEnergy>,
+) {
+    let sdt = time.delta_seconds() / config.sub_steps as f32;
+
+    // zero time blows up on startup
+    if sdt == 0. {
+        return;
+    }
+
+    let spheres = query_others
+        .iter()
+        .filter_map(|(trans, ball)| Some((trans.translation, ball?.0)))
+        .collect::<Vec<_>>();
+
+    // like soft bodies and cloth, ropes only get damping edits after they spawn
+    let damping_changed = last_damping
+        .replace(config.damping)
+        .is_some_and(|last| last != config.damping);
+
+    for (mut trans, rope_handle, mesh_handle, attachments) in ropes.query.iter_mut() {
+        let Some(rope) = ropes.ropes.get_mut(rope_handle) else {
+            continue;
+        };
+        if damping_changed {
+            rope.damping = config.damping;
+        }
+
+        // where each attached end is now and where it has to be at the end of the frame
+        let (first, last) = rope.end_indices();
+        let pins = [(first, attachments.start), (last, attachments.end)]
+            .into_iter()
+            .filter_map(|(index, attachment)| {
+                let (entity, offset) = attachment?;
+                let target = query_others.get(entity).ok()?.0.transform_point(offset);
+                Some((index, rope.position(index), target))
+            })
+            .collect::<Vec<_>>();
+
+        for step in 0..config.sub_steps {
+            // spread the move over the substeps, a jump would snap the rope
+            let t = (step + 1) as f32 / config.sub_steps as f32;
+            for &(index, from, to) in pins.iter() {
+                rope.pin(index, from.lerp(to, t));
+            }
+            rope.pre_solve(sdt, config.gravity);
+            rope.solve(sdt);
+            rope.solve_collisions(&spheres);
+            rope.post_solve(sdt);
+            step_energy.add(step as usize, rope.energy(config.gravity));
+        }
+
+        rope.update_transform(&mut trans);
+        if let Some(mesh) = meshes.get_mut(mesh_handle) {
+            rope.update_visual_mesh(&trans, mesh);
+        }
+    }
+}
+
 #[derive(Component)]
 struct TetMeshDebug;
 
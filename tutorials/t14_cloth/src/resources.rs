@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::{prelude::ReflectInspectorOptions, InspectorOptions};
 
+use crate::bodies::Damping;
+
 #[derive(Reflect, Resource, InspectorOptions)]
 #[reflect(Resource, InspectorOptions)]
 pub struct Config {
@@ -10,6 +12,10 @@ pub struct Config {
     #[inspector(min = 0, max = 100)]
     pub sub_steps: u32,
     pub gravity: Vec3,
+    // continuous collision for cloth, slower but fast moving particles no longer tunnel
+    pub ccd: bool,
+    // given to bodies as they spawn, edits are pushed to the existing ones
+    pub damping: Damping,
 }
 
 impl Default for Config {
@@ -19,6 +25,8 @@ impl Default for Config {
             half_size: 10.,
             sub_steps: 20,
             gravity: Vec3::new(0., -9.81, 0.),
+            ccd: false,
+            damping: Damping::default(),
         }
     }
 }
@@ -7,6 +7,7 @@ pub struct SpatialHash {
     pub cell_entries: Vec<usize>,
     pub query_ids: Vec<usize>,
     pub query_size: usize,
+    query_cells: Vec<usize>,
 }
 
 impl Default for SpatialHash {
@@ -25,6 +26,7 @@ impl SpatialHash {
             cell_entries: vec![0; max_num_objects],
             query_ids: vec![0; max_num_objects],
             query_size: 0,
+            query_cells: vec![],
         }
     }
 
@@ -83,14 +85,25 @@ impl SpatialHash {
         let z1 = self.int_coord(pos[2 + nr] + max_dist);
 
         self.query_size = 0;
+        self.query_cells.clear();
 
         for xi in x0..=x1 {
             for yi in y0..=y1 {
                 for zi in z0..=z1 {
                     let h = self.hash_coords(xi, yi, zi);
+                    // cells that hash to the same entry would report their objects twice
+                    if self.query_cells.contains(&h) {
+                        continue;
+                    }
+                    self.query_cells.push(h);
+
                     let start = self.cell_start[h];
                     let end = self.cell_start[h + 1];
                     for i in start..end {
+                        // a dense query can still outgrow the ids, grow instead of dropping candidates
+                        if self.query_size == self.query_ids.len() {
+                            self.query_ids.push(0);
+                        }
                         self.query_ids[self.query_size] = self.cell_entries[i];
                         self.query_size += 1;
                     }
@@ -1,4 +1,7 @@
-use crate::bodies::Cloth;
+use crate::{
+    bodies::Cloth,
+    energy::{ANGULAR_MOMENTUM, KINETIC_ENERGY, LINEAR_MOMENTUM, POTENTIAL_ENERGY, TOTAL_ENERGY},
+};
 
 use super::Keep;
 use bevy::{
@@ -14,7 +17,8 @@ impl Plugin for TextOverlayPlugin {
             .add_startup_system(setup_overlay)
             .add_system(update_fps)
             .add_system(update_verts)
-            .add_system(update_tri_count);
+            .add_system(update_tri_count)
+            .add_system(update_energy);
     }
 }
 
@@ -27,6 +31,9 @@ struct VertCountText;
 #[derive(Component)]
 struct TriCountText;
 
+#[derive(Component)]
+struct EnergyText;
+
 const UI_SIZE: f32 = 20.0;
 
 fn setup_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
@@ -152,6 +159,62 @@ fn setup_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
         TriCountText,
     ));
 
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(70.),
+                    ..Default::default()
+                },
+                align_self: AlignSelf::FlexEnd,
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: "Energy: ".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::GRAY,
+                        },
+                    },
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::BLACK,
+                        },
+                    },
+                    TextSection {
+                        value: "  Momentum: ".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::GRAY,
+                        },
+                    },
+                    TextSection {
+                        value: "".to_string(),
+                        style: TextStyle {
+                            font: ui_font.clone(),
+                            font_size: UI_SIZE,
+                            color: Color::BLACK,
+                        },
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Name::new("Energy"),
+        Keep,
+        EnergyText,
+    ));
+
 }
 
 fn update_fps(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, With<FpsText>>) {
@@ -205,3 +268,25 @@ fn update_tri_count(
 
     }
 }
+
+fn update_energy(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, With<EnergyText>>) {
+    let average = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.average())
+            .unwrap_or_default()
+    };
+    for mut text in query.iter_mut() {
+        text.sections[1].value = format!(
+            "{:.2} J (kinetic {:.2}, potential {:.2})",
+            average(TOTAL_ENERGY),
+            average(KINETIC_ENERGY),
+            average(POTENTIAL_ENERGY),
+        );
+        text.sections[3].value = format!(
+            "linear {:.3}, angular {:.3}",
+            average(LINEAR_MOMENTUM),
+            average(ANGULAR_MOMENTUM),
+        );
+    }
+}